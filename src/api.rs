@@ -1,13 +1,20 @@
 use actix_web::{get, post, put, web, HttpResponse, Responder};
-use crate::balance::get_futures_balance;
+use crate::audit::export_bundle;
 use crate::config::Settings;
-use crate::dto::{OpenOrderRequest, SymbolRequest};
-use crate::leverage::set_leverage;
-use crate::order::{close_all_positions, execute_future_order};
+use crate::dto::{AccountSnapshot, OpenOrderRequest, SymbolRequest};
+use crate::exchange::get_exchange;
 use crate::schedule::get_scheduler;
-use crate::blockchain::{get_blockchain_for, get_last_trade_for, get_all_symbols, BLOCKCHAIN};
+use crate::blockchain::{
+    get_all_symbols, get_archived_operation, get_archived_operations, get_blockchain_for, get_last_trade_for,
+    BLOCKCHAIN,
+};
+use crate::selection::select_candidates;
 use crate::spy::spy_cryptos;
-use crate::monitor::monitor_cryptos;
+use crate::universe::{get_universe, watched_symbols};
+use crate::strategy::get_strategy;
+use crate::monitor::{load_monitor_account, monitor_cryptos};
+use crate::ledger::get_ledger;
+use crate::user_stream::get_account_view;
 
 use std::fmt::Write;
 
@@ -15,7 +22,7 @@ use std::fmt::Write;
 pub async fn post_trades_start() -> impl Responder {
     let scheduler = get_scheduler();
     let mut scheduler = scheduler.lock().unwrap();
-    scheduler.start();
+    scheduler.start(get_exchange());
     HttpResponse::Ok().body("Timer started")
 }
 
@@ -53,11 +60,44 @@ pub async fn get_last_trade_by_symbol(path: web::Path<String>) -> impl Responder
     }
 }
 
+#[get("/trades/archive")]
+pub async fn get_trades_archive(query: web::Query<std::collections::HashMap<String, String>>) -> impl Responder {
+    let symbol = query.get("symbol").map(|s| s.to_uppercase());
+    let operations: Vec<_> = get_archived_operations()
+        .into_iter()
+        .filter(|op| symbol.as_ref().is_none_or(|s| &op.symbol == s))
+        .collect();
+    HttpResponse::Ok().json(operations)
+}
+
+#[get("/trades/archive/{id}")]
+pub async fn get_trades_archive_by_id(path: web::Path<u64>) -> impl Responder {
+    let id = path.into_inner();
+    match get_archived_operation(id) {
+        Some(operation) => HttpResponse::Ok().json(operation),
+        None => HttpResponse::NotFound().body(format!("Operacao arquivada {} nao encontrada", id)),
+    }
+}
+
+#[get("/trades/ledger/export")]
+pub async fn get_ledger_export() -> impl Responder {
+    let Some(ledger) = get_ledger() else {
+        return HttpResponse::NotFound().body("Ledger desabilitado");
+    };
+    match export_bundle(ledger) {
+        Ok(bundle) => HttpResponse::Ok().json(bundle),
+        Err(e) => HttpResponse::InternalServerError().body(format!("Erro: {}", e)),
+    }
+}
+
+#[get("/trades/machine/graph")]
+pub async fn get_machine_graph() -> impl Responder {
+    HttpResponse::Ok().content_type("text/vnd.graphviz").body(get_strategy().graph())
+}
+
 #[get("/trades/balance")]
 pub async fn get_trades_balance() -> impl Responder {
-    let settings = Settings::load();
-
-    match get_futures_balance(&settings.binance).await {
+    match get_exchange().get_futures_balance().await {
         Ok(balances) => {
             let usdt_balance: Vec<_> = balances
                 .into_iter()
@@ -72,11 +112,45 @@ pub async fn get_trades_balance() -> impl Responder {
     }
 }
 
+#[get("/trades/account")]
+pub async fn get_trades_account() -> impl Responder {
+    if let Some(snapshot) = get_account_view().snapshot() {
+        return HttpResponse::Ok().json(snapshot);
+    }
+
+    let exchange = get_exchange();
+    let balances = exchange.get_futures_balance().await;
+    let positions = exchange.get_positions().await;
+
+    match (balances, positions) {
+        (Ok(balances), Ok(positions)) => HttpResponse::Ok().json(AccountSnapshot {
+            source: "rest".to_string(),
+            updated_at: chrono::Utc::now().timestamp_millis() as u64,
+            balances,
+            positions: positions
+                .into_iter()
+                .filter(|p| p.position_amt.parse::<f64>().unwrap_or(0.0) != 0.0)
+                .collect(),
+            open_orders: vec![],
+        }),
+        (Err(e), _) | (_, Err(e)) => {
+            eprintln!("Erro ao consultar conta: {}", e);
+            HttpResponse::InternalServerError().body(format!("Erro: {}", e))
+        }
+    }
+}
+
+#[get("/trades/symbols/{symbol}")]
+pub async fn get_trades_symbol(path: web::Path<String>) -> impl Responder {
+    let symbol = path.into_inner().to_uppercase();
+    match get_exchange().get_symbol_meta(&symbol).await {
+        Ok(meta) => HttpResponse::Ok().json(meta),
+        Err(e) => HttpResponse::NotFound().body(format!("Erro: {}", e)),
+    }
+}
+
 #[post("/trades/order/open")]
 pub async fn post_trades_order(req: web::Json<OpenOrderRequest>) -> impl Responder {
-    let settings = Settings::load();
-    let binance_settings = &settings.binance;
-
     let side = req.side.to_uppercase();
     let symbol = req.symbol.to_uppercase();
 
@@ -84,7 +158,7 @@ pub async fn post_trades_order(req: web::Json<OpenOrderRequest>) -> impl Respond
         return HttpResponse::BadRequest().body("O parâmetro 'side' deve ser 'BUY' ou 'SELL'");
     }
 
-    match execute_future_order(binance_settings, &side, &symbol).await {
+    match get_exchange().execute_future_order(&side, &symbol).await {
         Ok(order) => HttpResponse::Ok().json(order),
         Err(e) => {
             eprintln!("Erro ao enviar ordem para Binance: {}", e);
@@ -95,10 +169,7 @@ pub async fn post_trades_order(req: web::Json<OpenOrderRequest>) -> impl Respond
 
 #[post("/trades/order/close")]
 pub async fn post_close_all_positions(req: web::Json<SymbolRequest>) -> impl Responder {
-    let settings = Settings::load();
-    let binance_settings = &settings.binance;
-
-    match close_all_positions(binance_settings, &req.symbol).await {
+    match get_exchange().close_all_positions(&req.symbol).await {
         Ok(orders) => HttpResponse::Ok().json(orders),
         Err(e) => {
             eprintln!("Erro ao fechar posições: {}", e);
@@ -112,7 +183,7 @@ pub async fn put_leverage(req: web::Json<SymbolRequest>) -> impl Responder {
     let settings = Settings::load();
     let symbol = &req.symbol;
 
-    match set_leverage(&settings.binance, symbol).await {
+    match get_exchange().set_leverage(symbol, settings.binance.leverage).await {
         Ok(response) => HttpResponse::Ok().json(response),
         Err(e) => {
             eprintln!("Erro ao aplicar alavancagem: {}", e);
@@ -129,18 +200,35 @@ pub async fn get_trades_spy() -> impl Responder {
         return HttpResponse::Forbidden().body("Serviço /trades/spy está desativado na configuração");
     }
 
-    let binance_settings = settings.binance.clone();
-    let cryptos = settings.cryptos.clone();
+    let trades = spy_cryptos(&get_exchange(), &settings.binance, watched_symbols()).await;
 
-    let trades = spy_cryptos(
-        &binance_settings.base_url,
-        &binance_settings.interval,
-        binance_settings.limit,
-        cryptos,
+    HttpResponse::Ok().json(trades)
+}
+
+/// The symbols being traded, the duplicates dropped from `cryptos` and
+/// every quarantined symbol with its reason.
+#[get("/trades/universe")]
+pub async fn get_trades_universe() -> impl Responder {
+    HttpResponse::Ok().json(get_universe().report())
+}
+
+/// Scores every symbol of the universe as the scheduler would when choosing
+/// the next chain, with the filters each one passed and why the winner won.
+#[get("/trades/candidates")]
+pub async fn get_trades_candidates() -> impl Responder {
+    let settings = Settings::load();
+    let trades = spy_cryptos(&get_exchange(), &settings.binance, watched_symbols()).await;
+    let report = select_candidates(
+        get_exchange().as_ref(),
+        get_strategy().as_ref(),
+        trades,
+        &get_all_symbols(),
+        &settings.strategy.selection,
+        settings.binance.max_concurrent_fetches,
     )
     .await;
 
-    HttpResponse::Ok().json(trades)
+    HttpResponse::Ok().json(report)
 }
 
 #[get("/trades/chains")]
@@ -170,15 +258,12 @@ pub async fn get_chain_validity(path: web::Path<String>) -> impl Responder {
 pub async fn get_trades_monitor(query: web::Query<std::collections::HashMap<String, String>>) -> impl Responder {
     let settings = Settings::load();
 
-    let trades = spy_cryptos(
-        &settings.binance.base_url,
-        &settings.binance.interval,
-        settings.binance.limit,
-        settings.cryptos.clone(),
-    )
-    .await;
+    let exchange = get_exchange();
+
+    let trades = spy_cryptos(&exchange, &settings.binance, watched_symbols()).await;
 
-    let response = monitor_cryptos(&trades, &settings);
+    let account = load_monitor_account(exchange.as_ref(), &settings).await;
+    let response = monitor_cryptos(&trades, &settings, account);
 
     match query.get("format").map(|f| f.as_str()) {
         Some("text") => {
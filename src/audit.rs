@@ -0,0 +1,274 @@
+use crate::blockchain::{chain_heads, ClosedOperation, TradeBlock, TradeBlockchain};
+use crate::config::LedgerSettings;
+use crate::ledger::{get_ledger, Ledger};
+use crate::signing::{get_signed_since, get_verifying_key, parse_verifying_key, sign, verify};
+use ed25519_dalek::VerifyingKey;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::task::JoinHandle;
+
+/// Serializes checkpoint writers so sequences never repeat.
+static CHECKPOINT_LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct CheckpointLeaf {
+    pub name: String,
+    pub length: usize,
+    pub head: String,
+}
+
+impl CheckpointLeaf {
+    fn digest(&self) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        hasher.update([0u8]);
+        hasher.update(format!("{}:{}:{}", self.name, self.length, self.head));
+        hasher.finalize().into()
+    }
+}
+
+/// Signed Merkle root over the heads of every chain at one point in time,
+/// linked to the previous checkpoint.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Checkpoint {
+    pub sequence: u64,
+    pub timestamp: u64,
+    pub leaves: Vec<CheckpointLeaf>,
+    pub root: String,
+    pub previous_root: String,
+    pub signature: Option<String>,
+}
+
+impl Checkpoint {
+    fn message(&self) -> String {
+        format!("{}:{}:{}:{}", self.sequence, self.timestamp, self.root, self.previous_root)
+    }
+}
+
+/// Binary Merkle tree with domain-separated leaves and nodes; an odd node
+/// is paired with itself.
+pub fn merkle_root(leaves: &[CheckpointLeaf]) -> String {
+    let mut level: Vec<[u8; 32]> = leaves.iter().map(CheckpointLeaf::digest).collect();
+    if level.is_empty() {
+        return hex::encode(Sha256::digest(b""));
+    }
+
+    while level.len() > 1 {
+        level = level
+            .chunks(2)
+            .map(|pair| {
+                let mut hasher = Sha256::new();
+                hasher.update([1u8]);
+                hasher.update(pair[0]);
+                hasher.update(pair.get(1).unwrap_or(&pair[0]));
+                hasher.finalize().into()
+            })
+            .collect();
+    }
+
+    hex::encode(level[0])
+}
+
+/// Appends a checkpoint of the current chains, unless nothing changed
+/// since the last one.
+pub fn write_checkpoint(ledger: &Ledger) -> Result<Option<Checkpoint>, String> {
+    let _guard = CHECKPOINT_LOCK.lock().unwrap();
+
+    let mut leaves: Vec<CheckpointLeaf> = chain_heads()
+        .into_iter()
+        .map(|(name, length, head)| CheckpointLeaf { name, length, head })
+        .collect();
+    leaves.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let last = ledger.load_checkpoints()?.pop();
+    if last.as_ref().map_or(leaves.is_empty(), |c| c.leaves == leaves) {
+        return Ok(None);
+    }
+
+    let mut checkpoint = Checkpoint {
+        sequence: last.as_ref().map(|c| c.sequence + 1).unwrap_or(0),
+        timestamp: SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs(),
+        root: merkle_root(&leaves),
+        leaves,
+        previous_root: last.map(|c| c.root).unwrap_or_else(|| "0".to_string()),
+        signature: None,
+    };
+    checkpoint.signature = sign(&checkpoint.message());
+
+    ledger.append_checkpoint(&checkpoint)?;
+    Ok(Some(checkpoint))
+}
+
+pub fn start_checkpoints(settings: &LedgerSettings) -> JoinHandle<()> {
+    let period = Duration::from_secs(settings.checkpoint_secs.max(1));
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(period);
+        loop {
+            interval.tick().await;
+            let Some(ledger) = get_ledger() else {
+                return;
+            };
+            match write_checkpoint(ledger) {
+                Ok(Some(c)) => println!("Checkpoint {} do ledger: {}", c.sequence, c.root),
+                Ok(None) => {}
+                Err(e) => eprintln!("Erro ao gravar checkpoint do ledger: {}", e),
+            }
+        }
+    })
+}
+
+/// Everything an auditor needs to check the ledger offline.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LedgerBundle {
+    pub created_at: u64,
+    pub public_key: String,
+    /// Unix time (s) from which the operator requires signatures.
+    #[serde(default)]
+    pub signed_since: u64,
+    pub chains: BTreeMap<String, Vec<TradeBlock>>,
+    pub archive: Vec<ClosedOperation>,
+    pub checkpoints: Vec<Checkpoint>,
+}
+
+/// Writes a fresh checkpoint and bundles the on-disk ledger with it.
+pub fn export_bundle(ledger: &Ledger) -> Result<LedgerBundle, String> {
+    let key = get_verifying_key().ok_or("Chave de assinatura do ledger nao carregada")?;
+    write_checkpoint(ledger)?;
+
+    Ok(LedgerBundle {
+        created_at: SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs(),
+        public_key: hex::encode(key.to_bytes()),
+        signed_since: get_signed_since().unwrap_or_default(),
+        chains: ledger.load()?.into_iter().collect(),
+        archive: ledger.load_archive()?,
+        checkpoints: ledger.load_checkpoints()?,
+    })
+}
+
+#[derive(Debug, Default)]
+pub struct VerifyReport {
+    pub chains: usize,
+    pub blocks: usize,
+    pub checkpoints: usize,
+    /// Blocks appended after the last checkpoint.
+    pub uncovered: usize,
+}
+
+/// Checks every chain, signature and checkpoint of `bundle` against `key`.
+/// Blocks written from `signed_since` on must be signed.
+pub fn verify_bundle(bundle: &LedgerBundle, key: &VerifyingKey, signed_since: u64) -> Result<VerifyReport, String> {
+    let mut report = VerifyReport::default();
+    let mut all: Vec<(&str, &[TradeBlock])> = Vec::new();
+
+    for (symbol, blocks) in &bundle.chains {
+        all.push((symbol, blocks));
+    }
+    for (i, op) in bundle.archive.iter().enumerate() {
+        if op.id != i as u64 {
+            return Err(format!("arquivo: operacao {} fora de sequencia", op.id));
+        }
+        all.push((&op.symbol, &op.blocks));
+    }
+
+    for (name, blocks) in &all {
+        TradeBlockchain::from_blocks(blocks.to_vec())
+            .verify_with(Some(key), Some(signed_since))
+            .map_err(|e| format!("{}: {}", name, e))?;
+        report.chains += 1;
+        report.blocks += blocks.len();
+    }
+
+    let mut previous_root = "0".to_string();
+    for (i, checkpoint) in bundle.checkpoints.iter().enumerate() {
+        let label = format!("checkpoint {}", checkpoint.sequence);
+
+        if checkpoint.sequence != i as u64 || checkpoint.previous_root != previous_root {
+            return Err(format!("{}: encadeamento quebrado", label));
+        }
+        if merkle_root(&checkpoint.leaves) != checkpoint.root {
+            return Err(format!("{}: raiz Merkle nao confere", label));
+        }
+        let signed = checkpoint.signature.as_ref().is_some_and(|s| verify(key, &checkpoint.message(), s));
+        if !signed {
+            return Err(format!("{}: assinatura invalida", label));
+        }
+
+        // Each leaf must still be a prefix of some chain in the bundle.
+        for leaf in &checkpoint.leaves {
+            let symbol = match leaf.name.strip_prefix("archive/") {
+                Some(id) => bundle.archive.get(id.parse::<usize>().unwrap_or(usize::MAX)).map(|op| op.symbol.as_str()),
+                None => Some(leaf.name.as_str()),
+            };
+            let found = all.iter().any(|(name, blocks)| {
+                Some(*name) == symbol
+                    && leaf.length > 0
+                    && blocks.get(leaf.length - 1).is_some_and(|b| b.hash == leaf.head)
+            });
+            if !found {
+                return Err(format!("{}: {} alterada apos o checkpoint", label, leaf.name));
+            }
+        }
+
+        previous_root = checkpoint.root.clone();
+        report.checkpoints += 1;
+    }
+
+    let covered: usize = bundle.checkpoints.last().map(|c| c.leaves.iter().map(|l| l.length).sum()).unwrap_or(0);
+    report.uncovered = report.blocks.saturating_sub(covered);
+    Ok(report)
+}
+
+/// `verify-ledger <bundle.json> [--public-key <hex>] [--signed-since <unix>]`.
+/// Needs neither the settings file nor network access.
+pub fn run_cli(args: &[String]) -> std::io::Result<()> {
+    let mut path = None;
+    let mut public_key = None;
+    let mut signed_since = None;
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match (arg.as_str(), path.is_none()) {
+            ("--public-key", _) => match iter.next() {
+                Some(value) => public_key = Some(value.clone()),
+                None => return Err(std::io::Error::other("Missing value for --public-key")),
+            },
+            ("--signed-since", _) => match iter.next().and_then(|v| v.parse::<u64>().ok()) {
+                Some(value) => signed_since = Some(value),
+                None => return Err(std::io::Error::other("Missing or invalid value for --signed-since")),
+            },
+            (value, true) if !value.starts_with("--") => path = Some(value.to_string()),
+            _ => return Err(std::io::Error::other(format!("Unknown or incomplete argument: {}", arg))),
+        }
+    }
+
+    let path = path.ok_or_else(|| std::io::Error::other("Usage: verify-ledger <bundle.json> [--public-key <hex>] [--signed-since <unix>]"))?;
+    let bundle: LedgerBundle = serde_json::from_str(&std::fs::read_to_string(&path)?).map_err(std::io::Error::other)?;
+
+    let key = match &public_key {
+        Some(value) => parse_verifying_key(value),
+        None => {
+            println!("Aviso: usando a chave publica do proprio bundle; passe --public-key para fixar a chave do operador.");
+            parse_verifying_key(&bundle.public_key)
+        }
+    }
+    .map_err(std::io::Error::other)?;
+
+    let signed_since = signed_since.unwrap_or_else(|| {
+        println!("Aviso: usando o inicio das assinaturas do proprio bundle; passe --signed-since para fixa-lo.");
+        bundle.signed_since
+    });
+
+    match verify_bundle(&bundle, &key, signed_since) {
+        Ok(report) => {
+            println!(
+                "Ledger valido: {} cadeias, {} blocos, {} checkpoints ({} blocos apos o ultimo checkpoint)",
+                report.chains, report.blocks, report.checkpoints, report.uncovered
+            );
+            Ok(())
+        }
+        Err(e) => Err(std::io::Error::other(format!("Ledger invalido: {}", e))),
+    }
+}
@@ -0,0 +1,347 @@
+use crate::candle_store::{interval_ms, parse_date, CandleStore};
+use crate::config::Settings;
+use crate::dto::{BalanceResponse, BookTicker, Candlestick, OrderResponse, PositionResponse};
+use crate::exchange::Exchange;
+use crate::leverage::LeverageResponse;
+use crate::order::to_decimal;
+use crate::paper::{PaperExchange, PaperFill};
+use crate::schedule::process_trades;
+use crate::spy::spy_cryptos;
+use crate::strategy::get_strategy;
+use crate::symbols::SymbolMeta;
+use async_trait::async_trait;
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// Market data source that only reveals candles closed before the replay
+/// clock. Order methods are handled by the `PaperExchange` wrapped around it.
+pub struct HistoricalMarket {
+    candles: HashMap<String, Vec<Candlestick>>,
+    now: Mutex<u64>,
+    step_size: f64,
+    min_notional: f64,
+}
+
+impl HistoricalMarket {
+    pub fn new(candles: HashMap<String, Vec<Candlestick>>, step_size: f64, min_notional: f64) -> Self {
+        HistoricalMarket {
+            candles,
+            now: Mutex::new(0),
+            step_size,
+            min_notional,
+        }
+    }
+
+    pub fn set_time(&self, time: u64) {
+        *self.now.lock().unwrap() = time;
+    }
+
+    fn window(&self, symbol: &str, limit: usize) -> Result<&[Candlestick], String> {
+        let candles = self
+            .candles
+            .get(symbol)
+            .ok_or_else(|| format!("No history loaded for {}", symbol))?;
+        let now = *self.now.lock().unwrap();
+
+        let end = candles.partition_point(|c| c.close_time <= now);
+        Ok(&candles[end.saturating_sub(limit)..end])
+    }
+}
+
+#[async_trait]
+impl Exchange for HistoricalMarket {
+    async fn get_candlesticks(&self, symbol: &str, _interval: &str, limit: u32) -> Result<Vec<Candlestick>, String> {
+        Ok(self.window(symbol, limit as usize)?.to_vec())
+    }
+
+    async fn get_candlesticks_between(
+        &self,
+        symbol: &str,
+        _interval: &str,
+        start_time: Option<u64>,
+        end_time: Option<u64>,
+        limit: u32,
+    ) -> Result<Vec<Candlestick>, String> {
+        let end = end_time.unwrap_or(u64::MAX);
+        let matching: Vec<Candlestick> = self
+            .window(symbol, usize::MAX)?
+            .iter()
+            .filter(|c| c.open_time >= start_time.unwrap_or(0) && c.open_time <= end)
+            .cloned()
+            .collect();
+
+        // Same as Binance: oldest first from `start_time`, newest before `end_time` otherwise.
+        let limit = limit as usize;
+        Ok(match start_time {
+            Some(_) => matching.into_iter().take(limit).collect(),
+            None => matching[matching.len().saturating_sub(limit)..].to_vec(),
+        })
+    }
+
+    async fn get_current_price(&self, symbol: &str) -> Result<f64, String> {
+        self.window(symbol, 1)?
+            .last()
+            .and_then(|c| c.close_price.parse::<f64>().ok())
+            .ok_or_else(|| format!("No price for {} at replay time", symbol))
+    }
+
+    /// Just after the replay clock, so the candle closing at it counts as closed.
+    async fn server_time(&self) -> Result<u64, String> {
+        Ok(*self.now.lock().unwrap() + 1)
+    }
+
+    async fn get_symbol_meta(&self, symbol: &str) -> Result<SymbolMeta, String> {
+        let step_size = to_decimal(self.step_size)?;
+
+        Ok(SymbolMeta {
+            symbol: symbol.to_string(),
+            status: "TRADING".to_string(),
+            quantity_precision: step_size.scale(),
+            price_precision: 8,
+            step_size,
+            market_step_size: step_size,
+            min_notional: to_decimal(self.min_notional)?,
+            ..Default::default()
+        })
+    }
+
+    /// Candles carry no order book, so there is no spread to report.
+    async fn get_book_ticker(&self, _symbol: &str) -> Result<Option<BookTicker>, String> {
+        Ok(None)
+    }
+
+    async fn get_positions(&self) -> Result<Vec<PositionResponse>, String> {
+        Err("Historical market has no account".to_string())
+    }
+
+    async fn execute_future_order(&self, _side: &str, _symbol: &str) -> Result<OrderResponse, String> {
+        Err("Historical market has no account".to_string())
+    }
+
+    async fn close_all_positions(&self, _symbol: &str) -> Result<Vec<OrderResponse>, String> {
+        Err("Historical market has no account".to_string())
+    }
+
+    async fn set_leverage(&self, _symbol: &str, _leverage: u32) -> Result<LeverageResponse, String> {
+        Err("Historical market has no account".to_string())
+    }
+
+    async fn place_protective_order(
+        &self,
+        _symbol: &str,
+        _side: &str,
+        _order_type: &str,
+        _stop_price: f64,
+    ) -> Result<OrderResponse, String> {
+        Err("Historical market has no account".to_string())
+    }
+
+    async fn cancel_protective_orders(&self, _symbol: &str) -> Result<(), String> {
+        Err("Historical market has no account".to_string())
+    }
+
+    async fn get_futures_balance(&self) -> Result<Vec<BalanceResponse>, String> {
+        Err("Historical market has no account".to_string())
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct EquityPoint {
+    pub time: u64,
+    pub equity: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BacktestSummary {
+    pub from: u64,
+    pub to: u64,
+    pub symbols: usize,
+    pub steps: usize,
+    pub initial_balance: f64,
+    pub final_equity: f64,
+    pub total_return_pct: f64,
+    pub max_drawdown_pct: f64,
+    pub fills: usize,
+    pub closed_trades: usize,
+    pub win_rate_pct: f64,
+    pub realized_pnl: f64,
+    pub fees_paid: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BacktestReport {
+    pub summary: BacktestSummary,
+    pub trades: Vec<PaperFill>,
+    pub equity_curve: Vec<EquityPoint>,
+}
+
+/// Replays `[from, to)` one closed candle at a time through the same
+/// `spy_cryptos` → `process_trades` cycle the scheduler runs live.
+pub async fn run_backtest(
+    settings: &Settings,
+    candles: HashMap<String, Vec<Candlestick>>,
+    symbols: Vec<String>,
+    from: u64,
+    to: u64,
+) -> Result<BacktestReport, String> {
+    let mut settings = settings.clone();
+    settings.binance.decide = true;
+
+    // Every close time of any loaded series, so no symbol misses a step.
+    let steps: Vec<u64> = candles
+        .values()
+        .flatten()
+        .map(|c| c.close_time)
+        .filter(|t| *t >= from && *t < to)
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect();
+
+    let market = Arc::new(HistoricalMarket::new(candles, settings.backtest.step_size, settings.backtest.min_notional));
+    let paper = Arc::new(PaperExchange::in_memory(market.clone(), &settings));
+    let exchange: Arc<dyn Exchange> = paper.clone();
+
+    let mut equity_curve = Vec::with_capacity(steps.len());
+
+    for time in &steps {
+        market.set_time(*time);
+
+        let trades = spy_cryptos(&exchange, &settings.binance, symbols.clone()).await;
+
+        process_trades(trades, &settings, exchange.as_ref()).await;
+
+        equity_curve.push(EquityPoint {
+            time: *time,
+            equity: paper.equity(),
+        });
+    }
+
+    let trades = paper.fills();
+    let summary = summarize(&settings, &trades, &equity_curve, symbols.len(), from, to);
+
+    Ok(BacktestReport {
+        summary,
+        trades,
+        equity_curve,
+    })
+}
+
+fn summarize(
+    settings: &Settings,
+    trades: &[PaperFill],
+    equity_curve: &[EquityPoint],
+    symbols: usize,
+    from: u64,
+    to: u64,
+) -> BacktestSummary {
+    let initial_balance = settings.paper.balance;
+    let final_equity = equity_curve.last().map(|p| p.equity).unwrap_or(initial_balance);
+
+    let mut peak = initial_balance;
+    let mut max_drawdown = 0.0_f64;
+    for point in equity_curve {
+        peak = peak.max(point.equity);
+        if peak > 0.0 {
+            max_drawdown = max_drawdown.max((peak - point.equity) / peak * 100.0);
+        }
+    }
+
+    let closes: Vec<&PaperFill> = trades.iter().filter(|t| t.realized_pnl != 0.0).collect();
+    let wins = closes.iter().filter(|t| t.realized_pnl > 0.0).count();
+
+    BacktestSummary {
+        from,
+        to,
+        symbols,
+        steps: equity_curve.len(),
+        initial_balance,
+        final_equity,
+        total_return_pct: (final_equity / initial_balance - 1.0) * 100.0,
+        max_drawdown_pct: max_drawdown,
+        fills: trades.len(),
+        closed_trades: closes.len(),
+        win_rate_pct: if closes.is_empty() { 0.0 } else { wins as f64 / closes.len() as f64 * 100.0 },
+        realized_pnl: trades.iter().map(|t| t.realized_pnl).sum(),
+        fees_paid: trades.iter().map(|t| t.fee).sum(),
+    }
+}
+
+/// `backtest --from YYYY-MM-DD --to YYYY-MM-DD [--symbols A,B,C]`
+pub async fn run_cli(args: &[String]) -> std::io::Result<()> {
+    let settings = Settings::load();
+
+    let mut from = None;
+    let mut to = None;
+    let mut symbols = settings.cryptos.clone();
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match (arg.as_str(), iter.next()) {
+            ("--from", Some(value)) => from = Some(value.clone()),
+            ("--to", Some(value)) => to = Some(value.clone()),
+            ("--symbols", Some(value)) => symbols = value.split(',').map(|s| s.trim().to_uppercase()).collect(),
+            _ => return Err(std::io::Error::other(format!("Unknown or incomplete argument: {}", arg))),
+        }
+    }
+
+    let from = parse_date(&from.ok_or_else(|| std::io::Error::other("--from is required"))?).map_err(std::io::Error::other)?;
+    let to = parse_date(&to.ok_or_else(|| std::io::Error::other("--to is required"))?).map_err(std::io::Error::other)?;
+
+    symbols.sort();
+    symbols.dedup();
+
+    let interval = &settings.binance.interval;
+    let step = interval_ms(interval)
+        .ok_or_else(|| std::io::Error::other(format!("Unsupported interval '{}'", interval)))?;
+    // The first step already needs a full `limit` window behind it.
+    let warmup_from = from.saturating_sub(settings.binance.limit as u64 * step);
+
+    let store = CandleStore::new(&settings.history.dir);
+    let strategy = get_strategy();
+    let mut to_load = symbols.clone();
+    to_load.extend(symbols.iter().map(|s| strategy.reference_symbol(s)));
+    to_load.sort();
+    to_load.dedup();
+
+    let mut candles = HashMap::new();
+    for symbol in &to_load {
+        match store.range(symbol, interval, warmup_from, to) {
+            Ok(history) if !history.is_empty() => {
+                candles.insert(symbol.clone(), history);
+            }
+            Ok(_) => eprintln!("Skipping {}: no stored candles, run `download` first", symbol),
+            Err(e) => eprintln!("Skipping {}: {}", symbol, e),
+        }
+    }
+    symbols.retain(|s| {
+        let reference = strategy.reference_symbol(s);
+        let loaded = candles.contains_key(s) && candles.contains_key(&reference);
+        if candles.contains_key(s) && !loaded {
+            eprintln!("Skipping {}: bias reference {} has no stored candles", s, reference);
+        }
+        loaded
+    });
+
+    let report = run_backtest(&settings, candles, symbols, from, to)
+        .await
+        .map_err(std::io::Error::other)?;
+
+    fs::create_dir_all(&settings.backtest.output_dir)?;
+    let output = Path::new(&settings.backtest.output_dir).join(format!("backtest_{}_{}.json", from, to));
+    fs::write(&output, serde_json::to_string_pretty(&report)?)?;
+
+    let s = &report.summary;
+    println!("\nBacktest {} -> {} ({} symbols, {} steps)", from, to, s.symbols, s.steps);
+    println!("Equity: {:.2} -> {:.2} USDT ({:+.2}%)", s.initial_balance, s.final_equity, s.total_return_pct);
+    println!("Max drawdown: {:.2}%", s.max_drawdown_pct);
+    println!(
+        "Fills: {} | Closed trades: {} | Win rate: {:.1}% | Realized PnL: {:.2} | Fees: {:.2}",
+        s.fills, s.closed_trades, s.win_rate_pct, s.realized_pnl, s.fees_paid
+    );
+    println!("Report written to {}", output.display());
+
+    Ok(())
+}
@@ -1,41 +1,44 @@
+use crate::client::get_binance_client;
 use crate::config::BinanceSettings;
-use crate::dto::{Candlestick, ExchangeInfoResponse, LotSizeFilter, LotSizeInfo};
-use reqwest::Client;
+use crate::dto::{BookTicker, Candlestick, ExchangeInfoResponse, PositionResponse, SpotExchangeInfo, Ticker24h};
+use reqwest::Method;
 use serde_json::Value;
-use hmac::{Hmac, Mac};
-use sha2::Sha256;
-use reqwest::header::{HeaderMap, HeaderValue, CONTENT_TYPE};
-use std::time::{SystemTime, UNIX_EPOCH};
-
-type HmacSha256 = Hmac<Sha256>;
 
 pub async fn get_candlesticks(
     base_url: &str,
     symbol: &str,
     interval: &str,
     limit: u32,
+) -> Result<Vec<Candlestick>, String> {
+    get_candlesticks_between(base_url, symbol, interval, None, None, limit).await
+}
+
+pub async fn get_candlesticks_between(
+    base_url: &str,
+    symbol: &str,
+    interval: &str,
+    start_time: Option<u64>,
+    end_time: Option<u64>,
+    limit: u32,
 ) -> Result<Vec<Candlestick>, String> {
     let url = format!("{}/uiKlines", base_url);
 
-    let params = [
-        ("symbol", symbol),
-        ("interval", interval),
-        ("limit", &limit.to_string()),
+    let mut params = vec![
+        ("symbol", symbol.to_string()),
+        ("interval", interval.to_string()),
+        ("limit", limit.to_string()),
     ];
+    if let Some(start_time) = start_time {
+        params.push(("startTime", start_time.to_string()));
+    }
+    if let Some(end_time) = end_time {
+        params.push(("endTime", end_time.to_string()));
+    }
 
-    let client = Client::new();
-
-    let response = client
-        .get(&url)
-        .query(&params)
-        .send()
-        .await
-        .map_err(|e| format!("Erro na requisicao HTTP: {:?}", e))?;
-
-    let raw_data = response
-        .json::<Vec<Vec<Value>>>()
+    let raw_data: Vec<Vec<Value>> = get_binance_client()
+        .get(&url, &params)
         .await
-        .map_err(|e| format!("Erro ao desserializar JSON da Binance: {:?}", e))?;
+        .map_err(|e| format!("Erro ao buscar candles da Binance: {}", e))?;
 
     let candlesticks: Vec<Candlestick> = raw_data
         .into_iter()
@@ -68,118 +71,73 @@ pub async fn get_current_price(
     settings: &BinanceSettings,
     symbol: &str,
 ) -> Result<f64, String> {
-    let url = format!("{}/ticker/price?symbol={}", settings.future_url, symbol);
-
-    let client = Client::new();
+    let url = format!("{}/ticker/price", settings.future_url);
 
-    let res = client
-        .get(&url)
-        .send()
+    let data: Value = get_binance_client()
+        .get(&url, &[("symbol", symbol.to_string())])
         .await
-        .map_err(|e| format!("Erro ao obter preco atual: {:?}", e))?;
-
-    if res.status().is_success() {
-        let data: Value = res
-            .json()
-            .await
-            .map_err(|e| format!("Erro ao interpretar resposta do preco: {:?}", e))?;
-
-        data["price"]
-            .as_str()
-            .ok_or("Campo 'price' ausente".to_string())?
-            .parse::<f64>()
-            .map_err(|_| "Erro ao converter preco para f64".to_string())
-    } else {
-        let err = res
-            .text()
-            .await
-            .unwrap_or_else(|_| "Erro desconhecido".to_string());
-        Err(format!("Erro ao buscar preco: {}", err))
-    }
+        .map_err(|e| format!("Erro ao buscar preco: {}", e))?;
+
+    data["price"]
+        .as_str()
+        .ok_or("Campo 'price' ausente".to_string())?
+        .parse::<f64>()
+        .map_err(|_| "Erro ao converter preco para f64".to_string())
 }
 
-pub async fn get_lot_size_info(
-    settings: &BinanceSettings,
-    symbol: &str,
-) -> Result<LotSizeInfo, String> {
-    let url = format!("{}/exchangeInfo?symbol={}", settings.future_url, symbol);
-    let client = Client::new();
+pub async fn get_book_ticker(settings: &BinanceSettings, symbol: &str) -> Result<BookTicker, String> {
+    let url = format!("{}/ticker/bookTicker", settings.future_url);
 
-    let res = client
-        .get(&url)
-        .send()
+    get_binance_client()
+        .get(&url, &[("symbol", symbol.to_string())])
         .await
-        .map_err(|e| format!("Erro ao obter exchangeInfo: {:?}", e))?;
-
-    if !res.status().is_success() {
-        let err = res
-            .text()
-            .await
-            .unwrap_or_else(|_| "Erro desconhecido".to_string());
-        return Err(format!("Erro da Binance: {}", err));
-    }
+        .map_err(|e| format!("Erro ao buscar book ticker: {}", e))
+}
 
-    let data: ExchangeInfoResponse = res
-        .json()
+/// Rules for every futures contract. The futures endpoint ignores a
+/// `symbol` filter, so this is always the full list.
+pub async fn get_exchange_info(settings: &BinanceSettings) -> Result<ExchangeInfoResponse, String> {
+    let url = format!("{}/exchangeInfo", settings.future_url);
+
+    get_binance_client()
+        .get(&url, &[])
         .await
-        .map_err(|e| format!("Erro ao interpretar exchangeInfo: {:?}", e))?;
-
-    for filter in &data.symbols.first().ok_or("Simbolo nao encontrado")?.filters {
-        if let LotSizeFilter::LotSize { step_size } = filter {
-            return step_size
-                .parse::<f64>()
-                .map(|step| LotSizeInfo { step_size: step })
-                .map_err(|_| "Erro ao converter stepSize para f64".to_string());
-        }
-    }
+        .map_err(|e| format!("Erro ao obter exchangeInfo: {}", e))
+}
+
+/// Every spot pair; the candles the strategy reads come from spot.
+pub async fn get_spot_exchange_info(settings: &BinanceSettings) -> Result<SpotExchangeInfo, String> {
+    let url = format!("{}/exchangeInfo", settings.base_url);
 
-    Err("Filtro LOT_SIZE nao encontrado".to_string())
+    get_binance_client()
+        .get(&url, &[])
+        .await
+        .map_err(|e| format!("Erro ao obter exchangeInfo spot: {}", e))
 }
 
-pub async fn get_unrealized_profit(
-    binance: &BinanceSettings,
-    symbol: &str,
-    api_key: &str,
-    secret: &str,
-) -> Result<Option<f64>, String> {
-    let ts = now_ms();
-    let query = format!("timestamp={}", ts);
-    let sig = sign(&query, secret);
-    let url = format!("{}/positionRisk?{}&signature={}", binance.future_url_v2, query, sig);
-
-    let mut headers = HeaderMap::new();
-    headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
-    headers.insert("X-MBX-APIKEY", HeaderValue::from_str(api_key).unwrap());
-
-    let client = Client::new();
-    let res = client.get(&url).headers(headers).send().await
-        .map_err(|e| format!("Erro HTTP: {:?}", e))?;
-
-    let positions: Vec<serde_json::Value> = res.json().await
-        .map_err(|e| format!("Erro ao interpretar JSON: {:?}", e))?;
-
-    for position in positions {
-        if position["symbol"] == symbol {
-            let amt = position["positionAmt"].as_str().unwrap_or("0").parse::<f64>().unwrap_or(0.0);
-            if amt.abs() > 0.0 {
-                let profit = position["unRealizedProfit"].as_str().unwrap_or("0").parse::<f64>().unwrap_or(0.0);
-                return Ok(Some(profit));
-            }
-        }
-    }
+/// 24h statistics of every futures contract.
+pub async fn get_tickers_24h(settings: &BinanceSettings) -> Result<Vec<Ticker24h>, String> {
+    let url = format!("{}/ticker/24hr", settings.future_url);
 
-    Ok(None)
+    get_binance_client()
+        .get(&url, &[])
+        .await
+        .map_err(|e| format!("Erro ao obter ticker 24h: {}", e))
 }
 
-fn now_ms() -> u64 {
-    SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap()
-        .as_millis() as u64
+pub async fn get_position_risk(settings: &BinanceSettings) -> Result<Vec<PositionResponse>, String> {
+    let url = format!("{}/positionRisk", settings.future_url_v2);
+
+    get_binance_client()
+        .signed(Method::GET, &url, &[])
+        .await
+        .map_err(|e| format!("Error when querying positions: {}", e))
 }
 
-fn sign(query: &str, secret: &str) -> String {
-    let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).unwrap();
-    mac.update(query.as_bytes());
-    hex::encode(mac.finalize().into_bytes())
+pub fn unrealized_profit_of(positions: &[PositionResponse], symbol: &str) -> Option<f64> {
+    positions
+        .iter()
+        .filter(|p| p.symbol == symbol)
+        .find(|p| p.position_amt.parse::<f64>().unwrap_or(0.0).abs() > 0.0)
+        .map(|p| p.unrealized_profit.parse::<f64>().unwrap_or(0.0))
 }
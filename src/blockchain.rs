@@ -1,5 +1,10 @@
-﻿use crate::dto::Trade;
-use crate::config::Settings;
+﻿use crate::dto::{OrderResponse, Trade, TradeStatus};
+use crate::config::{EvaluationMode, LedgerSettings, Settings};
+use crate::strategy::get_evaluation_mode;
+use crate::ledger::{get_ledger, init_ledger};
+use crate::audit::Checkpoint;
+use crate::signing::{get_signed_since, get_verifying_key, init_signing_key, pin_signed_since, sign, verify};
+use ed25519_dalek::VerifyingKey;
 use serde::{Serialize, Deserialize};
 use sha2::{Digest, Sha256};
 use std::collections::HashMap;
@@ -7,39 +12,191 @@ use std::sync::Mutex;
 use std::time::{SystemTime, UNIX_EPOCH};
 use once_cell::sync::Lazy;
 
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub enum ActionKind {
+    Buy,
+    Sell,
+    ClosePositions,
+    SetLeverage,
+    ProtectiveOrder,
+}
+
+/// What the bot did in response to a trade block, and how the exchange
+/// answered.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TradeAction {
+    pub kind: ActionKind,
+    pub status: Option<TradeStatus>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub orders: Vec<OrderResponse>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub leverage: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fill_price: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl TradeAction {
+    pub fn from_orders(kind: ActionKind, status: Option<TradeStatus>, result: Result<Vec<OrderResponse>, String>) -> Self {
+        let (orders, error) = match result {
+            Ok(orders) => (orders, None),
+            Err(e) => (vec![], Some(e)),
+        };
+        let fill_price = orders.iter().find_map(fill_price);
+
+        TradeAction { kind, status, orders, leverage: None, fill_price, error }
+    }
+
+    pub fn from_leverage(status: Option<TradeStatus>, requested: u32, result: Result<u32, String>) -> Self {
+        let (leverage, error) = match result {
+            Ok(applied) => (applied, None),
+            Err(e) => (requested, Some(e)),
+        };
+
+        TradeAction {
+            kind: ActionKind::SetLeverage,
+            status,
+            orders: vec![],
+            leverage: Some(leverage),
+            fill_price: None,
+            error,
+        }
+    }
+}
+
+/// Average fill price of an executed order, if the exchange reported one.
+fn fill_price(order: &OrderResponse) -> Option<String> {
+    let positive = |v: &String| v.parse::<f64>().is_ok_and(|p| p > 0.0);
+
+    order.avg_price.clone().filter(positive)
+        .or_else(|| Some(order.price.clone()).filter(positive))
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct TradeBlock {
     pub index: u64,
     pub timestamp: u64,
     pub trade: Trade,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub action: Option<TradeAction>,
+    /// Candles the strategy evaluated: closed only, or intrabar.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub evaluation: Option<EvaluationMode>,
     pub previous_hash: String,
     pub hash: String,
+    /// Operator's Ed25519 signature over `hash`, in hex.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
 }
 
 impl TradeBlock {
     pub fn new(index: u64, trade: Trade, previous_hash: String) -> Self {
+        Self::build(index, trade, None, previous_hash)
+    }
+
+    /// Block recording `action`, taken while `trade` was the latest snapshot.
+    pub fn action(index: u64, trade: Trade, action: TradeAction, previous_hash: String) -> Self {
+        Self::build(index, trade, Some(action), previous_hash)
+    }
+
+    fn build(index: u64, trade: Trade, action: Option<TradeAction>, previous_hash: String) -> Self {
         let timestamp = current_timestamp();
-        let hash = Self::calculate_hash(index, timestamp, &trade, &previous_hash);
+        let evaluation = Some(get_evaluation_mode());
+        let hash = Self::calculate_hash(index, timestamp, &trade, action.as_ref(), evaluation.as_ref(), &previous_hash);
+        let signature = sign(&hash);
         TradeBlock {
             index,
             timestamp,
             trade,
+            action,
+            evaluation,
             previous_hash,
             hash,
+            signature,
         }
     }
 
-    pub fn calculate_hash(index: u64, timestamp: u64, trade: &Trade, previous_hash: &str) -> String {
+    pub fn calculate_hash(
+        index: u64,
+        timestamp: u64,
+        trade: &Trade,
+        action: Option<&TradeAction>,
+        evaluation: Option<&EvaluationMode>,
+        previous_hash: &str,
+    ) -> String {
         let mut hasher = Sha256::new();
         hasher.update(index.to_be_bytes());
         hasher.update(timestamp.to_be_bytes());
         hasher.update(serde_json::to_string(trade).unwrap());
+        if let Some(action) = action {
+            hasher.update(serde_json::to_string(action).unwrap());
+        }
+        if let Some(evaluation) = evaluation {
+            hasher.update(serde_json::to_string(evaluation).unwrap());
+        }
         hasher.update(previous_hash.as_bytes());
         let result = hasher.finalize();
         hex::encode(result)
     }
 }
 
+/// Why an operation left the active set.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub enum CloseReason {
+    TakeProfit,
+    OutZone5,
+    OutZone3,
+    Invalidation,
+    NeutralZone,
+}
+
+/// A removed blockchain, kept for post-trade review.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ClosedOperation {
+    pub id: u64,
+    pub symbol: String,
+    pub reason: CloseReason,
+    pub opened_at: u64,
+    pub closed_at: u64,
+    pub blocks: Vec<TradeBlock>,
+}
+
+/// `ClosedOperation` without its blocks, for listings.
+#[derive(Debug, Serialize)]
+pub struct ClosedOperationSummary {
+    pub id: u64,
+    pub symbol: String,
+    pub reason: CloseReason,
+    pub opened_at: u64,
+    pub closed_at: u64,
+    pub blocks: usize,
+    pub actions: usize,
+    pub open_price: String,
+    pub close_price: String,
+    pub last_status: Option<TradeStatus>,
+}
+
+impl ClosedOperation {
+    pub fn summary(&self) -> ClosedOperationSummary {
+        let first = self.blocks.first().map(|b| &b.trade);
+        let last = self.blocks.last().map(|b| &b.trade);
+
+        ClosedOperationSummary {
+            id: self.id,
+            symbol: self.symbol.clone(),
+            reason: self.reason.clone(),
+            opened_at: self.opened_at,
+            closed_at: self.closed_at,
+            blocks: self.blocks.len(),
+            actions: self.blocks.iter().filter(|b| b.action.is_some()).count(),
+            open_price: first.map(|t| t.current_price.to_string()).unwrap_or_default(),
+            close_price: last.map(|t| t.current_price.to_string()).unwrap_or_default(),
+            last_status: last.and_then(|t| t.status.clone()),
+        }
+    }
+}
+
 fn current_timestamp() -> u64 {
     SystemTime::now()
         .duration_since(UNIX_EPOCH)
@@ -57,11 +214,18 @@ impl TradeBlockchain {
         Self { chain: vec![] }
     }
 
+    pub fn from_blocks(chain: Vec<TradeBlock>) -> Self {
+        Self { chain }
+    }
+
+    /// Appends `trade` when its status moved, or when it carries a
+    /// transition check different from the last one.
     pub fn add_block(&mut self, trade: Trade) -> bool {
-        if let Some(last_trade) = self.get_last_trade() {
-            if trade.status == last_trade.status {
-                return false;
-            }
+        if let Some(last_trade) = self.get_last_trade()
+            && trade.status == last_trade.status
+            && (trade.transition.is_none() || trade.transition == last_trade.transition)
+        {
+            return false;
         }
 
         let index = self.chain.len() as u64;
@@ -72,13 +236,34 @@ impl TradeBlockchain {
         let new_block = TradeBlock::new(index, trade.clone(), previous_hash);
         self.chain.push(new_block);
 
-        println!("\n\n\n[{}] - New block added - {} - Status: {:?}, Price: {}\n\n\n",
+        println!("\n\n\n[{}] - New block added - {} - Status: {:?}, Price: {}{}\n\n\n",
             chrono::Local::now().format("%Y-%m-%d %H:%M:%S"),
             trade.symbol,
             trade.status,
             trade.current_price,
+            trade.transition.as_ref().map(|c| format!(" - {:?}: {}", c.outcome, c.reason)).unwrap_or_default(),
+        );
+
+        true
+    }
+
+    pub fn add_action(&mut self, action: TradeAction) -> bool {
+        let Some(trade) = self.get_last_trade() else {
+            return false;
+        };
+
+        let index = self.chain.len() as u64;
+        let previous_hash = self.chain.last().map(|b| b.hash.clone()).unwrap_or_default();
+
+        println!(
+            "[{}] - Action block added - {} - {:?}{}",
+            chrono::Local::now().format("%Y-%m-%d %H:%M:%S"),
+            trade.symbol,
+            action.kind,
+            action.error.as_ref().map(|e| format!(" (erro: {})", e)).unwrap_or_default(),
         );
 
+        self.chain.push(TradeBlock::action(index, trade, action, previous_hash));
         true
     }
 
@@ -86,27 +271,46 @@ impl TradeBlockchain {
         self.chain.last().map(|block| block.trade.clone())
     }
 
+    /// Hash links plus signatures against the operator key of this process.
+    /// Unsigned blocks are accepted only from before the pinned signing start.
     pub fn is_valid(&self) -> bool {
-        for i in 1..self.chain.len() {
-            let current = &self.chain[i];
-            let previous = &self.chain[i - 1];
+        self.verify_with(get_verifying_key().as_ref(), get_signed_since()).is_ok()
+    }
+
+    /// Checks every hash link and, with a `key`, every signature present.
+    /// Blocks written at or after `signed_since` must also be signed.
+    pub fn verify_with(&self, key: Option<&VerifyingKey>, signed_since: Option<u64>) -> Result<(), String> {
+        for (i, current) in self.chain.iter().enumerate() {
+            let previous_hash = if i == 0 { "0" } else { &self.chain[i - 1].hash };
 
-            if current.previous_hash != previous.hash {
-                return false;
+            if current.index != i as u64 || current.previous_hash != previous_hash {
+                return Err(format!("bloco {}: encadeamento quebrado", i));
             }
 
             let recalculated_hash = TradeBlock::calculate_hash(
                 current.index,
                 current.timestamp,
                 &current.trade,
+                current.action.as_ref(),
+                current.evaluation.as_ref(),
                 &current.previous_hash,
             );
 
             if current.hash != recalculated_hash {
-                return false;
+                return Err(format!("bloco {}: hash nao confere", i));
+            }
+
+            match (&current.signature, key) {
+                (Some(signature), Some(key)) if !verify(key, &current.hash, signature) => {
+                    return Err(format!("bloco {}: assinatura invalida", i));
+                }
+                (None, _) if signed_since.is_some_and(|since| current.timestamp >= since) => {
+                    return Err(format!("bloco {}: sem assinatura", i));
+                }
+                _ => {}
             }
         }
-        true
+        Ok(())
     }
 
     pub fn all(&self) -> &[TradeBlock] {
@@ -118,15 +322,54 @@ pub static BLOCKCHAIN: Lazy<Mutex<HashMap<String, TradeBlockchain>>> = Lazy::new
     Mutex::new(HashMap::new())
 });
 
+static ARCHIVE: Lazy<Mutex<Vec<ClosedOperation>>> = Lazy::new(|| Mutex::new(Vec::new()));
+
 pub fn is_blockchain_limit_reached() -> bool {
     let settings = Settings::load();
     let map = BLOCKCHAIN.lock().unwrap();
     map.len() >= settings.limit_operations
 }
 
-pub fn remove_blockchain(symbol: &str) {
+/// Moves the chain of `symbol` from the active set into the archive.
+pub fn remove_blockchain(symbol: &str, reason: CloseReason) {
     let mut map = BLOCKCHAIN.lock().unwrap();
-    map.remove(symbol);
+    let Some(chain) = map.remove(symbol) else {
+        return;
+    };
+
+    let mut archive = ARCHIVE.lock().unwrap();
+    let operation = ClosedOperation {
+        id: archive.len() as u64,
+        symbol: symbol.to_string(),
+        reason,
+        opened_at: chain.all().first().map(|b| b.timestamp).unwrap_or_default(),
+        closed_at: current_timestamp(),
+        blocks: chain.chain,
+    };
+
+    println!(
+        "[{}] - Operacao arquivada - {} - Motivo: {:?}",
+        chrono::Local::now().format("%Y-%m-%d %H:%M:%S"),
+        symbol,
+        operation.reason
+    );
+
+    if let Some(ledger) = get_ledger()
+        && let Err(e) = ledger.archive(&operation)
+    {
+        eprintln!("Operacao de {} nao arquivada em disco: {}", symbol, e);
+    }
+    archive.push(operation);
+}
+
+pub fn get_archived_operations() -> Vec<ClosedOperationSummary> {
+    let archive = ARCHIVE.lock().unwrap();
+    archive.iter().map(ClosedOperation::summary).collect()
+}
+
+pub fn get_archived_operation(id: u64) -> Option<ClosedOperation> {
+    let archive = ARCHIVE.lock().unwrap();
+    archive.get(id as usize).cloned()
 }
 
 pub fn get_current_blockchain_symbols() -> Vec<String> {
@@ -134,11 +377,40 @@ pub fn get_current_blockchain_symbols() -> Vec<String> {
     map.keys().cloned().collect()
 }
 
+/// Writes the newest block of `chain` to the ledger, when one is open.
+fn persist_last_block(symbol: &str, chain: &TradeBlockchain) {
+    if let (Some(ledger), Some(block)) = (get_ledger(), chain.all().last())
+        && let Err(e) = ledger.append(symbol, block)
+    {
+        eprintln!("Bloco de {} nao persistido: {}", symbol, e);
+    }
+}
+
+/// Appends `action` to the active chain of `symbol`. Symbols without a
+/// chain (e.g. manual orders) are not recorded.
+pub fn add_action_block(symbol: &str, action: TradeAction) {
+    let mut map = BLOCKCHAIN.lock().unwrap();
+
+    if let Some(chain) = map.get_mut(symbol)
+        && chain.add_action(action)
+    {
+        persist_last_block(symbol, chain);
+    }
+}
+
+/// Records `trade` in its chain, opening one if there is room. True only
+/// when the status moved: blocks that only record a transition check do
+/// not call for new actions.
 pub fn add_trade_block(trade: Trade) -> bool {
     let mut map = BLOCKCHAIN.lock().unwrap();
 
     if let Some(chain) = map.get_mut(&trade.symbol) {
-        return chain.add_block(trade);
+        let moved = chain.get_last_trade().is_none_or(|last| last.status != trade.status);
+        let added = chain.add_block(trade.clone());
+        if added {
+            persist_last_block(&trade.symbol, chain);
+        }
+        return added && moved;
     }
 
     let settings = Settings::load();
@@ -150,12 +422,105 @@ pub fn add_trade_block(trade: Trade) -> bool {
     let added = new_chain.add_block(trade.clone());
 
     if added {
+        persist_last_block(&trade.symbol, &new_chain);
         map.insert(trade.symbol.clone(), new_chain);
     }
 
     added
 }
 
+/// Opens the on-disk ledger and loads the archive plus every active chain
+/// into `BLOCKCHAIN`. A chain or archived operation that fails verification
+/// aborts startup rather than trading blind.
+pub fn restore_blockchains(settings: &LedgerSettings) -> Result<usize, String> {
+    let public_key = init_signing_key(settings)?;
+    println!("Chave publica do ledger: {}", hex::encode(public_key.to_bytes()));
+
+    let ledger = init_ledger(settings)?;
+    let chains = ledger.load()?;
+    let operations = ledger.load_archive()?;
+    let inferred = signing_started(&chains, &operations, &ledger.load_checkpoints()?);
+    let since = pin_signed_since(settings, inferred)?;
+    println!("Assinaturas exigidas desde {} (unix)", since);
+    let signed_since = Some(since);
+
+    for (i, op) in operations.iter().enumerate() {
+        if op.id != i as u64 {
+            return Err(format!("Arquivo corrompido em {}: operacao {} fora de sequencia", settings.dir, op.id));
+        }
+        if let Err(e) = TradeBlockchain::from_blocks(op.blocks.clone()).verify_with(Some(&public_key), signed_since) {
+            return Err(format!(
+                "Arquivo corrompido em {}: operacao {} ({}): {}",
+                settings.dir, op.id, op.symbol, e
+            ));
+        }
+    }
+
+    let mut archive = ARCHIVE.lock().unwrap();
+    *archive = operations;
+    let mut map = BLOCKCHAIN.lock().unwrap();
+
+    for (symbol, blocks) in chains {
+        // Archived right before a crash, but the active file was not removed yet.
+        let archived = archive.iter().any(|op| {
+            op.symbol == symbol && op.blocks.last().map(|b| &b.hash) == blocks.last().map(|b| &b.hash)
+        });
+        if archived {
+            ledger.discard(&symbol)?;
+            continue;
+        }
+
+        let chain = TradeBlockchain::from_blocks(blocks);
+        if let Err(e) = chain.verify_with(Some(&public_key), signed_since) {
+            return Err(format!(
+                "Blockchain corrompida para {} em {}: {}",
+                symbol, settings.dir, e
+            ));
+        }
+
+        if let Some(trade) = chain.get_last_trade() {
+            println!(
+                "Blockchain restaurada - {} - {} blocos - Status: {:?}",
+                symbol,
+                chain.all().len(),
+                trade.status
+            );
+        }
+        map.insert(symbol, chain);
+    }
+
+    Ok(map.len())
+}
+
+/// Earliest signed block or checkpoint in the ledger, to pin when the
+/// operator key was introduced the first time no pinned value exists.
+fn signing_started(
+    chains: &HashMap<String, Vec<TradeBlock>>,
+    archive: &[ClosedOperation],
+    checkpoints: &[Checkpoint],
+) -> Option<u64> {
+    let blocks = chains.values().flatten().chain(archive.iter().flat_map(|op| &op.blocks));
+    let signed_blocks = blocks.filter(|b| b.signature.is_some()).map(|b| b.timestamp);
+    let signed_checkpoints = checkpoints.iter().filter(|c| c.signature.is_some()).map(|c| c.timestamp);
+    signed_blocks.chain(signed_checkpoints).min()
+}
+
+/// Name, length and head hash of every active chain and archived operation,
+/// the leaves of a ledger checkpoint.
+pub fn chain_heads() -> Vec<(String, usize, String)> {
+    let map = BLOCKCHAIN.lock().unwrap();
+    let archive = ARCHIVE.lock().unwrap();
+
+    let active = map.iter().filter_map(|(symbol, chain)| {
+        chain.all().last().map(|b| (symbol.clone(), chain.all().len(), b.hash.clone()))
+    });
+    let archived = archive.iter().filter_map(|op| {
+        op.blocks.last().map(|b| (format!("archive/{}", op.id), op.blocks.len(), b.hash.clone()))
+    });
+
+    active.chain(archived).collect()
+}
+
 pub fn get_blockchain_for(symbol: &str) -> Option<Vec<TradeBlock>> {
     let map = BLOCKCHAIN.lock().unwrap();
     map.get(symbol).map(|chain| chain.all().to_vec())
@@ -166,7 +531,84 @@ pub fn get_last_trade_for(symbol: &str) -> Option<Trade> {
     map.get(symbol).and_then(|chain| chain.get_last_trade())
 }
 
+/// Trades of the status blocks of `symbol`'s active chain, oldest first.
+/// Action blocks, which repeat the trade they reacted to, are left out.
+pub fn get_status_trades_for(symbol: &str) -> Vec<Trade> {
+    let map = BLOCKCHAIN.lock().unwrap();
+    map.get(symbol)
+        .map(|chain| chain.all().iter().filter(|b| b.action.is_none()).map(|b| b.trade.clone()).collect())
+        .unwrap_or_default()
+}
+
 pub fn get_all_symbols() -> Vec<String> {
     let map = BLOCKCHAIN.lock().unwrap();
     map.keys().cloned().collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::{Signer, SigningKey};
+
+    fn trade() -> Trade {
+        let mut fields = serde_json::json!({"symbol": "ETHUSDT", "bias": "Bullish", "status": null, "of": 0});
+        for name in [
+            "current_price", "cma", "oma", "zone_max", "zone_7", "zone_6", "zone_5", "zone_4", "zone_3", "zone_2",
+            "zone_1", "zone_min", "performance_24", "performance_btc_24", "amplitude_ma_200", "volume",
+            "quote_asset_volume", "number_of_trades", "taker_buy_base_asset_volume", "taker_buy_quote_asset_volume",
+        ] {
+            fields[name] = "1".into();
+        }
+        serde_json::from_value(fields).unwrap()
+    }
+
+    /// Chain with one block per timestamp, signed with `key` where `signed`.
+    fn chain(key: &SigningKey, blocks: &[(u64, bool)]) -> TradeBlockchain {
+        let mut chain: Vec<TradeBlock> = Vec::new();
+        for (index, &(timestamp, signed)) in blocks.iter().enumerate() {
+            let previous_hash = chain.last().map_or("0".to_string(), |b| b.hash.clone());
+            let hash = TradeBlock::calculate_hash(index as u64, timestamp, &trade(), None, None, &previous_hash);
+            let signature = signed.then(|| hex::encode(key.sign(hash.as_bytes()).to_bytes()));
+            chain.push(TradeBlock {
+                index: index as u64,
+                timestamp,
+                trade: trade(),
+                action: None,
+                evaluation: None,
+                previous_hash,
+                hash,
+                signature,
+            });
+        }
+        TradeBlockchain::from_blocks(chain)
+    }
+
+    #[test]
+    fn unsigned_blocks_are_rejected_after_signing_started() {
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let public = key.verifying_key();
+
+        let legacy = chain(&key, &[(100, false), (200, true), (300, true)]);
+        assert!(legacy.verify_with(Some(&public), Some(200)).is_ok());
+
+        let stripped = chain(&key, &[(100, false), (200, true), (300, false)]);
+        assert_eq!(stripped.verify_with(Some(&public), Some(200)), Err("bloco 2: sem assinatura".to_string()));
+        assert!(stripped.verify_with(Some(&public), None).is_ok());
+    }
+
+    #[test]
+    fn signing_starts_at_the_first_signed_block_or_checkpoint() {
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let active = HashMap::from([("ETHUSDT".to_string(), chain(&key, &[(100, false), (300, true)]).chain)]);
+        let archive = vec![ClosedOperation {
+            id: 0,
+            symbol: "SOLUSDT".to_string(),
+            reason: CloseReason::TakeProfit,
+            opened_at: 50,
+            closed_at: 250,
+            blocks: chain(&key, &[(50, false), (250, true)]).chain,
+        }];
+        assert_eq!(signing_started(&active, &archive, &[]), Some(250));
+        assert_eq!(signing_started(&HashMap::new(), &[], &[]), None);
+    }
+}
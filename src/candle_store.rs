@@ -0,0 +1,268 @@
+use crate::dto::Candlestick;
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::PathBuf;
+use std::str::FromStr;
+
+const MAGIC: &[u8; 4] = b"RKDC";
+const VERSION: u32 = 2;
+const HEADER_LEN: usize = 8;
+const RECORD_LEN: usize = 24 + 8 * DECIMAL_LEN;
+const DECIMAL_LEN: usize = 16;
+/// Version 1 stored prices and volumes as `f64`; still readable, and
+/// rewritten as version 2 on the next merge.
+const LEGACY_VERSION: u32 = 1;
+const LEGACY_RECORD_LEN: usize = 88;
+
+/// Candles on disk, one file per `{interval}/{SYMBOL}.bin`. Each file is a
+/// small header followed by fixed-size little-endian records sorted by
+/// `open_time`, so appends are cheap and reads need no parsing. Prices and
+/// volumes are kept as exact decimals, so they read back as Binance sent them.
+pub struct CandleStore {
+    dir: PathBuf,
+}
+
+impl CandleStore {
+    pub fn new(dir: &str) -> Self {
+        CandleStore { dir: PathBuf::from(dir) }
+    }
+
+    fn path(&self, symbol: &str, interval: &str) -> PathBuf {
+        self.dir.join(interval).join(format!("{}.bin", symbol))
+    }
+
+    pub fn read(&self, symbol: &str, interval: &str) -> Result<Vec<Candlestick>, String> {
+        self.load(symbol, interval).map(|(_, candles)| candles)
+    }
+
+    /// The stored candles and the format version of their file.
+    fn load(&self, symbol: &str, interval: &str) -> Result<(u32, Vec<Candlestick>), String> {
+        let path = self.path(symbol, interval);
+        if !path.exists() {
+            return Ok((VERSION, vec![]));
+        }
+
+        let mut bytes = vec![];
+        BufReader::new(File::open(&path).map_err(|e| format!("Error opening {}: {}", path.display(), e))?)
+            .read_to_end(&mut bytes)
+            .map_err(|e| format!("Error reading {}: {}", path.display(), e))?;
+
+        if bytes.len() < HEADER_LEN || &bytes[..4] != MAGIC {
+            return Err(format!("{} is not a candle file", path.display()));
+        }
+        let version = u32::from_le_bytes(bytes[4..8].try_into().unwrap());
+        let candles = match version {
+            VERSION => bytes[HEADER_LEN..].chunks_exact(RECORD_LEN).map(decode).collect(),
+            LEGACY_VERSION => bytes[HEADER_LEN..].chunks_exact(LEGACY_RECORD_LEN).map(decode_legacy).collect(),
+            _ => return Err(format!("{} has unsupported version {}", path.display(), version)),
+        };
+        Ok((version, candles))
+    }
+
+    /// Candles with `from <= open_time < to`.
+    pub fn range(&self, symbol: &str, interval: &str, from: u64, to: u64) -> Result<Vec<Candlestick>, String> {
+        let candles = self.read(symbol, interval)?;
+        let start = candles.partition_point(|c| c.open_time < from);
+        let end = candles.partition_point(|c| c.open_time < to);
+        Ok(candles[start..end].to_vec())
+    }
+
+    /// Merges `candles` into the stored series, keeping it sorted and
+    /// free of duplicates. Returns how many new candles were written.
+    pub fn merge(&self, symbol: &str, interval: &str, candles: &[Candlestick]) -> Result<usize, String> {
+        if candles.is_empty() {
+            return Ok(0);
+        }
+
+        let (version, mut stored) = self.load(symbol, interval)?;
+        let last = stored.last().map(|c| c.open_time);
+        let before = stored.len();
+
+        let mut incoming: Vec<Candlestick> = candles.to_vec();
+        incoming.sort_by_key(|c| c.open_time);
+
+        // Pure appends are the common case and avoid rewriting the file.
+        if version == VERSION && last.is_none_or(|last| incoming[0].open_time > last) {
+            incoming.dedup_by_key(|c| c.open_time);
+            self.write(symbol, interval, &incoming, before > 0)?;
+            return Ok(incoming.len());
+        }
+
+        stored.extend(incoming);
+        stored.sort_by_key(|c| c.open_time);
+        stored.dedup_by_key(|c| c.open_time);
+        let added = stored.len() - before;
+
+        if added > 0 || version != VERSION {
+            self.write(symbol, interval, &stored, false)?;
+        }
+        Ok(added)
+    }
+
+    fn write(&self, symbol: &str, interval: &str, candles: &[Candlestick], append: bool) -> Result<(), String> {
+        let records = candles.iter().map(encode).collect::<Result<Vec<_>, _>>()?;
+        let path = self.path(symbol, interval);
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|e| format!("Error creating {}: {}", dir.display(), e))?;
+        }
+
+        let file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(append)
+            .truncate(!append)
+            .open(&path)
+            .map_err(|e| format!("Error opening {}: {}", path.display(), e))?;
+        let mut writer = BufWriter::new(file);
+
+        let mut write_all = |bytes: &[u8]| {
+            writer
+                .write_all(bytes)
+                .map_err(|e| format!("Error writing {}: {}", path.display(), e))
+        };
+
+        if !append {
+            write_all(MAGIC)?;
+            write_all(&VERSION.to_le_bytes())?;
+        }
+        for record in &records {
+            write_all(record)?;
+        }
+
+        writer.flush().map_err(|e| format!("Error writing {}: {}", path.display(), e))
+    }
+}
+
+/// Fails on a price or volume that is not a decimal, rather than storing
+/// a made-up value.
+fn encode(candle: &Candlestick) -> Result<[u8; RECORD_LEN], String> {
+    let mut record = [0u8; RECORD_LEN];
+    let decimals = [
+        ("open", &candle.open_price),
+        ("high", &candle.high_price),
+        ("low", &candle.low_price),
+        ("close", &candle.close_price),
+        ("volume", &candle.volume),
+        ("quote_volume", &candle.quote_asset_volume),
+        ("taker_buy_base", &candle.taker_buy_base_asset_volume),
+        ("taker_buy_quote", &candle.taker_buy_quote_asset_volume),
+    ];
+
+    record[0..8].copy_from_slice(&candle.open_time.to_le_bytes());
+    record[8..16].copy_from_slice(&candle.close_time.to_le_bytes());
+    record[16..24].copy_from_slice(&candle.number_of_trades.to_le_bytes());
+    for (i, (name, value)) in decimals.iter().enumerate() {
+        let value = Decimal::from_str(value)
+            .map_err(|e| format!("Invalid {} '{}' in candle {}: {}", name, value, candle.open_time, e))?;
+        let at = 24 + i * DECIMAL_LEN;
+        record[at..at + DECIMAL_LEN].copy_from_slice(&value.serialize());
+    }
+    Ok(record)
+}
+
+fn decode(record: &[u8]) -> Candlestick {
+    let decimal_at = |n: usize| {
+        let at = 24 + n * DECIMAL_LEN;
+        Decimal::deserialize(record[at..at + DECIMAL_LEN].try_into().unwrap()).to_string()
+    };
+    candle_from(record, decimal_at)
+}
+
+fn decode_legacy(record: &[u8]) -> Candlestick {
+    let f64_at = |n: usize| f64::from_le_bytes(record[24 + n * 8..32 + n * 8].try_into().unwrap()).to_string();
+    candle_from(record, f64_at)
+}
+
+fn candle_from(record: &[u8], value_at: impl Fn(usize) -> String) -> Candlestick {
+    let u64_at = |i: usize| u64::from_le_bytes(record[i..i + 8].try_into().unwrap());
+
+    Candlestick {
+        open_time: u64_at(0),
+        open_price: value_at(0),
+        high_price: value_at(1),
+        low_price: value_at(2),
+        close_price: value_at(3),
+        volume: value_at(4),
+        close_time: u64_at(8),
+        quote_asset_volume: value_at(5),
+        number_of_trades: u64_at(16),
+        taker_buy_base_asset_volume: value_at(6),
+        taker_buy_quote_asset_volume: value_at(7),
+        ignore: "0".to_string(),
+    }
+}
+
+/// Length of a Binance kline interval such as `1m`, `4h` or `1d`.
+pub fn interval_ms(interval: &str) -> Option<u64> {
+    let (value, unit) = interval.split_at(interval.len().checked_sub(1)?);
+    let value: u64 = value.parse().ok()?;
+    let unit_ms = match unit {
+        "m" => 60_000,
+        "h" => 3_600_000,
+        "d" => 86_400_000,
+        "w" => 604_800_000,
+        _ => return None,
+    };
+    Some(value * unit_ms)
+}
+
+/// `YYYY-MM-DD` at 00:00 UTC, in milliseconds.
+pub fn parse_date(value: &str) -> Result<u64, String> {
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map_err(|e| format!("Invalid date '{}': {}", value, e))?
+        .and_hms_opt(0, 0, 0)
+        .map(|t| t.and_utc().timestamp_millis() as u64)
+        .ok_or_else(|| format!("Invalid date '{}'", value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candle(open_time: u64, close: &str) -> Candlestick {
+        Candlestick {
+            open_time,
+            open_price: "3400.01000000".to_string(),
+            high_price: "3410.50000000".to_string(),
+            low_price: "3390.00000000".to_string(),
+            close_price: close.to_string(),
+            volume: "1234.56700000".to_string(),
+            close_time: open_time + 3_599_999,
+            quote_asset_volume: "4197533.12345678".to_string(),
+            number_of_trades: 42,
+            taker_buy_base_asset_volume: "600.00000001".to_string(),
+            taker_buy_quote_asset_volume: "2040000.10000000".to_string(),
+            ignore: "0".to_string(),
+        }
+    }
+
+    fn store(name: &str) -> CandleStore {
+        let dir = std::env::temp_dir().join(format!("candle_store_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        CandleStore::new(dir.to_str().unwrap())
+    }
+
+    #[test]
+    fn decimals_read_back_exactly() {
+        let store = store("exact");
+        let written = vec![candle(0, "3405.12345678"), candle(3_600_000, "0.00000001")];
+        assert_eq!(store.merge("ETHUSDT", "1h", &written).unwrap(), 2);
+
+        let read = store.read("ETHUSDT", "1h").unwrap();
+        assert_eq!(read[0].close_price, "3405.12345678");
+        assert_eq!(read[0].quote_asset_volume, "4197533.12345678");
+        assert_eq!(read[1].close_price, "0.00000001");
+        let _ = fs::remove_dir_all(&store.dir);
+    }
+
+    #[test]
+    fn garbled_value_is_rejected() {
+        let store = store("garbled");
+        let err = store.merge("ETHUSDT", "1h", &[candle(0, "34O5.1")]).unwrap_err();
+        assert!(err.contains("close"), "{}", err);
+        assert!(!store.path("ETHUSDT", "1h").exists());
+        let _ = fs::remove_dir_all(&store.dir);
+    }
+}
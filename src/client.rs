@@ -0,0 +1,259 @@
+use crate::config::{BinanceSettings, Settings};
+use crate::credential::get_credentials;
+use hmac::{Hmac, Mac};
+use once_cell::sync::Lazy;
+use reqwest::header::{HeaderMap, HeaderValue, CONTENT_TYPE};
+use reqwest::{Client, Method, StatusCode};
+use serde::de::DeserializeOwned;
+use sha2::Sha256;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use url::form_urlencoded;
+
+type HmacSha256 = Hmac<Sha256>;
+
+const RECV_WINDOW: &str = "10000";
+const MAX_RETRIES: u32 = 3;
+const RETRY_BASE_DELAY_MS: u64 = 250;
+const TIME_SYNC_INTERVAL: Duration = Duration::from_secs(300);
+const TIMESTAMP_OUT_OF_WINDOW: i64 = -1021;
+
+static BINANCE_CLIENT: Lazy<Arc<BinanceClient>> =
+    Lazy::new(|| Arc::new(BinanceClient::new(Settings::load().binance)));
+
+pub fn get_binance_client() -> Arc<BinanceClient> {
+    BINANCE_CLIENT.clone()
+}
+
+/// Shared HTTP client for every Binance call: keeps one connection pool,
+/// caches the `/time` offset and signs private requests the same way.
+pub struct BinanceClient {
+    http: Client,
+    settings: BinanceSettings,
+    time_offset: Mutex<Option<(i64, Instant)>>,
+}
+
+enum Failure {
+    Retry(String),
+    ResyncAndRetry(String),
+    Fatal(String),
+}
+
+impl BinanceClient {
+    pub fn new(settings: BinanceSettings) -> Self {
+        let http = Client::builder()
+            .pool_idle_timeout(Duration::from_secs(90))
+            .pool_max_idle_per_host(16)
+            .timeout(Duration::from_secs(15))
+            .build()
+            .expect("Failed to build HTTP client");
+
+        BinanceClient {
+            http,
+            settings,
+            time_offset: Mutex::new(None),
+        }
+    }
+
+    pub async fn get<T: DeserializeOwned>(
+        &self,
+        url: &str,
+        params: &[(&str, String)],
+    ) -> Result<T, String> {
+        let mut attempt = 0;
+
+        loop {
+            let result = self.http.get(url).query(params).send().await;
+
+            match Self::read_response(result, true).await {
+                Ok(value) => return Ok(value),
+                Err(Failure::Retry(e)) | Err(Failure::ResyncAndRetry(e)) if attempt < MAX_RETRIES => {
+                    eprintln!("Retrying GET {} after error: {}", url, e);
+                }
+                Err(Failure::Retry(e)) | Err(Failure::ResyncAndRetry(e)) | Err(Failure::Fatal(e)) => {
+                    return Err(e);
+                }
+            }
+
+            backoff(attempt).await;
+            attempt += 1;
+        }
+    }
+
+    /// Sends a signed request. `recvWindow`, `timestamp` and `signature` are
+    /// appended here, so callers only pass the endpoint specific parameters.
+    pub async fn signed<T: DeserializeOwned>(
+        &self,
+        method: Method,
+        url: &str,
+        params: &[(&str, String)],
+    ) -> Result<T, String> {
+        let credentials = get_credentials();
+        let idempotent = method == Method::GET;
+
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        headers.insert(
+            "X-MBX-APIKEY",
+            HeaderValue::from_str(&credentials.key).map_err(|e| format!("Invalid API key: {:?}", e))?,
+        );
+
+        let mut attempt = 0;
+
+        loop {
+            let timestamp = self.timestamp().await;
+
+            let query = form_urlencoded::Serializer::new(String::new())
+                .extend_pairs(params.iter().map(|(k, v)| (*k, v.as_str())))
+                .append_pair("recvWindow", RECV_WINDOW)
+                .append_pair("timestamp", &timestamp.to_string())
+                .finish();
+            let signature = sign(&query, &credentials.secret);
+            let full_url = format!("{}?{}&signature={}", url, query, signature);
+
+            let result = self
+                .http
+                .request(method.clone(), &full_url)
+                .headers(headers.clone())
+                .send()
+                .await;
+
+            match Self::read_response(result, idempotent).await {
+                Ok(value) => return Ok(value),
+                Err(Failure::ResyncAndRetry(e)) if attempt < MAX_RETRIES => {
+                    eprintln!("Timestamp rejected on {} {}, resyncing clock: {}", method, url, e);
+                    self.invalidate_time_offset();
+                }
+                Err(Failure::Retry(e)) if attempt < MAX_RETRIES => {
+                    eprintln!("Retrying {} {} after error: {}", method, url, e);
+                }
+                Err(Failure::Retry(e)) | Err(Failure::ResyncAndRetry(e)) | Err(Failure::Fatal(e)) => {
+                    return Err(e);
+                }
+            }
+
+            backoff(attempt).await;
+            attempt += 1;
+        }
+    }
+
+    /// Sends a request authenticated by the API key alone, as the listenKey
+    /// endpoints expect (no timestamp or signature).
+    pub async fn keyed<T: DeserializeOwned>(&self, method: Method, url: &str) -> Result<T, String> {
+        let credentials = get_credentials();
+        let mut attempt = 0;
+
+        loop {
+            let result = self
+                .http
+                .request(method.clone(), url)
+                .header("X-MBX-APIKEY", &credentials.key)
+                .send()
+                .await;
+
+            match Self::read_response(result, true).await {
+                Ok(value) => return Ok(value),
+                Err(Failure::Retry(e)) if attempt < MAX_RETRIES => {
+                    eprintln!("Retrying {} {} after error: {}", method, url, e);
+                }
+                Err(Failure::Retry(e)) | Err(Failure::ResyncAndRetry(e)) | Err(Failure::Fatal(e)) => {
+                    return Err(e);
+                }
+            }
+
+            backoff(attempt).await;
+            attempt += 1;
+        }
+    }
+
+    pub async fn sync_time(&self) -> Result<i64, String> {
+        let url = format!("{}/time", self.settings.future_url);
+        let json: serde_json::Value = self.get(&url, &[]).await?;
+
+        let server_time = json["serverTime"].as_i64().ok_or("serverTime field missing")?;
+        let offset = server_time - now_ms() as i64;
+
+        *self.time_offset.lock().unwrap() = Some((offset, Instant::now()));
+        Ok(offset)
+    }
+
+    /// Binance server time in ms, from the cached offset.
+    pub async fn server_time(&self) -> u64 {
+        self.timestamp().await
+    }
+
+    async fn timestamp(&self) -> u64 {
+        let cached = *self.time_offset.lock().unwrap();
+
+        let offset = match cached {
+            Some((offset, synced_at)) if synced_at.elapsed() < TIME_SYNC_INTERVAL => offset,
+            _ => match self.sync_time().await {
+                Ok(offset) => offset,
+                Err(e) => {
+                    eprintln!("Error syncing server time: {}", e);
+                    cached.map(|(offset, _)| offset).unwrap_or(0)
+                }
+            },
+        };
+
+        (now_ms() as i64 + offset) as u64
+    }
+
+    fn invalidate_time_offset(&self) {
+        *self.time_offset.lock().unwrap() = None;
+    }
+
+    async fn read_response<T: DeserializeOwned>(
+        result: Result<reqwest::Response, reqwest::Error>,
+        idempotent: bool,
+    ) -> Result<T, Failure> {
+        let res = match result {
+            Ok(res) => res,
+            // A refused connection never reached Binance, so it is always safe to resend.
+            Err(e) if e.is_connect() => return Err(Failure::Retry(format!("HTTP error: {:?}", e))),
+            Err(e) if idempotent => return Err(Failure::Retry(format!("HTTP error: {:?}", e))),
+            Err(e) => return Err(Failure::Fatal(format!("HTTP error: {:?}", e))),
+        };
+
+        let status = res.status();
+        if status.is_success() {
+            return res
+                .json::<T>()
+                .await
+                .map_err(|e| Failure::Fatal(format!("Error interpreting JSON: {:?}", e)));
+        }
+
+        let body = res.text().await.unwrap_or_else(|_| "Unknown error".to_string());
+        let message = format!("Binance error {}: {}", status, body);
+
+        let code = serde_json::from_str::<serde_json::Value>(&body)
+            .ok()
+            .and_then(|v| v["code"].as_i64());
+
+        if code == Some(TIMESTAMP_OUT_OF_WINDOW) {
+            Err(Failure::ResyncAndRetry(message))
+        } else if status == StatusCode::TOO_MANY_REQUESTS || (idempotent && status.is_server_error()) {
+            Err(Failure::Retry(message))
+        } else {
+            Err(Failure::Fatal(message))
+        }
+    }
+}
+
+async fn backoff(attempt: u32) {
+    let delay = RETRY_BASE_DELAY_MS * 2u64.pow(attempt);
+    tokio::time::sleep(Duration::from_millis(delay)).await;
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64
+}
+
+fn sign(query: &str, secret: &str) -> String {
+    let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).unwrap();
+    mac.update(query.as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
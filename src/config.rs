@@ -1,4 +1,13 @@
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ExecutionMode {
+    #[default]
+    Live,
+    Paper,
+}
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct BinanceSettings {
@@ -9,6 +18,360 @@ pub struct BinanceSettings {
     pub limit: u32,
     pub leverage: u32,
     pub decide: bool,
+    #[serde(default)]
+    pub mode: ExecutionMode,
+    #[serde(default = "default_symbols_refresh_secs")]
+    pub symbols_refresh_secs: u64,
+    #[serde(default = "default_max_concurrent_fetches")]
+    pub max_concurrent_fetches: usize,
+}
+
+fn default_symbols_refresh_secs() -> u64 {
+    3600
+}
+
+fn default_max_concurrent_fetches() -> usize {
+    8
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct PaperSettings {
+    pub balance: f64,
+    pub fee_rate: f64,
+    pub slippage: f64,
+    pub state_file: String,
+}
+
+impl Default for PaperSettings {
+    fn default() -> Self {
+        PaperSettings {
+            balance: 1000.0,
+            fee_rate: 0.0004,
+            slippage: 0.0,
+            state_file: "data/paper_account.json".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct MockSettings {
+    pub enabled: bool,
+    pub port: u16,
+    pub script: String,
+    pub step_secs: u64,
+    pub balance: f64,
+}
+
+impl Default for MockSettings {
+    fn default() -> Self {
+        MockSettings {
+            enabled: false,
+            port: 9090,
+            script: "config/MockMarket".to_string(),
+            step_secs: 0,
+            balance: 1000.0,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ProtectionSource {
+    #[default]
+    Zone,
+    Percent,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct ProtectionSettings {
+    pub enabled: bool,
+    pub source: ProtectionSource,
+    pub stop_loss_pct: f64,
+    pub take_profit_pct: f64,
+}
+
+impl Default for ProtectionSettings {
+    fn default() -> Self {
+        ProtectionSettings {
+            enabled: false,
+            source: ProtectionSource::Zone,
+            stop_loss_pct: 2.0,
+            take_profit_pct: 4.0,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum StreamTrigger {
+    #[default]
+    Update,
+    Close,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct StreamSettings {
+    pub enabled: bool,
+    pub url: String,
+    pub trigger: StreamTrigger,
+    pub min_eval_ms: u64,
+    pub user_data: bool,
+    pub user_url: String,
+}
+
+impl Default for StreamSettings {
+    fn default() -> Self {
+        StreamSettings {
+            enabled: false,
+            url: "wss://stream.binance.com:9443/stream".to_string(),
+            trigger: StreamTrigger::Update,
+            min_eval_ms: 1000,
+            user_data: false,
+            user_url: "wss://fstream.binance.com/ws".to_string(),
+        }
+    }
+}
+
+/// Which candles the strategy sees: `Intrabar` includes the forming one,
+/// `Closed` only those whose `close_time` has passed on the venue clock.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum EvaluationMode {
+    #[default]
+    Intrabar,
+    Closed,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct StrategySettings {
+    pub name: String,
+    pub evaluation: EvaluationMode,
+    pub zone: ZoneSettings,
+    pub bias: BiasSettings,
+    pub selection: SelectionSettings,
+}
+
+impl Default for StrategySettings {
+    fn default() -> Self {
+        StrategySettings {
+            name: "zone".to_string(),
+            evaluation: EvaluationMode::Intrabar,
+            zone: ZoneSettings::default(),
+            bias: BiasSettings::default(),
+            selection: SelectionSettings::default(),
+        }
+    }
+}
+
+/// How a new symbol is chosen among the candidates: `weights` per factor
+/// of the strategy (unlisted factors weigh 1), and `seed` for the
+/// tie-break between equal scores.
+#[derive(Debug, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct SelectionSettings {
+    pub seed: u64,
+    pub weights: HashMap<String, f64>,
+    pub filters: UniverseFilters,
+}
+
+/// Liquidity and volatility a symbol needs before it is scored. Zero
+/// turns a limit off.
+#[derive(Debug, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct UniverseFilters {
+    pub min_quote_volume_24h: f64,
+    pub max_spread_pct: f64,
+    pub min_atr_pct: f64,
+    pub max_atr_pct: f64,
+    pub min_listing_days: u64,
+}
+
+impl SelectionSettings {
+    pub fn weight(&self, factor: &str) -> f64 {
+        self.weights.get(factor).copied().unwrap_or(1.0)
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ZoneSpacing {
+    Linear,
+    Log,
+    Atr,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum MovingAverage {
+    Sma,
+    Ema,
+}
+
+/// How the bias is computed: the CMA over the last `cma_period` candles of
+/// `reference` against the OMA over `oma_period` candles ending `oma_shift`
+/// candles earlier. `reference = "self"` uses the symbol's own candles.
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct BiasRule {
+    pub reference: String,
+    pub ma: MovingAverage,
+    pub cma_period: usize,
+    pub oma_period: usize,
+    pub oma_shift: usize,
+}
+
+impl Default for BiasRule {
+    fn default() -> Self {
+        BiasRule {
+            reference: "BTCUSDT".to_string(),
+            ma: MovingAverage::Sma,
+            cma_period: 200,
+            oma_period: 200,
+            oma_shift: 71,
+        }
+    }
+}
+
+impl BiasRule {
+    /// Symbol whose candles set the bias of `symbol`.
+    pub fn reference_for(&self, symbol: &str) -> String {
+        if self.reference.eq_ignore_ascii_case("self") {
+            symbol.to_string()
+        } else {
+            self.reference.to_uppercase()
+        }
+    }
+
+    /// Reference candles needed for both averages.
+    pub fn required_candles(&self) -> usize {
+        self.cma_period.max(self.oma_period + self.oma_shift)
+    }
+}
+
+/// Fields of `BiasRule` a symbol overrides; unset ones come from the
+/// default rule.
+#[derive(Debug, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct BiasOverride {
+    pub reference: Option<String>,
+    pub ma: Option<MovingAverage>,
+    pub cma_period: Option<usize>,
+    pub oma_period: Option<usize>,
+    pub oma_shift: Option<usize>,
+}
+
+#[derive(Debug, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct BiasSettings {
+    #[serde(flatten)]
+    pub default: BiasRule,
+    /// Keyed by symbol, in any case.
+    pub symbols: HashMap<String, BiasOverride>,
+}
+
+impl BiasSettings {
+    pub fn rule_for(&self, symbol: &str) -> BiasRule {
+        let Some(o) = self.symbols.iter().find(|(key, _)| key.eq_ignore_ascii_case(symbol)).map(|(_, o)| o) else {
+            return self.default.clone();
+        };
+        BiasRule {
+            reference: o.reference.clone().unwrap_or_else(|| self.default.reference.clone()),
+            ma: o.ma.unwrap_or(self.default.ma),
+            cma_period: o.cma_period.unwrap_or(self.default.cma_period),
+            oma_period: o.oma_period.unwrap_or(self.default.oma_period),
+            oma_shift: o.oma_shift.unwrap_or(self.default.oma_shift),
+        }
+    }
+}
+
+/// Geometry of the zone strategy. `count` levels are spread over the
+/// high/low of the last `lookback` candles. `machine` is the TOML table of
+/// status transitions; empty uses the one built into the binary.
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct ZoneSettings {
+    pub count: usize,
+    pub lookback: usize,
+    pub spacing: ZoneSpacing,
+    pub atr_period: usize,
+    pub atr_multiplier: f64,
+    pub machine: String,
+}
+
+impl Default for ZoneSettings {
+    fn default() -> Self {
+        ZoneSettings {
+            count: 7,
+            lookback: 200,
+            spacing: ZoneSpacing::Log,
+            atr_period: 14,
+            atr_multiplier: 1.0,
+            machine: String::new(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct LedgerSettings {
+    pub enabled: bool,
+    pub dir: String,
+    pub key_file: String,
+    /// Unix time (s) from which every block must be signed. Unset, it is
+    /// pinned once in `{key_file}.since`.
+    pub signed_since: Option<u64>,
+    pub checkpoint_secs: u64,
+}
+
+impl Default for LedgerSettings {
+    fn default() -> Self {
+        LedgerSettings {
+            enabled: false,
+            dir: "data/ledger".to_string(),
+            key_file: "keys/ledger.key".to_string(),
+            signed_since: None,
+            checkpoint_secs: 3600,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct HistorySettings {
+    pub dir: String,
+    pub since: String,
+}
+
+impl Default for HistorySettings {
+    fn default() -> Self {
+        HistorySettings {
+            dir: "data/candles".to_string(),
+            since: "2024-01-01".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct BacktestSettings {
+    pub output_dir: String,
+    pub step_size: f64,
+    pub min_notional: f64,
+}
+
+impl Default for BacktestSettings {
+    fn default() -> Self {
+        BacktestSettings {
+            output_dir: "data/backtest".to_string(),
+            step_size: 0.00001,
+            min_notional: 5.0,
+        }
+    }
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -20,12 +383,52 @@ pub struct Settings {
     pub money: f64,
     pub gain: f64,
     pub show_details_monitor: bool,
+    #[serde(default)]
+    pub strategy: StrategySettings,
+    #[serde(default)]
+    pub paper: PaperSettings,
+    #[serde(default)]
+    pub mock: MockSettings,
+    #[serde(default)]
+    pub protection: ProtectionSettings,
+    #[serde(default)]
+    pub stream: StreamSettings,
+    #[serde(default)]
+    pub ledger: LedgerSettings,
+    #[serde(default)]
+    pub history: HistorySettings,
+    #[serde(default)]
+    pub backtest: BacktestSettings,
+    #[serde(default)]
+    pub universe: UniverseSettings,
+}
+
+/// Checks of `cryptos` against spot and futures `/exchangeInfo`, every
+/// `refresh_secs`. With `discover_top` above zero, the universe is instead
+/// the top N USDT perpetuals by 24h quote volume.
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct UniverseSettings {
+    pub refresh_secs: u64,
+    pub discover_top: usize,
+}
+
+impl Default for UniverseSettings {
+    fn default() -> Self {
+        UniverseSettings {
+            refresh_secs: 3600,
+            discover_top: 0,
+        }
+    }
 }
 
 impl Settings {
+    /// `config/Settings.toml`, with any `RKD__SECTION__KEY` variable taking
+    /// precedence (e.g. `RKD__BINANCE__FUTURE_URL`).
     pub fn load() -> Self {
         config::Config::builder()
             .add_source(config::File::with_name("config/Settings").required(true))
+            .add_source(config::Environment::with_prefix("RKD").separator("__"))
             .build()
             .expect("Failed to load configuration file")
             .try_deserialize()
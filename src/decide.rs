@@ -1,113 +1,70 @@
-use crate::blockchain::get_last_trade_for;
-use crate::dto::{Bias, TradeStatus};
-use crate::order::{execute_future_order, close_all_positions};
-use crate::config::BinanceSettings;
-use crate::leverage::set_leverage_with_value;
+use crate::blockchain::{add_action_block, get_last_trade_for, ActionKind, TradeAction};
+use crate::dto::Trade;
+use crate::exchange::Exchange;
+use crate::protection::sync_protection;
+use crate::strategy::{get_strategy, StrategyAction};
 
-pub fn decide(symbol: &str, binance_settings: &BinanceSettings) {
-    let trade = match get_last_trade_for(symbol) {
-        Some(t) => t,
-        None => {
-            println!("No trades found for decision for symbol: {}", symbol);
-            return;
-        }
-    };
+async fn open_position(trade: &Trade, side: &str, exchange: &dyn Exchange) {
+    let result = exchange.execute_future_order(side, &trade.symbol).await;
+    match &result {
+        Ok(order) => println!("{} order executed: {:?}", side, order),
+        Err(e) => eprintln!("Error executing {} order: {}", side, e),
+    }
 
-    let bias = trade.bias.clone();
-    let status = trade.status.clone();
-    let symbol = &trade.symbol;
+    let kind = if side == "BUY" { ActionKind::Buy } else { ActionKind::Sell };
+    let action = TradeAction::from_orders(kind, trade.status.clone(), result.map(|order| vec![order]));
+    add_action_block(&trade.symbol, action);
+}
 
-    match (bias, status) {
-        (_, None) => {
-            let binance = binance_settings.clone();
-            let symbol = symbol.clone();
-            tokio::spawn(async move {
-                match close_all_positions(&binance, &symbol).await {
-                    Ok(closed) => println!("All positions closed (status None): {:?}", closed),
-                    Err(e) => eprintln!("Error closing positions (status None): {}", e),
-                }
-                if let Err(e) = set_leverage_with_value(&binance, &symbol, 1).await {
-                    eprintln!("Error setting leverage to 1 (status None): {}", e);
-                }
-            });
-        }
+async fn close_positions(trade: &Trade, context: &str, exchange: &dyn Exchange) {
+    let result = exchange.close_all_positions(&trade.symbol).await;
+    match &result {
+        Ok(closed) => println!("Closed positions ({}): {:?}", context, closed),
+        Err(e) => eprintln!("Error closing positions ({}): {}", context, e),
+    }
 
-        (Bias::Bullish, Some(TradeStatus::InZone7))
-        | (Bias::Bullish, Some(TradeStatus::InZone3))
-        | (Bias::Bullish, Some(TradeStatus::LongZone3)) => {
-            let binance = binance_settings.clone();
-            let symbol = symbol.clone();
-            tokio::spawn(async move {
-                match execute_future_order(&binance, "BUY", &symbol).await {
-                    Ok(order) => println!("BUY order executed: {:?}", order),
-                    Err(e) => eprintln!("Error executing BUY order: {}", e),
-                }
-            });
-        }
+    let action = TradeAction::from_orders(ActionKind::ClosePositions, trade.status.clone(), result);
+    add_action_block(&trade.symbol, action);
+}
 
-        (Bias::Bearish, Some(TradeStatus::InZone1))
-        | (Bias::Bearish, Some(TradeStatus::InZone5))
-        | (Bias::Bearish, Some(TradeStatus::ShortZone5)) => {
-            let binance = binance_settings.clone();
-            let symbol = symbol.clone();
-            tokio::spawn(async move {
-                match execute_future_order(&binance, "SELL", &symbol).await {
-                    Ok(order) => println!("SELL order executed: {:?}", order),
-                    Err(e) => eprintln!("Error executing SELL order: {}", e),
-                }
-            });
-        }
+async fn set_leverage(trade: &Trade, leverage: u32, context: &str, exchange: &dyn Exchange) {
+    let result = exchange.set_leverage(&trade.symbol, leverage).await;
+    if let Err(e) = &result {
+        eprintln!("Error setting leverage to {} ({}): {}", leverage, context, e);
+    }
 
-        (Bias::Bullish, Some(TradeStatus::TargetZone7))
-        | (Bias::Bearish, Some(TradeStatus::TargetZone1)) => {
-            let binance = binance_settings.clone();
-            let symbol = symbol.clone();
-            tokio::spawn(async move {
-                if let Err(e) = set_leverage_with_value(&binance, &symbol, 1).await {
-                    eprintln!("Error setting leverage to 1 (target zone): {}", e);
-                }
-            });
-        }
+    let action = TradeAction::from_leverage(trade.status.clone(), leverage, result.map(|r| r.leverage));
+    add_action_block(&trade.symbol, action);
+}
 
-        (Bias::Bullish, Some(TradeStatus::OutZone5))
-        | (Bias::Bullish, Some(TradeStatus::PrepareZone1))
-        | (Bias::Bearish, Some(TradeStatus::OutZone3))
-        | (Bias::Bearish, Some(TradeStatus::PrepareZone7)) => {
-            let binance = binance_settings.clone();
-            let symbol = symbol.clone();
-            tokio::spawn(async move {
-                match close_all_positions(&binance, &symbol).await {
-                    Ok(closed) => println!("Closed positions (lev 1): {:?}", closed),
-                    Err(e) => eprintln!("Error closing positions: {}", e),
-                }
-                if let Err(e) = set_leverage_with_value(&binance, &symbol, 1).await {
-                    eprintln!("Error setting leverage to 1: {}", e);
-                }
-            });
+pub async fn decide(symbol: &str, exchange: &dyn Exchange) {
+    let trade = match get_last_trade_for(symbol) {
+        Some(t) => t,
+        None => {
+            println!("No trades found for decision for symbol: {}", symbol);
+            return;
         }
+    };
 
-        (Bias::Bullish, Some(TradeStatus::PrepareZone1Long))
-        | (Bias::Bearish, Some(TradeStatus::PrepareZone7Short)) => {
-            let binance = binance_settings.clone();
-            let symbol = symbol.clone();
-            tokio::spawn(async move {
-                match close_all_positions(&binance, &symbol).await {
-                    Ok(closed) => println!("Closed positions (lev 2): {:?}", closed),
-                    Err(e) => eprintln!("Error closing positions: {}", e),
-                }
-                if let Err(e) = set_leverage_with_value(&binance, &symbol, 2).await {
-                    eprintln!("Error setting leverage to 2: {}", e);
-                }
-            });
-        }
+    let actions = get_strategy().actions(&trade);
+    if actions.is_empty() {
+        println!(
+            "No action taken for status: {:?} with bias: {:?} (symbol: {})",
+            trade.status,
+            trade.bias,
+            trade.symbol
+        );
+    }
 
-        _ => {
-            println!(
-                "No action taken for status: {:?} with bias: {:?} (symbol: {})",
-                trade.status,
-                trade.bias,
-                trade.symbol
-            );
+    let context = format!("{:?}", trade.status);
+    for action in actions {
+        match action {
+            StrategyAction::Buy => open_position(&trade, "BUY", exchange).await,
+            StrategyAction::Sell => open_position(&trade, "SELL", exchange).await,
+            StrategyAction::ClosePositions => close_positions(&trade, &context, exchange).await,
+            StrategyAction::SetLeverage(leverage) => set_leverage(&trade, leverage, &context, exchange).await,
         }
     }
+
+    sync_protection(&trade, exchange).await;
 }
@@ -1,5 +1,9 @@
-use serde::{Deserialize, Serialize};
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::cmp::Ordering;
 use std::fmt;
+use std::str::FromStr;
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub enum Bias {
@@ -19,7 +23,7 @@ impl fmt::Display for Bias {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Candlestick {
     pub open_time: u64,
     pub open_price: String,
@@ -35,32 +39,130 @@ pub struct Candlestick {
     pub ignore: String,
 }
 
+/// Exact decimal field of `Trade`. It keeps the text it was parsed or
+/// formatted from, so stored blocks serialize (and hash) exactly as they
+/// were written.
+#[derive(Debug, Clone)]
+pub struct Num {
+    value: Decimal,
+    text: String,
+}
+
+impl Num {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let value = Decimal::from_str(text).map_err(|e| format!("Valor numerico invalido '{}': {}", text, e))?;
+        Ok(Num { value, text: text.to_string() })
+    }
+
+    /// `value` with `decimals` places, failing on NaN or infinity.
+    pub fn from_f64(value: f64, decimals: usize) -> Result<Self, String> {
+        if !value.is_finite() {
+            return Err(format!("Valor numerico invalido: {}", value));
+        }
+        Self::parse(&format!("{:.*}", decimals, value))
+    }
+
+    pub fn value(&self) -> Decimal {
+        self.value
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        self.value.to_f64().unwrap_or(0.0)
+    }
+}
+
+impl PartialEq for Num {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value
+    }
+}
+
+impl PartialOrd for Num {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.value.partial_cmp(&other.value)
+    }
+}
+
+impl fmt::Display for Num {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+impl Serialize for Num {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.text)
+    }
+}
+
+impl<'de> Deserialize<'de> for Num {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        Num::parse(&text).map_err(serde::de::Error::custom)
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Trade {
     pub symbol: String,
-    pub current_price: String,
-    pub cma: String,
-    pub oma: String,
+    pub current_price: Num,
+    pub cma: Num,
+    pub oma: Num,
     pub bias: Bias,
     pub status: Option<TradeStatus>,
-    pub zone_max: String,
-    pub zone_7: String,
-    pub zone_6: String,
-    pub zone_5: String,
-    pub zone_4: String,
-    pub zone_3: String,
-    pub zone_2: String,
-    pub zone_1: String,
-    pub zone_min: String,
+    pub zone_max: Num,
+    pub zone_7: Num,
+    pub zone_6: Num,
+    pub zone_5: Num,
+    pub zone_4: Num,
+    pub zone_3: Num,
+    pub zone_2: Num,
+    pub zone_1: Num,
+    pub zone_min: Num,
     pub of: usize,
-    pub performance_24: String,
-    pub performance_btc_24: String,
-    pub amplitude_ma_200: String,
-    pub volume: String,
-    pub quote_asset_volume: String,
-    pub number_of_trades: String,
-    pub taker_buy_base_asset_volume: String,
-    pub taker_buy_quote_asset_volume: String,
+    pub performance_24: Num,
+    pub performance_btc_24: Num,
+    pub amplitude_ma_200: Num,
+    pub volume: Num,
+    pub quote_asset_volume: Num,
+    pub number_of_trades: Num,
+    pub taker_buy_base_asset_volume: Num,
+    pub taker_buy_quote_asset_volume: Num,
+    /// Open time of the newest candle evaluated, in ms. Trades of one cycle
+    /// carry the `as_of` of their `MarketSnapshot`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub candle_time: Option<u64>,
+    /// Confirmation progress of a transition that needs it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transition: Option<TransitionCheck>,
+    /// Quote volume of the candles closed in the last 24h.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quote_volume_24h: Option<Num>,
+    /// ATR over `strategy.zone.atr_period` candles, in % of the price.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub atr_pct: Option<Num>,
+    /// Why this symbol was chosen to open its chain.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub selection: Option<String>,
+}
+
+/// Where a transition with confirmation rules stands after one evaluation.
+/// While `Pending` or after `Rejected`, the trade keeps the `from` status.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct TransitionCheck {
+    pub from: Option<TradeStatus>,
+    pub to: Option<TradeStatus>,
+    pub outcome: CheckOutcome,
+    pub confirmations: u32,
+    pub required: u32,
+    pub reason: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum CheckOutcome {
+    Pending,
+    Confirmed,
+    Rejected,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
@@ -88,26 +190,98 @@ pub struct ExchangeInfoResponse {
 
 #[derive(Debug, Deserialize)]
 pub struct SymbolInfo {
-    pub filters: Vec<LotSizeFilter>,
+    pub symbol: String,
+    #[serde(default)]
+    pub status: String,
+    #[serde(rename = "pricePrecision", default)]
+    pub price_precision: u32,
+    #[serde(rename = "quantityPrecision", default)]
+    pub quantity_precision: u32,
+    /// Listing time in ms; futures only.
+    #[serde(rename = "onboardDate", default)]
+    pub onboard_date: u64,
+    #[serde(rename = "contractType", default)]
+    pub contract_type: String,
+    #[serde(rename = "quoteAsset", default)]
+    pub quote_asset: String,
+    pub filters: Vec<SymbolFilter>,
+}
+
+/// Spot `/exchangeInfo`, reduced to what the universe check reads.
+#[derive(Debug, Deserialize)]
+pub struct SpotExchangeInfo {
+    pub symbols: Vec<SpotSymbol>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SpotSymbol {
+    pub symbol: String,
+    pub status: String,
+}
+
+/// One entry of futures `/ticker/24hr`.
+#[derive(Debug, Deserialize)]
+pub struct Ticker24h {
+    pub symbol: String,
+    #[serde(rename = "quoteVolume")]
+    pub quote_volume: String,
+}
+
+/// Best bid and ask of a contract, from `/ticker/bookTicker`.
+#[derive(Debug, Deserialize, Clone)]
+pub struct BookTicker {
+    #[serde(rename = "bidPrice")]
+    pub bid_price: String,
+    #[serde(rename = "askPrice")]
+    pub ask_price: String,
 }
 
 #[derive(Debug, Deserialize)]
 #[serde(tag = "filterType")]
-pub enum LotSizeFilter {
+pub enum SymbolFilter {
     #[serde(rename = "LOT_SIZE")]
     LotSize {
         #[serde(rename = "stepSize")]
         step_size: String,
+        #[serde(rename = "minQty")]
+        min_qty: String,
+        #[serde(rename = "maxQty")]
+        max_qty: String,
+    },
+    #[serde(rename = "MARKET_LOT_SIZE")]
+    MarketLotSize {
+        #[serde(rename = "stepSize")]
+        step_size: String,
+        #[serde(rename = "minQty")]
+        min_qty: String,
+        #[serde(rename = "maxQty")]
+        max_qty: String,
+    },
+    #[serde(rename = "PRICE_FILTER")]
+    PriceFilter {
+        #[serde(rename = "tickSize")]
+        tick_size: String,
+        #[serde(rename = "minPrice")]
+        min_price: String,
+        #[serde(rename = "maxPrice")]
+        max_price: String,
+    },
+    #[serde(rename = "MIN_NOTIONAL")]
+    MinNotional {
+        notional: String,
+    },
+    #[serde(rename = "PERCENT_PRICE")]
+    PercentPrice {
+        #[serde(rename = "multiplierUp")]
+        multiplier_up: String,
+        #[serde(rename = "multiplierDown")]
+        multiplier_down: String,
     },
     #[serde(other)]
     Other,
 }
 
-pub struct LotSizeInfo {
-    pub step_size: f64,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct BalanceResponse {
     pub asset: String,
 
@@ -118,7 +292,27 @@ pub struct BalanceResponse {
     pub available: String,
 }
 
-#[derive(Deserialize, Serialize, Debug)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PositionResponse {
+    pub symbol: String,
+
+    #[serde(rename = "positionAmt")]
+    pub position_amt: String,
+
+    #[serde(rename = "entryPrice")]
+    pub entry_price: String,
+
+    #[serde(rename = "markPrice", default)]
+    pub mark_price: String,
+
+    #[serde(rename = "unRealizedProfit")]
+    pub unrealized_profit: String,
+
+    #[serde(default)]
+    pub leverage: String,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct OrderResponse {
     pub symbol: String,
 
@@ -138,6 +332,9 @@ pub struct OrderResponse {
     #[serde(rename = "cummulativeQuoteQty")]
     pub cummulative_quote_qty: Option<String>,
 
+    #[serde(rename = "avgPrice", default, skip_serializing_if = "Option::is_none")]
+    pub avg_price: Option<String>,
+
     #[serde(rename = "timeInForce")]
     pub time_in_force: String,
 
@@ -148,6 +345,18 @@ pub struct OrderResponse {
     pub update_time: u64,
 }
 
+/// Positions, balances and open orders as the engine currently sees them.
+/// `source` is `stream` when served from the user data stream and `rest`
+/// when it had to be fetched.
+#[derive(Debug, Serialize)]
+pub struct AccountSnapshot {
+    pub source: String,
+    pub updated_at: u64,
+    pub balances: Vec<BalanceResponse>,
+    pub positions: Vec<PositionResponse>,
+    pub open_orders: Vec<OrderResponse>,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct OpenOrderRequest {
     pub side: String,
@@ -168,6 +377,14 @@ pub struct TradeMonitorResponse {
     pub timestamp: String,
     pub trades: Vec<TradeMonitorItem>,
     pub zone_distribution: Vec<ZoneCount>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub account: Option<MonitorAccount>,
+}
+
+#[derive(Debug, Serialize, Default)]
+pub struct MonitorAccount {
+    pub balance: Option<BalanceResponse>,
+    pub positions: Vec<PositionResponse>,
 }
 
 #[derive(Debug, Serialize)]
@@ -185,6 +402,8 @@ pub struct TradeMonitorItem {
     pub taker_buy_base_volume: f64,
     pub taker_buy_quote_volume: f64,
     pub is_active: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unrealized_pnl: Option<f64>,
 }
 
 #[derive(Debug, Serialize)]
@@ -0,0 +1,216 @@
+use crate::balance::get_futures_balance;
+use crate::client::get_binance_client;
+use crate::binance::{
+    get_book_ticker, get_candlesticks, get_candlesticks_between, get_current_price, get_position_risk,
+    unrealized_profit_of,
+};
+use crate::config::{BinanceSettings, ExecutionMode, Settings};
+use crate::dto::{BalanceResponse, BookTicker, Candlestick, OrderResponse, PositionResponse};
+use crate::leverage::{set_leverage_with_value, LeverageResponse};
+use crate::order::{
+    cancel_all_open_orders, close_all_positions, execute_future_order, order_quantity, place_protective_order,
+    to_decimal, trigger_price,
+};
+use crate::paper::PaperExchange;
+use crate::symbols::{get_symbol_registry, SymbolMeta};
+use crate::user_stream::get_account_view;
+use async_trait::async_trait;
+use once_cell::sync::Lazy;
+use std::sync::Arc;
+
+/// Everything the engine needs from a futures venue. The scheduler, API and
+/// decision code only talk to this trait, so a fake or simulated exchange
+/// can stand in for Binance.
+#[async_trait]
+pub trait Exchange: Send + Sync {
+    async fn get_candlesticks(&self, symbol: &str, interval: &str, limit: u32) -> Result<Vec<Candlestick>, String>;
+
+    /// Up to `limit` candles opened inside `[start_time, end_time]`, for
+    /// paging through history.
+    async fn get_candlesticks_between(
+        &self,
+        symbol: &str,
+        interval: &str,
+        start_time: Option<u64>,
+        end_time: Option<u64>,
+        limit: u32,
+    ) -> Result<Vec<Candlestick>, String>;
+
+    async fn get_current_price(&self, symbol: &str) -> Result<f64, String>;
+
+    /// Venue clock in ms, to tell closed candles from the forming one.
+    async fn server_time(&self) -> Result<u64, String>;
+
+    async fn get_symbol_meta(&self, symbol: &str) -> Result<SymbolMeta, String>;
+
+    /// Streamed kline of `symbol`, for exchanges that price off candles and
+    /// would otherwise only see the market move on REST calls.
+    fn on_candle(&self, _symbol: &str, _candle: &Candlestick) {}
+
+    /// Best bid and ask, or `None` where there is no order book (replays).
+    async fn get_book_ticker(&self, symbol: &str) -> Result<Option<BookTicker>, String>;
+
+    async fn get_positions(&self) -> Result<Vec<PositionResponse>, String>;
+
+    /// Positions as the venue reports them right now, bypassing any streamed
+    /// view that may not have caught up with an order just sent.
+    async fn get_venue_positions(&self) -> Result<Vec<PositionResponse>, String> {
+        self.get_positions().await
+    }
+
+    async fn execute_future_order(&self, side: &str, symbol: &str) -> Result<OrderResponse, String>;
+
+    async fn close_all_positions(&self, symbol: &str) -> Result<Vec<OrderResponse>, String>;
+
+    async fn set_leverage(&self, symbol: &str, leverage: u32) -> Result<LeverageResponse, String>;
+
+    /// Reduce-only `STOP_MARKET`/`TAKE_PROFIT_MARKET` order that closes the
+    /// whole position on `symbol` once `stop_price` is reached.
+    async fn place_protective_order(
+        &self,
+        symbol: &str,
+        side: &str,
+        order_type: &str,
+        stop_price: f64,
+    ) -> Result<OrderResponse, String>;
+
+    async fn cancel_protective_orders(&self, symbol: &str) -> Result<(), String>;
+
+    async fn get_futures_balance(&self) -> Result<Vec<BalanceResponse>, String>;
+
+    async fn get_unrealized_profit(&self, symbol: &str) -> Result<Option<f64>, String> {
+        let positions = self.get_positions().await?;
+        Ok(unrealized_profit_of(&positions, symbol))
+    }
+}
+
+pub struct BinanceExchange {
+    settings: BinanceSettings,
+    /// USDT per order, read once with the rest of the settings.
+    money: f64,
+}
+
+impl BinanceExchange {
+    pub fn new(settings: &Settings) -> Self {
+        BinanceExchange { settings: settings.binance.clone(), money: settings.money }
+    }
+}
+
+#[async_trait]
+impl Exchange for BinanceExchange {
+    async fn get_candlesticks(&self, symbol: &str, interval: &str, limit: u32) -> Result<Vec<Candlestick>, String> {
+        get_candlesticks(&self.settings.base_url, symbol, interval, limit).await
+    }
+
+    async fn get_candlesticks_between(
+        &self,
+        symbol: &str,
+        interval: &str,
+        start_time: Option<u64>,
+        end_time: Option<u64>,
+        limit: u32,
+    ) -> Result<Vec<Candlestick>, String> {
+        get_candlesticks_between(&self.settings.base_url, symbol, interval, start_time, end_time, limit).await
+    }
+
+    async fn get_current_price(&self, symbol: &str) -> Result<f64, String> {
+        get_current_price(&self.settings, symbol).await
+    }
+
+    async fn server_time(&self) -> Result<u64, String> {
+        Ok(get_binance_client().server_time().await)
+    }
+
+    async fn get_symbol_meta(&self, symbol: &str) -> Result<SymbolMeta, String> {
+        get_symbol_registry().get(&self.settings, symbol).await
+    }
+
+    async fn get_book_ticker(&self, symbol: &str) -> Result<Option<BookTicker>, String> {
+        get_book_ticker(&self.settings, symbol).await.map(Some)
+    }
+
+    async fn get_positions(&self) -> Result<Vec<PositionResponse>, String> {
+        if let Some(positions) = get_account_view().positions() {
+            return Ok(positions);
+        }
+        get_position_risk(&self.settings).await
+    }
+
+    async fn get_venue_positions(&self) -> Result<Vec<PositionResponse>, String> {
+        get_position_risk(&self.settings).await
+    }
+
+    async fn execute_future_order(&self, side: &str, symbol: &str) -> Result<OrderResponse, String> {
+        let price = to_decimal(self.get_current_price(symbol).await?)?;
+        let meta = self.get_symbol_meta(symbol).await?;
+        let money = to_decimal(self.money)?;
+
+        let quantity = order_quantity(money, price, &meta)?;
+
+        println!(
+            "Sending order with side: '{}', quantity: '{}' (USDT: {}, Cryptocurrency Price: {}, StepSize: {})",
+            side, quantity, money, price, meta.market_step_size
+        );
+
+        execute_future_order(&self.settings, side, symbol, quantity.to_string()).await
+    }
+
+    async fn close_all_positions(&self, symbol: &str) -> Result<Vec<OrderResponse>, String> {
+        let positions = self.get_positions().await?;
+        let closed = close_all_positions(&self.settings, symbol, positions).await?;
+        // The position is already closed here; a failed cancel must not
+        // hide the fills from the caller.
+        if let Err(e) = self.cancel_protective_orders(symbol).await {
+            eprintln!("Error cancelling protective orders for {} after close: {}", symbol, e);
+        }
+        Ok(closed)
+    }
+
+    async fn set_leverage(&self, symbol: &str, leverage: u32) -> Result<LeverageResponse, String> {
+        set_leverage_with_value(&self.settings, symbol, leverage)
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    async fn place_protective_order(
+        &self,
+        symbol: &str,
+        side: &str,
+        order_type: &str,
+        stop_price: f64,
+    ) -> Result<OrderResponse, String> {
+        let meta = self.get_symbol_meta(symbol).await?;
+        let reference = to_decimal(self.get_current_price(symbol).await?)?;
+        let stop_price = trigger_price(to_decimal(stop_price)?, reference, &meta)?;
+        place_protective_order(&self.settings, symbol, side, order_type, stop_price.to_string()).await
+    }
+
+    async fn cancel_protective_orders(&self, symbol: &str) -> Result<(), String> {
+        // Nothing to cancel when the user data stream says the book is empty.
+        if get_account_view().has_open_orders(symbol) == Some(false) {
+            return Ok(());
+        }
+        cancel_all_open_orders(&self.settings, symbol).await
+    }
+
+    async fn get_futures_balance(&self) -> Result<Vec<BalanceResponse>, String> {
+        if let Some(balances) = get_account_view().balances() {
+            return Ok(balances);
+        }
+        get_futures_balance(&self.settings).await.map_err(|e| e.to_string())
+    }
+}
+
+static EXCHANGE: Lazy<Arc<dyn Exchange>> = Lazy::new(|| {
+    let settings = Settings::load();
+    let binance: Arc<dyn Exchange> = Arc::new(BinanceExchange::new(&settings));
+
+    match settings.binance.mode {
+        ExecutionMode::Live => binance,
+        ExecutionMode::Paper => Arc::new(PaperExchange::new(binance, &settings)),
+    }
+});
+
+pub fn get_exchange() -> Arc<dyn Exchange> {
+    EXCHANGE.clone()
+}
@@ -0,0 +1,131 @@
+use crate::candle_store::{interval_ms, parse_date, CandleStore};
+use crate::config::Settings;
+use crate::dto::Candlestick;
+use crate::exchange::{get_exchange, Exchange};
+use crate::strategy::get_strategy;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const PAGE_LIMIT: u32 = 1000;
+const PAGE_DELAY: Duration = Duration::from_millis(250);
+
+/// Brings the stored series for `symbol` up to date: backfills everything
+/// missing since `since`, closes interior gaps and appends new candles.
+/// Only closed candles are stored.
+pub async fn sync_symbol(
+    exchange: &dyn Exchange,
+    store: &CandleStore,
+    symbol: &str,
+    interval: &str,
+    since: u64,
+) -> Result<usize, String> {
+    let step = interval_ms(interval).ok_or_else(|| format!("Unsupported interval '{}'", interval))?;
+    let now = now_ms();
+    let last_closed_open = (now / step) * step - step;
+
+    let stored = store.read(symbol, interval)?;
+    let mut missing = vec![];
+
+    match (stored.first(), stored.last()) {
+        (Some(first), Some(last)) => {
+            if first.open_time > since {
+                missing.push((since, first.open_time - step));
+            }
+            for pair in stored.windows(2) {
+                if pair[1].open_time - pair[0].open_time > step {
+                    missing.push((pair[0].open_time + step, pair[1].open_time - step));
+                }
+            }
+            if last.open_time < last_closed_open {
+                missing.push((last.open_time + step, last_closed_open));
+            }
+        }
+        _ => missing.push((since, last_closed_open)),
+    }
+
+    let mut added = 0;
+    for (from, to) in missing {
+        let candles = fetch_range(exchange, symbol, interval, from, to, now).await?;
+        added += store.merge(symbol, interval, &candles)?;
+    }
+
+    Ok(added)
+}
+
+/// Pages backwards from `to` until `from` or the start of the listing.
+async fn fetch_range(
+    exchange: &dyn Exchange,
+    symbol: &str,
+    interval: &str,
+    from: u64,
+    to: u64,
+    now: u64,
+) -> Result<Vec<Candlestick>, String> {
+    let mut end = to;
+    let mut candles = vec![];
+
+    while end >= from {
+        // With only `endTime` set Binance returns the newest `limit` candles
+        // before it, which is what lets us walk backwards.
+        let page = exchange
+            .get_candlesticks_between(symbol, interval, None, Some(end), PAGE_LIMIT)
+            .await?;
+
+        let Some(first) = page.first() else { break };
+        let first_open = first.open_time;
+        let listing_start = page.len() < PAGE_LIMIT as usize;
+
+        candles.extend(page.into_iter().filter(|c| c.open_time >= from && c.close_time < now));
+
+        if listing_start || first_open <= from {
+            break;
+        }
+        end = first_open - 1;
+        tokio::time::sleep(PAGE_DELAY).await;
+    }
+
+    Ok(candles)
+}
+
+/// `download [--since YYYY-MM-DD] [--symbols A,B,C]`
+pub async fn run_cli(args: &[String]) -> std::io::Result<()> {
+    let settings = Settings::load();
+
+    let mut since = settings.history.since.clone();
+    let mut symbols = settings.cryptos.clone();
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match (arg.as_str(), iter.next()) {
+            ("--since", Some(value)) => since = value.clone(),
+            ("--symbols", Some(value)) => symbols = value.split(',').map(|s| s.trim().to_uppercase()).collect(),
+            _ => return Err(std::io::Error::other(format!("Unknown or incomplete argument: {}", arg))),
+        }
+    }
+
+    let since = parse_date(&since).map_err(std::io::Error::other)?;
+    let strategy = get_strategy();
+    let references: Vec<String> = symbols.iter().map(|s| strategy.reference_symbol(s)).collect();
+    symbols.extend(references);
+    symbols.sort();
+    symbols.dedup();
+
+    let exchange = get_exchange();
+    let store = CandleStore::new(&settings.history.dir);
+    let interval = &settings.binance.interval;
+
+    for symbol in &symbols {
+        match sync_symbol(exchange.as_ref(), &store, symbol, interval, since).await {
+            Ok(added) => println!("{} {}: {} new candles", symbol, interval, added),
+            Err(e) => eprintln!("{} {}: {}", symbol, interval, e),
+        }
+    }
+
+    Ok(())
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64
+}
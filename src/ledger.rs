@@ -0,0 +1,158 @@
+use crate::audit::Checkpoint;
+use crate::blockchain::{ClosedOperation, TradeBlock};
+use crate::config::LedgerSettings;
+use once_cell::sync::OnceCell;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+static LEDGER: OnceCell<Ledger> = OnceCell::new();
+
+/// Append-only copy of the trade blockchains: one JSON block per line in
+/// `{dir}/{SYMBOL}.jsonl`. Chains that are removed become one
+/// `ClosedOperation` line in `{dir}/archive.jsonl`, and signed Merkle
+/// checkpoints go to `{dir}/checkpoints.jsonl`.
+pub struct Ledger {
+    dir: PathBuf,
+}
+
+impl Ledger {
+    pub fn new(settings: &LedgerSettings) -> Result<Self, String> {
+        let dir = PathBuf::from(&settings.dir);
+        fs::create_dir_all(&dir)
+            .map_err(|e| format!("Erro ao criar {}: {}", dir.display(), e))?;
+        Ok(Ledger { dir })
+    }
+
+    fn path(&self, symbol: &str) -> PathBuf {
+        self.dir.join(format!("{}.jsonl", symbol))
+    }
+
+    fn archive_path(&self) -> PathBuf {
+        self.dir.join("archive.jsonl")
+    }
+
+    fn checkpoints_path(&self) -> PathBuf {
+        self.dir.join("checkpoints.jsonl")
+    }
+
+    pub fn append(&self, symbol: &str, block: &TradeBlock) -> Result<(), String> {
+        append_line(&self.path(symbol), block)
+    }
+
+    /// Records `operation` in the archive, then drops its active file. A crash
+    /// in between leaves both, which `restore_blockchains` cleans up.
+    pub fn archive(&self, operation: &ClosedOperation) -> Result<(), String> {
+        append_line(&self.archive_path(), operation)?;
+        self.discard(&operation.symbol)
+    }
+
+    pub fn discard(&self, symbol: &str) -> Result<(), String> {
+        let path = self.path(symbol);
+        if !path.exists() {
+            return Ok(());
+        }
+        fs::remove_file(&path).map_err(|e| format!("Erro ao remover {}: {}", path.display(), e))
+    }
+
+    pub fn load_archive(&self) -> Result<Vec<ClosedOperation>, String> {
+        let path = self.archive_path();
+        if !path.exists() {
+            return Ok(vec![]);
+        }
+        read_lines(&path)
+    }
+
+    pub fn append_checkpoint(&self, checkpoint: &Checkpoint) -> Result<(), String> {
+        append_line(&self.checkpoints_path(), checkpoint)
+    }
+
+    pub fn load_checkpoints(&self) -> Result<Vec<Checkpoint>, String> {
+        let path = self.checkpoints_path();
+        if !path.exists() {
+            return Ok(vec![]);
+        }
+        read_lines(&path)
+    }
+
+    /// Reads every active chain. A last line cut short by a crash is
+    /// truncated away; any other unreadable line is an error.
+    pub fn load(&self) -> Result<HashMap<String, Vec<TradeBlock>>, String> {
+        let mut chains = HashMap::new();
+        let entries = fs::read_dir(&self.dir).map_err(|e| format!("Erro ao ler {}: {}", self.dir.display(), e))?;
+
+        for entry in entries {
+            let path = entry.map_err(|e| e.to_string())?.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("jsonl")
+                || path == self.archive_path()
+                || path == self.checkpoints_path()
+            {
+                continue;
+            }
+            let Some(symbol) = path.file_stem().and_then(|s| s.to_str()).map(str::to_string) else {
+                continue;
+            };
+
+            let blocks: Vec<TradeBlock> = read_lines(&path)?;
+            if !blocks.is_empty() {
+                chains.insert(symbol, blocks);
+            }
+        }
+
+        Ok(chains)
+    }
+}
+
+fn append_line<T: Serialize>(path: &Path, value: &T) -> Result<(), String> {
+    let line = serde_json::to_string(value).map_err(|e| e.to_string())?;
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| format!("Erro ao abrir {}: {}", path.display(), e))?;
+
+    writeln!(file, "{}", line)
+        .and_then(|_| file.sync_data())
+        .map_err(|e| format!("Erro ao gravar {}: {}", path.display(), e))
+}
+
+fn read_lines<T: DeserializeOwned>(path: &Path) -> Result<Vec<T>, String> {
+    let content = fs::read_to_string(path).map_err(|e| format!("Erro ao ler {}: {}", path.display(), e))?;
+    let mut values = Vec::new();
+    let mut valid_len = 0;
+
+    for line in content.split_inclusive('\n') {
+        // Every append ends with a newline, so a line without one was cut short.
+        if !line.ends_with('\n') {
+            eprintln!("Descartando linha incompleta no fim de {}", path.display());
+            File::options()
+                .write(true)
+                .open(path)
+                .and_then(|file| file.set_len(valid_len as u64))
+                .map_err(|e| format!("Erro ao truncar {}: {}", path.display(), e))?;
+            break;
+        }
+
+        let value = serde_json::from_str::<T>(line.trim_end())
+            .map_err(|e| format!("Linha invalida em {}: {}", path.display(), e))?;
+        values.push(value);
+        valid_len += line.len();
+    }
+
+    Ok(values)
+}
+
+/// Opens the ledger for the running process. Only the server calls this;
+/// replays such as the backtester keep their chains in memory.
+pub fn init_ledger(settings: &LedgerSettings) -> Result<&'static Ledger, String> {
+    let ledger = Ledger::new(settings)?;
+    Ok(LEDGER.get_or_init(|| ledger))
+}
+
+pub fn get_ledger() -> Option<&'static Ledger> {
+    LEDGER.get()
+}
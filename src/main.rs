@@ -1,22 +1,42 @@
 mod dto;
 mod api;
+mod audit;
+mod backtest;
 mod trade;
 mod config;
 mod blockchain;
+mod candle_store;
 mod order;
 mod balance;
 mod binance;
+mod client;
 mod credential;
 mod schedule;
 mod leverage;
+mod ledger;
 mod decide;
+mod exchange;
+mod history;
+mod mock;
 mod monitor;
-mod status_trade;
+mod paper;
+mod protection;
+mod strategy;
+mod selection;
+mod signing;
+mod snapshot;
 mod spy;
+mod state_machine;
+mod stream;
 mod swap;
+mod symbols;
+mod universe;
+mod user_stream;
 mod crypto_candidate;
+mod zone_strategy;
 
 use actix_web::{App, HttpServer};
+use crate::config::{ExecutionMode, Settings};
 use api::{
     post_trades_start,
     post_trades_stop,
@@ -30,11 +50,49 @@ use api::{
     post_close_all_positions,
     put_leverage,
     get_trades_spy,
+    get_trades_candidates,
     get_trades_monitor, // <-- NOVO
+    get_trades_account,
+    get_trades_symbol,
+    get_trades_archive,
+    get_trades_archive_by_id,
+    get_ledger_export,
+    get_machine_graph,
+    get_trades_universe,
 };
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) == Some("backtest") {
+        return backtest::run_cli(&args[2..]).await;
+    }
+    if args.get(1).map(String::as_str) == Some("download") {
+        return history::run_cli(&args[2..]).await;
+    }
+    if args.get(1).map(String::as_str) == Some("verify-ledger") {
+        return audit::run_cli(&args[2..]);
+    }
+    if args.get(1).map(String::as_str) == Some("machine-graph") {
+        return state_machine::run_cli(&args[2..]);
+    }
+
+    let settings = Settings::load();
+    let strategy = strategy::build_strategy(&settings).map_err(std::io::Error::other)?;
+    println!("Estrategia: {}", strategy.name());
+    if settings.ledger.enabled {
+        let restored = blockchain::restore_blockchains(&settings.ledger).map_err(std::io::Error::other)?;
+        println!("{} blockchain(s) restaurada(s) de {}", restored, settings.ledger.dir);
+        audit::start_checkpoints(&settings.ledger);
+    }
+    if settings.mock.enabled {
+        actix_web::rt::spawn(mock::start_mock_server(&settings.mock)?);
+    }
+    universe::start_universe(&settings).await;
+    if settings.binance.mode == ExecutionMode::Live && settings.stream.user_data && !settings.mock.enabled {
+        user_stream::start_user_stream(settings.clone());
+    }
+
     println!("Server running at http://localhost:8080");
 
     HttpServer::new(|| {
@@ -51,7 +109,15 @@ async fn main() -> std::io::Result<()> {
             .service(post_close_all_positions)
             .service(put_leverage)
             .service(get_trades_spy)
+            .service(get_trades_candidates)
             .service(get_trades_monitor) // <-- NOVO
+            .service(get_trades_account)
+            .service(get_trades_symbol)
+            .service(get_trades_archive)
+            .service(get_trades_archive_by_id)
+            .service(get_ledger_export)
+            .service(get_machine_graph)
+            .service(get_trades_universe)
     })
     .bind(("127.0.0.1", 8080))?
     .run()
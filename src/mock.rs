@@ -0,0 +1,719 @@
+use crate::config::MockSettings;
+use crate::credential::{get_credentials, Credential};
+use actix_web::dev::Server;
+use actix_web::{web, App, HttpRequest, HttpResponse, HttpServer};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+type HmacSha256 = Hmac<Sha256>;
+
+const HOUR_MS: u64 = 3_600_000;
+/// Listing time reported for every mock contract (2020-01-01).
+const MOCK_ONBOARD_DATE: u64 = 1_577_836_800_000;
+
+/// Price script for the mock exchange. Each symbol follows a piecewise
+/// linear path through `(candle index, close price)` waypoints.
+#[derive(Debug, Deserialize, Clone)]
+pub struct MockScript {
+    #[serde(default = "default_step_size")]
+    pub step_size: String,
+    #[serde(default)]
+    pub start_index: usize,
+    pub symbols: HashMap<String, MockPath>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct MockPath {
+    pub waypoints: Vec<(usize, f64)>,
+}
+
+fn default_step_size() -> String {
+    "0.001".to_string()
+}
+
+impl MockPath {
+    fn price_at(&self, index: usize) -> f64 {
+        let points = &self.waypoints;
+        if points.is_empty() {
+            return 0.0;
+        }
+        if index <= points[0].0 {
+            return points[0].1;
+        }
+
+        for pair in points.windows(2) {
+            let (i0, p0) = pair[0];
+            let (i1, p1) = pair[1];
+            if index <= i1 {
+                let t = (index - i0) as f64 / (i1 - i0).max(1) as f64;
+                return p0 + (p1 - p0) * t;
+            }
+        }
+
+        points[points.len() - 1].1
+    }
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct MockOrder {
+    pub order_id: u64,
+    pub cursor: usize,
+    pub symbol: String,
+    pub side: String,
+    pub order_type: String,
+    pub quantity: f64,
+    pub price: f64,
+    pub reduce_only: bool,
+}
+
+/// Resting `closePosition` stop or take-profit order.
+#[derive(Debug, Serialize, Clone)]
+pub struct MockStop {
+    pub order_id: u64,
+    pub symbol: String,
+    pub side: String,
+    pub order_type: String,
+    pub stop_price: f64,
+}
+
+impl MockStop {
+    fn is_hit(&self, price: f64) -> bool {
+        let falling = (self.order_type == "STOP_MARKET") == (self.side == "SELL");
+        if falling { price <= self.stop_price } else { price >= self.stop_price }
+    }
+}
+
+#[derive(Debug, Default)]
+struct MockPosition {
+    amount: f64,
+    entry_price: f64,
+}
+
+struct MockState {
+    script: MockScript,
+    cursor: usize,
+    base_time: u64,
+    balance: f64,
+    positions: HashMap<String, MockPosition>,
+    leverage: HashMap<String, u32>,
+    orders: Vec<MockOrder>,
+    stops: Vec<MockStop>,
+    next_order_id: u64,
+}
+
+impl MockState {
+    fn next_id(&mut self) -> u64 {
+        self.next_order_id += 1;
+        self.next_order_id
+    }
+
+    /// Fills at the current price and records the order. Returns the order
+    /// id and fill price.
+    fn fill(
+        &mut self,
+        symbol: &str,
+        side: &str,
+        order_type: &str,
+        quantity: f64,
+        reduce_only: bool,
+    ) -> Result<(u64, f64), HttpResponse> {
+        let price = self.price(symbol).ok_or_else(invalid_symbol)?;
+
+        let signed_quantity = if side == "BUY" { quantity } else { -quantity };
+        let position = self.positions.entry(symbol.to_string()).or_default();
+
+        if reduce_only && (position.amount == 0.0 || position.amount.signum() == signed_quantity.signum()) {
+            return Err(binance_error(-2022, "ReduceOnly Order is rejected."));
+        }
+
+        let total = position.amount + signed_quantity;
+        if position.amount == 0.0 || position.amount.signum() == signed_quantity.signum() {
+            position.entry_price = (position.amount.abs() * position.entry_price + quantity * price) / total.abs();
+        } else if total.signum() != position.amount.signum() && total.abs() > 1e-12 {
+            position.entry_price = price;
+        }
+        position.amount = if total.abs() < 1e-12 { 0.0 } else { total };
+
+        let order_id = self.next_id();
+        let cursor = self.cursor;
+        self.orders.push(MockOrder {
+            order_id,
+            cursor,
+            symbol: symbol.to_string(),
+            side: side.to_string(),
+            order_type: order_type.to_string(),
+            quantity,
+            price,
+            reduce_only,
+        });
+
+        Ok((order_id, price))
+    }
+
+    /// Moves every path forward one candle at a time, firing resting stops
+    /// on the way.
+    fn advance(&mut self, steps: usize) {
+        for _ in 0..steps {
+            self.cursor += 1;
+            self.trigger_stops();
+        }
+    }
+
+    fn trigger_stops(&mut self) {
+        let hit: Vec<MockStop> = self
+            .stops
+            .iter()
+            .filter(|stop| self.price(&stop.symbol).is_some_and(|price| stop.is_hit(price)))
+            .cloned()
+            .collect();
+
+        for stop in hit {
+            // `closePosition` orders cancel their sibling once the position is gone.
+            self.stops.retain(|s| s.symbol != stop.symbol);
+
+            let amount = self.positions.get(&stop.symbol).map(|p| p.amount).unwrap_or(0.0);
+            if amount != 0.0 {
+                let _ = self.fill(&stop.symbol, &stop.side, &stop.order_type, amount.abs(), true);
+            }
+        }
+    }
+
+    fn price(&self, symbol: &str) -> Option<f64> {
+        self.script.symbols.get(symbol).map(|p| p.price_at(self.cursor))
+    }
+
+    fn klines(&self, symbol: &str, limit: usize) -> Option<Vec<serde_json::Value>> {
+        let path = self.script.symbols.get(symbol)?;
+        let end = self.cursor;
+        let start = (end + 1).saturating_sub(limit);
+
+        Some(
+            (start..=end)
+                .map(|i| {
+                    let close = path.price_at(i);
+                    let open = if i == 0 { close } else { path.price_at(i - 1) };
+                    let high = open.max(close) * 1.001;
+                    let low = open.min(close) * 0.999;
+                    let open_time = self.base_time + i as u64 * HOUR_MS;
+                    json!([
+                        open_time,
+                        open.to_string(),
+                        high.to_string(),
+                        low.to_string(),
+                        close.to_string(),
+                        "1000",
+                        open_time + HOUR_MS - 1,
+                        (1000.0 * close).to_string(),
+                        100,
+                        "500",
+                        (500.0 * close).to_string(),
+                        "0"
+                    ])
+                })
+                .collect(),
+        )
+    }
+}
+
+type SharedState = web::Data<Mutex<MockState>>;
+
+/// Builds the mock server. Point `base_url` at `http://host:port/api/v3`,
+/// `future_url` at `/fapi/v1` and `future_url_v2` at `/fapi/v2`.
+pub fn start_mock_server(settings: &MockSettings) -> std::io::Result<Server> {
+    let script = load_script(&settings.script)?;
+
+    let now = now_ms();
+    let state = web::Data::new(Mutex::new(MockState {
+        cursor: script.start_index,
+        base_time: now - now % HOUR_MS - script.start_index as u64 * HOUR_MS,
+        script,
+        balance: settings.balance,
+        positions: HashMap::new(),
+        leverage: HashMap::new(),
+        orders: vec![],
+        stops: vec![],
+        next_order_id: 0,
+    }));
+
+    if settings.step_secs > 0 {
+        let state = state.clone();
+        let step = Duration::from_secs(settings.step_secs);
+        actix_web::rt::spawn(async move {
+            let mut interval = tokio::time::interval(step);
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                state.lock().unwrap().advance(1);
+            }
+        });
+    }
+
+    let credential = get_credentials();
+    println!("Mock Binance running at http://127.0.0.1:{}", settings.port);
+
+    Ok(HttpServer::new(move || {
+        App::new()
+            .app_data(state.clone())
+            .app_data(web::Data::new(credential.clone()))
+            .route("/api/v3/uiKlines", web::get().to(get_klines))
+            .route("/fapi/v1/ticker/price", web::get().to(get_price))
+            .route("/fapi/v1/ticker/bookTicker", web::get().to(get_book_ticker))
+            .route("/fapi/v1/ticker/24hr", web::get().to(get_tickers_24h))
+            .route("/api/v3/exchangeInfo", web::get().to(get_spot_exchange_info))
+            .route("/fapi/v1/exchangeInfo", web::get().to(get_exchange_info))
+            .route("/fapi/v1/time", web::get().to(get_time))
+            .route("/fapi/v1/order", web::post().to(post_order))
+            .route("/fapi/v1/openOrders", web::get().to(get_open_orders))
+            .route("/fapi/v1/allOpenOrders", web::delete().to(delete_all_open_orders))
+            .route("/fapi/v1/leverage", web::post().to(post_leverage))
+            .route("/fapi/v2/positionRisk", web::get().to(get_position_risk))
+            .route("/fapi/v2/balance", web::get().to(get_balance))
+            .route("/mock/advance", web::post().to(post_advance))
+            .route("/mock/orders", web::get().to(get_orders))
+    })
+    .bind(("127.0.0.1", settings.port))?
+    .run())
+}
+
+fn load_script(path: &str) -> std::io::Result<MockScript> {
+    let mut script: MockScript = config::Config::builder()
+        .add_source(config::File::with_name(path).required(true))
+        .build()
+        .and_then(|c| c.try_deserialize())
+        .map_err(|e| std::io::Error::other(format!("Failed to load mock script: {}", e)))?;
+
+    // The config crate lowercases table keys.
+    script.symbols = script
+        .symbols
+        .into_iter()
+        .map(|(symbol, mut path)| {
+            // `price_at` walks the waypoints in candle order.
+            path.waypoints.sort_by_key(|&(index, _)| index);
+            (symbol.to_uppercase(), path)
+        })
+        .collect();
+    Ok(script)
+}
+
+fn binance_error(code: i64, msg: &str) -> HttpResponse {
+    HttpResponse::BadRequest().json(json!({ "code": code, "msg": msg }))
+}
+
+fn invalid_symbol() -> HttpResponse {
+    binance_error(-1121, "Invalid symbol.")
+}
+
+/// Checks the API key header and the HMAC of everything before `&signature=`
+/// against `Credential`, the same way Binance does.
+fn verify_signature(req: &HttpRequest, credential: &Credential) -> Result<HashMap<String, String>, HttpResponse> {
+    let api_key = req
+        .headers()
+        .get("X-MBX-APIKEY")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+    if api_key != credential.key {
+        return Err(HttpResponse::Unauthorized()
+            .json(json!({ "code": -2015, "msg": "Invalid API-key, IP, or permissions for action." })));
+    }
+
+    let query = req.query_string();
+    let (payload, signature) = query
+        .rsplit_once("&signature=")
+        .ok_or_else(|| binance_error(-1102, "Mandatory parameter 'signature' was not sent."))?;
+
+    let mut mac = HmacSha256::new_from_slice(credential.secret.as_bytes()).unwrap();
+    mac.update(payload.as_bytes());
+    if hex::encode(mac.finalize().into_bytes()) != signature {
+        return Err(binance_error(-1022, "Signature for this request is not valid."));
+    }
+
+    Ok(url::form_urlencoded::parse(payload.as_bytes()).into_owned().collect())
+}
+
+async fn get_klines(state: SharedState, query: web::Query<HashMap<String, String>>) -> HttpResponse {
+    let symbol = query.get("symbol").cloned().unwrap_or_default();
+    let limit = query.get("limit").and_then(|l| l.parse().ok()).unwrap_or(500);
+
+    match state.lock().unwrap().klines(&symbol, limit) {
+        Some(klines) => HttpResponse::Ok().json(klines),
+        None => invalid_symbol(),
+    }
+}
+
+async fn get_price(state: SharedState, query: web::Query<HashMap<String, String>>) -> HttpResponse {
+    let symbol = query.get("symbol").cloned().unwrap_or_default();
+
+    match state.lock().unwrap().price(&symbol) {
+        Some(price) => HttpResponse::Ok().json(json!({ "symbol": symbol, "price": price.to_string() })),
+        None => invalid_symbol(),
+    }
+}
+
+/// One tick either side of the scripted price.
+async fn get_book_ticker(state: SharedState, query: web::Query<HashMap<String, String>>) -> HttpResponse {
+    let symbol = query.get("symbol").cloned().unwrap_or_default();
+
+    match state.lock().unwrap().price(&symbol) {
+        Some(price) => HttpResponse::Ok().json(json!({
+            "symbol": symbol,
+            "bidPrice": format!("{:.2}", price - 0.01),
+            "askPrice": format!("{:.2}", price + 0.01),
+        })),
+        None => invalid_symbol(),
+    }
+}
+
+async fn get_exchange_info(state: SharedState) -> HttpResponse {
+    let state = state.lock().unwrap();
+    let step_size = &state.script.step_size;
+    let quantity_precision = step_size.split('.').nth(1).map(|d| d.trim_end_matches('0').len()).unwrap_or(0);
+
+    // Like Binance futures, the full list is returned and `symbol` is ignored.
+    let symbols: Vec<_> = state
+        .script
+        .symbols
+        .keys()
+        .map(|symbol| {
+            json!({
+                "symbol": symbol,
+                "status": "TRADING",
+                "pricePrecision": 2,
+                "quantityPrecision": quantity_precision,
+                "onboardDate": MOCK_ONBOARD_DATE,
+                "contractType": "PERPETUAL",
+                "quoteAsset": "USDT",
+                "filters": [{
+                    "filterType": "PRICE_FILTER",
+                    "minPrice": "0.01",
+                    "maxPrice": "10000000",
+                    "tickSize": "0.01"
+                }, {
+                    "filterType": "LOT_SIZE",
+                    "stepSize": step_size,
+                    "minQty": step_size,
+                    "maxQty": "1000000"
+                }, {
+                    "filterType": "MARKET_LOT_SIZE",
+                    "stepSize": step_size,
+                    "minQty": step_size,
+                    "maxQty": "100000"
+                }, {
+                    "filterType": "MIN_NOTIONAL",
+                    "notional": "5"
+                }, {
+                    "filterType": "PERCENT_PRICE",
+                    "multiplierUp": "1.0500",
+                    "multiplierDown": "0.9500",
+                    "multiplierDecimal": "4"
+                }]
+            })
+        })
+        .collect();
+
+    HttpResponse::Ok().json(json!({ "symbols": symbols }))
+}
+
+/// Spot pairs of the scripted symbols, all trading.
+async fn get_spot_exchange_info(state: SharedState) -> HttpResponse {
+    let state = state.lock().unwrap();
+    let symbols: Vec<_> = state
+        .script
+        .symbols
+        .keys()
+        .map(|symbol| json!({ "symbol": symbol, "status": "TRADING" }))
+        .collect();
+
+    HttpResponse::Ok().json(json!({ "symbols": symbols }))
+}
+
+/// Quote volume of the last 24 scripted candles of every symbol.
+async fn get_tickers_24h(state: SharedState) -> HttpResponse {
+    let state = state.lock().unwrap();
+    let tickers: Vec<_> = state
+        .script
+        .symbols
+        .keys()
+        .map(|symbol| {
+            let volume: f64 = (state.cursor.saturating_sub(23)..=state.cursor)
+                .map(|i| 1000.0 * state.script.symbols[symbol].price_at(i))
+                .sum();
+            json!({ "symbol": symbol, "quoteVolume": volume.to_string() })
+        })
+        .collect();
+
+    HttpResponse::Ok().json(tickers)
+}
+
+async fn get_time() -> HttpResponse {
+    HttpResponse::Ok().json(json!({ "serverTime": now_ms() }))
+}
+
+async fn post_order(req: HttpRequest, state: SharedState, credential: web::Data<Arc<Credential>>) -> HttpResponse {
+    let params = match verify_signature(&req, &credential) {
+        Ok(params) => params,
+        Err(response) => return response,
+    };
+
+    let symbol = params.get("symbol").cloned().unwrap_or_default();
+    let side = params.get("side").cloned().unwrap_or_default();
+    let order_type = params.get("type").cloned().unwrap_or_else(|| "MARKET".to_string());
+    let reduce_only = params.get("reduceOnly").map(|v| v == "true").unwrap_or(false);
+
+    let mut state = state.lock().unwrap();
+
+    if order_type == "STOP_MARKET" || order_type == "TAKE_PROFIT_MARKET" {
+        if state.price(&symbol).is_none() {
+            return invalid_symbol();
+        }
+        if params.get("closePosition").map(String::as_str) != Some("true") {
+            return binance_error(-1106, "Mock only supports closePosition stop orders.");
+        }
+        let stop_price = match params.get("stopPrice").and_then(|p| p.parse::<f64>().ok()) {
+            Some(p) if p > 0.0 => p,
+            _ => return binance_error(-1102, "Mandatory parameter 'stopPrice' was not sent."),
+        };
+
+        let stop = MockStop {
+            order_id: state.next_id(),
+            symbol,
+            side,
+            order_type,
+            stop_price,
+        };
+        state.stops.push(stop.clone());
+        return HttpResponse::Ok().json(stop_json(&stop));
+    }
+
+    let quantity = match params.get("quantity").and_then(|q| q.parse::<f64>().ok()) {
+        Some(q) if q > 0.0 => q,
+        _ => return binance_error(-1100, "Illegal characters found in parameter 'quantity'."),
+    };
+
+    let (order_id, price) = match state.fill(&symbol, &side, &order_type, quantity, reduce_only) {
+        Ok(filled) => filled,
+        Err(response) => return response,
+    };
+
+    // Like Binance, the default ACK response is sent before the fill is known.
+    let result = params.get("newOrderRespType").map(String::as_str) == Some("RESULT");
+    let (status, avg_price, executed) = if result {
+        ("FILLED", price.to_string(), quantity)
+    } else {
+        ("NEW", "0.00".to_string(), 0.0)
+    };
+
+    HttpResponse::Ok().json(json!({
+        "symbol": symbol,
+        "orderId": order_id,
+        "status": status,
+        "side": side,
+        "price": "0",
+        "avgPrice": avg_price,
+        "origQty": quantity.to_string(),
+        "executedQty": executed.to_string(),
+        "cumQuote": (quantity * price).to_string(),
+        "timeInForce": "GTC",
+        "type": order_type,
+        "reduceOnly": reduce_only,
+        "updateTime": now_ms()
+    }))
+}
+
+fn stop_json(stop: &MockStop) -> serde_json::Value {
+    json!({
+        "symbol": stop.symbol,
+        "orderId": stop.order_id,
+        "status": "NEW",
+        "side": stop.side,
+        "price": "0",
+        "stopPrice": stop.stop_price.to_string(),
+        "origQty": "0",
+        "executedQty": "0",
+        "timeInForce": "GTE_GTC",
+        "type": stop.order_type,
+        "closePosition": true,
+        "updateTime": now_ms()
+    })
+}
+
+async fn get_open_orders(req: HttpRequest, state: SharedState, credential: web::Data<Arc<Credential>>) -> HttpResponse {
+    if let Err(response) = verify_signature(&req, &credential) {
+        return response;
+    }
+
+    let orders: Vec<_> = state.lock().unwrap().stops.iter().map(stop_json).collect();
+    HttpResponse::Ok().json(orders)
+}
+
+async fn delete_all_open_orders(
+    req: HttpRequest,
+    state: SharedState,
+    credential: web::Data<Arc<Credential>>,
+) -> HttpResponse {
+    let params = match verify_signature(&req, &credential) {
+        Ok(params) => params,
+        Err(response) => return response,
+    };
+
+    let symbol = params.get("symbol").cloned().unwrap_or_default();
+    state.lock().unwrap().stops.retain(|s| s.symbol != symbol);
+
+    HttpResponse::Ok().json(json!({ "code": 200, "msg": "The operation of cancel all open order is done." }))
+}
+
+async fn post_leverage(req: HttpRequest, state: SharedState, credential: web::Data<Arc<Credential>>) -> HttpResponse {
+    let params = match verify_signature(&req, &credential) {
+        Ok(params) => params,
+        Err(response) => return response,
+    };
+
+    let symbol = params.get("symbol").cloned().unwrap_or_default();
+    let leverage = params.get("leverage").and_then(|l| l.parse::<u32>().ok()).unwrap_or(1);
+
+    let mut state = state.lock().unwrap();
+    if !state.script.symbols.contains_key(&symbol) {
+        return invalid_symbol();
+    }
+    state.leverage.insert(symbol.clone(), leverage);
+
+    HttpResponse::Ok().json(json!({ "symbol": symbol, "leverage": leverage, "maxNotionalValue": "1000000" }))
+}
+
+async fn get_position_risk(req: HttpRequest, state: SharedState, credential: web::Data<Arc<Credential>>) -> HttpResponse {
+    if let Err(response) = verify_signature(&req, &credential) {
+        return response;
+    }
+
+    let state = state.lock().unwrap();
+    let positions: Vec<_> = state
+        .positions
+        .iter()
+        .map(|(symbol, position)| {
+            let mark = state.price(symbol).unwrap_or(position.entry_price);
+            json!({
+                "symbol": symbol,
+                "positionAmt": position.amount.to_string(),
+                "entryPrice": position.entry_price.to_string(),
+                "markPrice": mark.to_string(),
+                "unRealizedProfit": (position.amount * (mark - position.entry_price)).to_string(),
+                "leverage": state.leverage.get(symbol).copied().unwrap_or(1).to_string()
+            })
+        })
+        .collect();
+
+    HttpResponse::Ok().json(positions)
+}
+
+async fn get_balance(req: HttpRequest, state: SharedState, credential: web::Data<Arc<Credential>>) -> HttpResponse {
+    if let Err(response) = verify_signature(&req, &credential) {
+        return response;
+    }
+
+    let balance = state.lock().unwrap().balance.to_string();
+    HttpResponse::Ok().json(json!([{ "asset": "USDT", "balance": balance, "availableBalance": balance }]))
+}
+
+async fn post_advance(state: SharedState, query: web::Query<HashMap<String, String>>) -> HttpResponse {
+    let steps = query.get("steps").and_then(|s| s.parse::<usize>().ok()).unwrap_or(1);
+    let mut state = state.lock().unwrap();
+    state.advance(steps);
+    HttpResponse::Ok().json(json!({ "cursor": state.cursor }))
+}
+
+async fn get_orders(state: SharedState) -> HttpResponse {
+    HttpResponse::Ok().json(&state.lock().unwrap().orders)
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Settings;
+    use crate::exchange::{BinanceExchange, Exchange};
+    use crate::schedule::process_trades;
+    use crate::spy::spy_cryptos;
+
+    #[test]
+    fn waypoints_are_sorted_on_load() {
+        let path = std::env::temp_dir().join(format!("mock_unsorted_{}.toml", std::process::id()));
+        std::fs::write(&path, "[symbols.ETHUSDT]\nwaypoints = [[10, 200.0], [0, 100.0]]\n").unwrap();
+        let script = load_script(path.to_str().unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let eth = &script.symbols["ETHUSDT"];
+        assert_eq!(eth.waypoints, vec![(0, 100.0), (10, 200.0)]);
+        assert_eq!(eth.price_at(5), 150.0);
+        assert_eq!(eth.price_at(20), 200.0);
+    }
+
+    #[actix_web::test]
+    async fn scheduler_cycles_send_orders_to_the_mock() {
+        let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let base = format!("http://127.0.0.1:{}", port);
+        // Set before anything reads the settings or credentials.
+        unsafe {
+            std::env::set_var("BINANCE_API_KEY", "mock-key");
+            std::env::set_var("BINANCE_API_SECRET", "mock-secret");
+            std::env::set_var("RKD__BINANCE__BASE_URL", format!("{}/api/v3", base));
+            std::env::set_var("RKD__BINANCE__FUTURE_URL", format!("{}/fapi/v1", base));
+            std::env::set_var("RKD__BINANCE__FUTURE_URL_V2", format!("{}/fapi/v2", base));
+            std::env::set_var("RKD__BINANCE__MODE", "live");
+            std::env::set_var("RKD__BINANCE__DECIDE", "true");
+            std::env::set_var("RKD__PROTECTION__ENABLED", "true");
+        }
+        let settings = Settings::load();
+        let mock = MockSettings {
+            enabled: true,
+            port,
+            script: "config/MockMarket".to_string(),
+            step_secs: 0,
+            balance: 1000.0,
+        };
+        actix_web::rt::spawn(start_mock_server(&mock).unwrap());
+
+        // ETHUSDT dips from 3400 to 2900 and climbs back to 3600: the bot
+        // buys when it re-enters zone 7 and the take-profit fills on the way up.
+        let exchange: Arc<dyn Exchange> = Arc::new(BinanceExchange::new(&settings));
+        let http = reqwest::Client::new();
+        for _ in 0..80 {
+            let trades = spy_cryptos(&exchange, &settings.binance, vec!["ETHUSDT".to_string()]).await;
+            process_trades(trades, &settings, exchange.as_ref()).await;
+            http.post(format!("{}/mock/advance?steps=1", base)).send().await.unwrap();
+        }
+
+        let orders: Vec<serde_json::Value> =
+            http.get(format!("{}/mock/orders", base)).send().await.unwrap().json().await.unwrap();
+        assert_eq!(orders.len(), 2, "{:?}", orders);
+
+        let entry = &orders[0];
+        assert_eq!(entry["symbol"], "ETHUSDT");
+        assert_eq!(entry["side"], "BUY");
+        assert_eq!(entry["order_type"], "MARKET");
+        assert_eq!(entry["reduce_only"], false);
+
+        let exit = &orders[1];
+        assert_eq!(exit["side"], "SELL");
+        assert_eq!(exit["order_type"], "TAKE_PROFIT_MARKET");
+        assert_eq!(exit["reduce_only"], true);
+        assert_eq!(exit["quantity"], entry["quantity"]);
+        assert!(exit["price"].as_f64() > entry["price"].as_f64());
+
+        let buy = crate::blockchain::get_blockchain_for("ETHUSDT")
+            .unwrap()
+            .into_iter()
+            .find_map(|block| block.action.filter(|a| a.kind == crate::blockchain::ActionKind::Buy))
+            .unwrap();
+        assert_eq!(buy.fill_price, Some(entry["price"].as_f64().unwrap().to_string()));
+    }
+}
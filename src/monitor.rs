@@ -1,24 +1,40 @@
 use crate::blockchain::get_current_blockchain_symbols;
-use crate::config::Settings;
-use crate::dto::{Bias, Trade, TradeMonitorItem, TradeMonitorResponse, ZoneCount};
+use crate::config::{ExecutionMode, Settings};
+use crate::dto::{Bias, MonitorAccount, Trade, TradeMonitorItem, TradeMonitorResponse, ZoneCount};
+use crate::exchange::Exchange;
 use prettytable::{color, Attr, Cell, Row, Table};
 use chrono::Local;
 
-pub fn monitor_cryptos(trades: &[Trade], settings: &Settings) -> TradeMonitorResponse {
-    fn parse(value: &str) -> f64 {
-        value.parse::<f64>().unwrap_or(0.0)
+type MonitorRow = (f64, f64, f64, f64, f64, f64, f64, f64, f64, f64);
+
+/// In paper mode the monitor also shows the simulated account, so a run can
+/// be followed without calling `/trades/balance`.
+pub async fn load_monitor_account(exchange: &dyn Exchange, settings: &Settings) -> Option<MonitorAccount> {
+    if settings.binance.mode != ExecutionMode::Paper {
+        return None;
     }
 
+    let balance = exchange
+        .get_futures_balance()
+        .await
+        .ok()
+        .and_then(|balances| balances.into_iter().find(|b| b.asset == "USDT"));
+    let positions = exchange.get_positions().await.unwrap_or_default();
+
+    Some(MonitorAccount { balance, positions })
+}
+
+pub fn monitor_cryptos(trades: &[Trade], settings: &Settings, account: Option<MonitorAccount>) -> TradeMonitorResponse {
     fn find_zone_index(trade: &Trade) -> Option<usize> {
-        let price = parse(&trade.current_price);
-        let zones = vec![
-            parse(&trade.zone_1),
-            parse(&trade.zone_2),
-            parse(&trade.zone_3),
-            parse(&trade.zone_4),
-            parse(&trade.zone_5),
-            parse(&trade.zone_6),
-            parse(&trade.zone_7),
+        let price = trade.current_price.to_f64();
+        let zones = [
+            trade.zone_1.to_f64(),
+            trade.zone_2.to_f64(),
+            trade.zone_3.to_f64(),
+            trade.zone_4.to_f64(),
+            trade.zone_5.to_f64(),
+            trade.zone_6.to_f64(),
+            trade.zone_7.to_f64(),
             f64::MAX,
         ];
 
@@ -75,7 +91,7 @@ pub fn monitor_cryptos(trades: &[Trade], settings: &Settings) -> TradeMonitorRes
         ((price.ln() - min.ln()) / (max.ln() - min.ln())) * 100.0
     }
 
-    fn extract_column(values: &[(f64, f64, f64, f64, f64, f64, f64, f64, f64, f64)], index: usize) -> Vec<f64> {
+    fn extract_column(values: &[MonitorRow], index: usize) -> Vec<f64> {
         values.iter().map(|v| match index {
             0 => v.0,
             1 => v.1,
@@ -130,23 +146,21 @@ pub fn monitor_cryptos(trades: &[Trade], settings: &Settings) -> TradeMonitorRes
         ]));
     }
 
-    let values: Vec<(
-        f64, f64, f64, f64, f64, f64, f64, f64, f64, f64,
-    )> = trades.iter().map(|t| {
-        let min = parse(&t.zone_min);
-        let max = parse(&t.zone_max);
-        let current = parse(&t.current_price);
+    let values: Vec<MonitorRow> = trades.iter().map(|t| {
+        let min = t.zone_min.to_f64();
+        let max = t.zone_max.to_f64();
+        let current = t.current_price.to_f64();
         (
-            parse(&t.performance_24),
-            parse(&t.performance_btc_24),
-            parse(&t.amplitude_ma_200),
+            t.performance_24.to_f64(),
+            t.performance_btc_24.to_f64(),
+            t.amplitude_ma_200.to_f64(),
             calc_log_ampl(min, max),
             calc_log_position(current, min, max),
-            parse(&t.volume),
-            parse(&t.quote_asset_volume),
-            parse(&t.number_of_trades),
-            parse(&t.taker_buy_base_asset_volume),
-            parse(&t.taker_buy_quote_asset_volume),
+            t.volume.to_f64(),
+            t.quote_asset_volume.to_f64(),
+            t.number_of_trades.to_f64(),
+            t.taker_buy_base_asset_volume.to_f64(),
+            t.taker_buy_quote_asset_volume.to_f64(),
         )
     }).collect();
 
@@ -245,6 +259,12 @@ pub fn monitor_cryptos(trades: &[Trade], settings: &Settings) -> TradeMonitorRes
             taker_buy_base_volume: taker_base_col[i],
             taker_buy_quote_volume: taker_quote_col[i],
             is_active: active_symbols.contains(&t.symbol),
+            unrealized_pnl: account.as_ref().and_then(|a| {
+                a.positions
+                    .iter()
+                    .find(|p| p.symbol == t.symbol)
+                    .and_then(|p| p.unrealized_profit.parse::<f64>().ok())
+            }),
         });
     }
 
@@ -260,6 +280,25 @@ pub fn monitor_cryptos(trades: &[Trade], settings: &Settings) -> TradeMonitorRes
             .join(" | ")
     );
 
+    if let Some(account) = &account {
+        if let Some(balance) = &account.balance {
+            println!(
+                "\nConta paper: saldo {} USDT | disponivel {} USDT",
+                balance.total, balance.available
+            );
+        }
+        for position in &account.positions {
+            println!(
+                "  {} qtd {} entrada {} mark {} PnL {}",
+                position.symbol,
+                position.position_amt,
+                position.entry_price,
+                position.mark_price,
+                position.unrealized_profit
+            );
+        }
+    }
+
     let json_distribution: Vec<ZoneCount> = zone_counts
         .iter()
         .enumerate()
@@ -273,5 +312,6 @@ pub fn monitor_cryptos(trades: &[Trade], settings: &Settings) -> TradeMonitorRes
         timestamp: now.to_rfc3339(),
         trades: json_items,
         zone_distribution: json_distribution,
+        account,
     }
 }
@@ -1,243 +1,255 @@
-﻿use crate::binance::{get_current_price, get_lot_size_info};
-use crate::credential::get_credentials;
-use crate::dto::OrderResponse;
-use crate::config::{BinanceSettings, Settings};
-use hmac::{Hmac, Mac};
-use reqwest::{
-    header::{HeaderMap, HeaderValue, CONTENT_TYPE},
-    Client,
-};
-use sha2::Sha256;
-use std::collections::HashMap;
-use std::time::{SystemTime, UNIX_EPOCH};
-use url::form_urlencoded;
-
-type HmacSha256 = Hmac<Sha256>;
-
-fn round_quantity(value: f64, step: f64) -> f64 {
-    (value / step).floor() * step
+use crate::client::get_binance_client;
+use crate::dto::{OrderResponse, PositionResponse};
+use crate::config::BinanceSettings;
+use crate::symbols::{get_symbol_registry, SymbolMeta};
+use reqwest::Method;
+use rust_decimal::{Decimal, RoundingStrategy};
+use std::str::FromStr;
+
+/// Exact decimal form of a price or amount that arrived as `f64`.
+pub fn to_decimal(value: f64) -> Result<Decimal, String> {
+    Decimal::from_str(&value.to_string()).map_err(|e| format!("Invalid decimal '{}': {}", value, e))
 }
 
-fn get_timestamp() -> u64 {
-    let start = SystemTime::now();
-    let since = start.duration_since(UNIX_EPOCH).unwrap();
-    since.as_millis() as u64
+fn floor_to_step(value: Decimal, step: Decimal) -> Decimal {
+    if step.is_zero() {
+        return value;
+    }
+    (value / step).floor() * step
 }
 
-fn sign_query(query: &str, secret: &str) -> String {
-    let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).unwrap();
-    mac.update(query.as_bytes());
-    hex::encode(mac.finalize().into_bytes())
+/// Sizes a market order worth `money` USDT, rounded down to the
+/// `MARKET_LOT_SIZE` step and checked against its bounds and `MIN_NOTIONAL`.
+pub fn order_quantity(money: Decimal, price: Decimal, meta: &SymbolMeta) -> Result<Decimal, String> {
+    if !meta.is_trading() {
+        return Err(format!("{} is not trading (status {})", meta.symbol, meta.status));
+    }
+    if price <= Decimal::ZERO {
+        return Err(format!("Invalid price {} for {}", price, meta.symbol));
+    }
+
+    let quantity = floor_to_step(money / price, meta.market_step_size)
+        .round_dp_with_strategy(meta.quantity_precision, RoundingStrategy::ToZero)
+        .normalize();
+
+    if quantity.is_zero() || quantity < meta.market_min_qty {
+        return Err(format!(
+            "Quantity {} is below the minimum of {} for {}",
+            quantity, meta.market_min_qty, meta.symbol
+        ));
+    }
+    if !meta.market_max_qty.is_zero() && quantity > meta.market_max_qty {
+        return Err(format!(
+            "Quantity {} is above the maximum of {} for {}",
+            quantity, meta.market_max_qty, meta.symbol
+        ));
+    }
+
+    let notional = quantity * price;
+    if notional < meta.min_notional {
+        return Err(format!(
+            "Total order value ({:.2} USDT) is less than the minimum required ({} USDT)",
+            notional, meta.min_notional
+        ));
+    }
+
+    Ok(quantity)
 }
 
-async fn get_server_time_offset(settings: &BinanceSettings) -> Result<i64, String> {
-    let client = Client::new();
-    let time_url = format!("{}/time", settings.future_url);
+/// Quantity of a reduce-only market order closing `amount`, capped at the
+/// `MARKET_LOT_SIZE` maximum.
+pub fn closing_quantity(amount: Decimal, meta: &SymbolMeta) -> Decimal {
+    let quantity = floor_to_step(amount.abs(), meta.market_step_size)
+        .round_dp_with_strategy(meta.quantity_precision, RoundingStrategy::ToZero);
+    let quantity = if meta.market_max_qty.is_zero() {
+        quantity
+    } else {
+        quantity.min(meta.market_max_qty)
+    };
+    quantity.normalize()
+}
 
-    let res = client
-        .get(&time_url)
-        .send()
-        .await
-        .map_err(|e| format!("Error querying /time: {:?}", e))?;
+fn round_to_tick(price: Decimal, meta: &SymbolMeta, strategy: RoundingStrategy) -> Decimal {
+    if meta.tick_size.is_zero() {
+        price.round_dp_with_strategy(meta.price_precision, strategy)
+    } else {
+        (price / meta.tick_size).round_dp_with_strategy(0, strategy) * meta.tick_size
+    }
+    .normalize()
+}
 
-    let json: serde_json::Value = res
-        .json()
-        .await
-        .map_err(|e| format!("Error parsing /time: {:?}", e))?;
+/// Rounds a trigger price to the tick size, pulls it inside the
+/// `PERCENT_PRICE` band around `reference` (the current price) and checks
+/// it against `PRICE_FILTER`. Pulling it in only moves the stop or target
+/// closer to the price, so the position stays protected.
+pub fn trigger_price(price: Decimal, reference: Decimal, meta: &SymbolMeta) -> Result<Decimal, String> {
+    let mut rounded = round_to_tick(price, meta, RoundingStrategy::MidpointNearestEven);
+
+    if reference > Decimal::ZERO {
+        let high = round_to_tick(reference * meta.multiplier_up, meta, RoundingStrategy::ToZero);
+        let low = round_to_tick(reference * meta.multiplier_down, meta, RoundingStrategy::AwayFromZero);
+        let band = if !meta.multiplier_up.is_zero() && rounded > high {
+            Some(high)
+        } else if !meta.multiplier_down.is_zero() && rounded < low {
+            Some(low)
+        } else {
+            None
+        };
+        if let Some(limit) = band {
+            println!(
+                "Trigger {} for {} outside PERCENT_PRICE ({} to {} of {}), using {}",
+                rounded, meta.symbol, meta.multiplier_down, meta.multiplier_up, reference, limit
+            );
+            rounded = limit;
+        }
+    }
 
-    let server_time = json["serverTime"].as_i64().ok_or("serverTime field missing")?;
-    let local_time = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .map_err(|_| "Local clock error")?
-        .as_millis() as i64;
+    if rounded <= Decimal::ZERO || rounded < meta.min_price {
+        return Err(format!("Price {} is below the minimum of {} for {}", rounded, meta.min_price, meta.symbol));
+    }
+    if !meta.max_price.is_zero() && rounded > meta.max_price {
+        return Err(format!("Price {} is above the maximum of {} for {}", rounded, meta.max_price, meta.symbol));
+    }
 
-    Ok(server_time - local_time)
+    Ok(rounded)
 }
 
 pub async fn execute_future_order(
     settings: &BinanceSettings,
     side: &str,
     symbol: &str,
+    quantity_str: String,
 ) -> Result<OrderResponse, String> {
-    let credentials = get_credentials();
-    let api_key = &credentials.key;
-    let secret_key = &credentials.secret;
-
-    let base_url = format!("{}/order", settings.future_url);
-
-    let offset = get_server_time_offset(settings).await.unwrap_or(0);
-    let timestamp = (get_timestamp() as i64 + offset) as u64;
-    let timestamp_str = timestamp.to_string();
-
-    let preco_btc = get_current_price(settings, symbol).await?;
-    let lot_size_info = get_lot_size_info(settings, symbol).await?;
-
-    let config = Settings::load();
-    let money = config.money;
-
-    let quantity_raw = money / preco_btc;
-    let quantity = round_quantity(quantity_raw, lot_size_info.step_size);
-
-    let precision = (1.0 / lot_size_info.step_size).log10().round() as usize;
-    let quantity_str = format!("{:.*}", precision, quantity)
-        .trim_end_matches('0')
-        .trim_end_matches('.')
-        .to_string();
-
-    println!(
-        "Sending order with side: '{}', quantity: '{}' (USDT: {}, Cryptocurrency Price: {}, StepSize: {})",
-        side, quantity_str, money, preco_btc, lot_size_info.step_size
-    );
-
-    let notional = quantity * preco_btc;
-    if notional < 20.0 {
-        return Err(format!(
-            "Total order value ({:.2} USDT) is less than the minimum required (20 USDT)",
-            notional
-        ));
-    }
-
-    let mut params = HashMap::new();
-    params.insert("symbol", symbol);
-    params.insert("side", side);
-    params.insert("type", "MARKET");
-    params.insert("quantity", &quantity_str);
-    params.insert("recvWindow", "10000");
-    params.insert("timestamp", &timestamp_str);
-
-    let query_string = form_urlencoded::Serializer::new(String::new())
-        .extend_pairs(&params)
-        .finish();
-
-    let signature = sign_query(&query_string, secret_key);
-    let signed_query = format!("{}&signature={}", query_string, signature);
-
-    let mut headers = HeaderMap::new();
-    headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
-    headers.insert("X-MBX-APIKEY", HeaderValue::from_str(api_key).unwrap());
+    let params = [
+        ("symbol", symbol.to_string()),
+        ("side", side.to_string()),
+        ("type", "MARKET".to_string()),
+        ("quantity", quantity_str),
+        // The default ACK comes back before the fill, with avgPrice "0".
+        ("newOrderRespType", "RESULT".to_string()),
+    ];
+
+    get_binance_client()
+        .signed(Method::POST, &format!("{}/order", settings.future_url), &params)
+        .await
+}
 
-    let client = Client::new();
+/// Places a `STOP_MARKET` or `TAKE_PROFIT_MARKET` order with
+/// `closePosition=true`, which Binance treats as reduce-only for the whole
+/// position.
+pub async fn place_protective_order(
+    settings: &BinanceSettings,
+    symbol: &str,
+    side: &str,
+    order_type: &str,
+    stop_price: String,
+) -> Result<OrderResponse, String> {
+    let params = [
+        ("symbol", symbol.to_string()),
+        ("side", side.to_string()),
+        ("type", order_type.to_string()),
+        ("stopPrice", stop_price),
+        ("closePosition", "true".to_string()),
+        ("workingType", "MARK_PRICE".to_string()),
+    ];
+
+    get_binance_client()
+        .signed(Method::POST, &format!("{}/order", settings.future_url), &params)
+        .await
+}
 
-    let res = client
-        .post(format!("{}?{}", base_url, signed_query))
-        .headers(headers)
-        .send()
+pub async fn cancel_all_open_orders(settings: &BinanceSettings, symbol: &str) -> Result<(), String> {
+    get_binance_client()
+        .signed::<serde_json::Value>(
+            Method::DELETE,
+            &format!("{}/allOpenOrders", settings.future_url),
+            &[("symbol", symbol.to_string())],
+        )
         .await
-        .map_err(|e| format!("Request error: {:?}", e))?;
+        .map(|_| ())
+        .map_err(|e| format!("Error cancelling open orders for {}: {}", symbol, e))
+}
 
-    if res.status().is_success() {
-        res.json::<OrderResponse>()
-            .await
-            .map_err(|e| format!("Error interpreting JSON: {:?}", e))
-    } else {
-        let err = res.text().await.unwrap_or_else(|_| "Unknown error".to_string());
-        Err(format!("Binance Error: {}", err))
-    }
+pub async fn get_open_orders(settings: &BinanceSettings) -> Result<Vec<OrderResponse>, String> {
+    get_binance_client()
+        .signed(Method::GET, &format!("{}/openOrders", settings.future_url), &[])
+        .await
+        .map_err(|e| format!("Error when querying open orders: {}", e))
 }
 
+/// Sends a reduce-only market order for every non-zero entry of `positions`
+/// on `symbol`.
 pub async fn close_all_positions(
     settings: &BinanceSettings,
     symbol: &str,
+    positions: Vec<PositionResponse>,
 ) -> Result<Vec<OrderResponse>, String> {
-    let credentials = get_credentials();
-    let api_key = &credentials.key;
-    let secret_key = &credentials.secret;
+    let client = get_binance_client();
 
-    let offset = get_server_time_offset(settings).await.unwrap_or(0);
-    let timestamp = (get_timestamp() as i64 + offset) as u64;
-    let query = format!("timestamp={}", timestamp);
-    let signature = sign_query(&query, secret_key);
-    let full_query = format!("{}&signature={}", query, signature);
-
-    let position_risk_url = format!("{}/positionRisk?{}", settings.future_url_v2, full_query);
+    let mut results = Vec::new();
 
-    let mut headers = HeaderMap::new();
-    headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
-    headers.insert("X-MBX-APIKEY", HeaderValue::from_str(api_key).unwrap());
+    for position in positions.into_iter().filter(|p| p.symbol == symbol) {
+        let amt = Decimal::from_str(&position.position_amt).unwrap_or_default();
 
-    let client = Client::new();
+        if amt.is_zero() {
+            continue;
+        }
 
-    let res = client
-        .get(&position_risk_url)
-        .headers(headers.clone())
-        .send()
-        .await
-        .map_err(|e| format!("Error when querying positions: {:?}", e))?;
+        let side = if amt > Decimal::ZERO { "SELL" } else { "BUY" };
 
-    let status = res.status();
-    if !status.is_success() {
-        let err_text = res.text().await.unwrap_or_default();
-        return Err(format!(
-            "HTTP error {} when querying positions: {}",
-            status,
-            err_text
-        ));
-    }
+        let meta = get_symbol_registry().get(settings, symbol).await?;
+        let quantity_str = closing_quantity(amt, &meta).to_string();
 
-    let positions: Vec<serde_json::Value> = res
-        .json()
-        .await
-        .map_err(|e| format!("Error interpreting JSON response: {:?}", e))?;
+        let params = [
+            ("symbol", symbol.to_string()),
+            ("side", side.to_string()),
+            ("type", "MARKET".to_string()),
+            ("reduceOnly", "true".to_string()),
+            ("quantity", quantity_str),
+            ("newOrderRespType", "RESULT".to_string()),
+        ];
 
-    let mut results = Vec::new();
+        let parsed: OrderResponse = client
+            .signed(Method::POST, &format!("{}/order", settings.future_url), &params)
+            .await
+            .map_err(|e| format!("Error closing position {}: {}", symbol, e))?;
+        results.push(parsed);
+    }
 
-    for position in positions.into_iter().filter(|p| p["symbol"] == symbol) {
-        let amt = position["positionAmt"].as_str().unwrap_or("0").parse::<f64>().unwrap_or(0.0);
-        let symbol = position["symbol"].as_str().unwrap_or("");
+    Ok(results)
+}
 
-        if amt.abs() < 1e-8 {
-            continue;
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn meta() -> SymbolMeta {
+        SymbolMeta {
+            symbol: "ETHUSDT".to_string(),
+            tick_size: Decimal::new(1, 2),
+            min_price: Decimal::new(1, 2),
+            max_price: Decimal::from(1_000_000),
+            multiplier_up: Decimal::new(105, 2),
+            multiplier_down: Decimal::new(95, 2),
+            ..Default::default()
         }
+    }
 
-        let side = if amt > 0.0 { "SELL" } else { "BUY" };
-        let quantity = amt.abs();
-
-        let lot_size_info = get_lot_size_info(settings, symbol).await?;
-        let quantity_rounded = round_quantity(quantity, lot_size_info.step_size);
-        let precision = (1.0 / lot_size_info.step_size).log10().round() as usize;
-        let quantity_str = format!("{:.*}", precision, quantity_rounded)
-            .trim_end_matches('0')
-            .trim_end_matches('.')
-            .to_string();
-
-        let order_url = format!("{}/order", settings.future_url);
-        let timestamp = get_timestamp();
-        let timestamp_str = timestamp.to_string();
-
-        let mut params = HashMap::new();
-        params.insert("symbol", symbol);
-        params.insert("side", side);
-        params.insert("type", "MARKET");
-        params.insert("reduceOnly", "true");
-        params.insert("quantity", &quantity_str);
-        params.insert("recvWindow", "10000");
-        params.insert("timestamp", &timestamp_str);
-
-        let query_string = form_urlencoded::Serializer::new(String::new())
-            .extend_pairs(&params)
-            .finish();
-
-        let signature = sign_query(&query_string, secret_key);
-        let signed_query = format!("{}&signature={}", query_string, signature);
-
-        let response = client
-            .post(format!("{}?{}", order_url, signed_query))
-            .headers(headers.clone())
-            .send()
-            .await
-            .map_err(|e| format!("Error sending closing order: {:?}", e))?;
-
-        if response.status().is_success() {
-            let parsed = response
-                .json::<OrderResponse>()
-                .await
-                .map_err(|e| format!("Error interpreting order: {:?}", e))?;
-            results.push(parsed);
-        } else {
-            let err_text = response.text().await.unwrap_or_default();
-            return Err(format!("Error closing position {}: {}", symbol, err_text));
-        }
+    #[test]
+    fn trigger_inside_the_band_is_only_rounded() {
+        let price = trigger_price(Decimal::new(3_100_004, 3), Decimal::from(3000), &meta()).unwrap();
+        assert_eq!(price, Decimal::new(3100, 0));
     }
 
-    Ok(results)
+    #[test]
+    fn trigger_outside_the_band_is_pulled_in() {
+        let reference = Decimal::new(330_833, 2);
+        let stop = trigger_price(Decimal::new(311_029, 2), reference, &meta()).unwrap();
+        let target = trigger_price(Decimal::new(3500, 0), reference, &meta()).unwrap();
+
+        assert_eq!(stop, Decimal::new(314_292, 2));
+        assert_eq!(target, Decimal::new(347_374, 2));
+        assert!(stop >= reference * Decimal::new(95, 2));
+        assert!(target <= reference * Decimal::new(105, 2));
+    }
 }
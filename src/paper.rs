@@ -0,0 +1,533 @@
+use crate::config::{PaperSettings, Settings};
+use crate::dto::{BalanceResponse, BookTicker, Candlestick, OrderResponse, PositionResponse};
+use crate::exchange::Exchange;
+use crate::leverage::LeverageResponse;
+use crate::order::{order_quantity, to_decimal};
+use crate::symbols::SymbolMeta;
+use async_trait::async_trait;
+use rust_decimal::prelude::ToPrimitive;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct PaperPosition {
+    /// Signed quantity: positive for longs, negative for shorts.
+    amount: f64,
+    entry_price: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PaperFill {
+    pub time: u64,
+    pub symbol: String,
+    pub side: String,
+    pub quantity: f64,
+    pub price: f64,
+    pub fee: f64,
+    pub realized_pnl: f64,
+}
+
+/// Resting `STOP_MARKET`/`TAKE_PROFIT_MARKET` order that closes the whole
+/// position once the price reaches `stop_price`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct PaperTrigger {
+    order_id: u64,
+    side: String,
+    order_type: String,
+    stop_price: f64,
+    placed_at: u64,
+}
+
+impl PaperTrigger {
+    fn is_hit(&self, low: f64, high: f64) -> bool {
+        let falling = (self.order_type == "STOP_MARKET") == (self.side == "SELL");
+        if falling { low <= self.stop_price } else { high >= self.stop_price }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct PriceMark {
+    price: f64,
+    time: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct PaperAccount {
+    wallet_balance: f64,
+    realized_pnl: f64,
+    fees_paid: f64,
+    positions: HashMap<String, PaperPosition>,
+    leverage: HashMap<String, u32>,
+    next_order_id: u64,
+    #[serde(default)]
+    triggers: HashMap<String, Vec<PaperTrigger>>,
+}
+
+impl PaperAccount {
+    fn new(balance: f64) -> Self {
+        PaperAccount {
+            wallet_balance: balance,
+            realized_pnl: 0.0,
+            fees_paid: 0.0,
+            positions: HashMap::new(),
+            leverage: HashMap::new(),
+            next_order_id: 1,
+            triggers: HashMap::new(),
+        }
+    }
+
+    fn leverage_for(&self, symbol: &str, default: u32) -> u32 {
+        self.leverage.get(symbol).copied().unwrap_or(default).max(1)
+    }
+
+    /// Applies a fill of `quantity` (signed) at `price`, realizing PnL on the
+    /// part that reduces an existing position. Returns the fee and that PnL.
+    fn fill(&mut self, symbol: &str, quantity: f64, price: f64, fee_rate: f64) -> (f64, f64) {
+        let fee = quantity.abs() * price * fee_rate;
+        self.wallet_balance -= fee;
+        self.fees_paid += fee;
+
+        let position = self
+            .positions
+            .entry(symbol.to_string())
+            .or_insert(PaperPosition { amount: 0.0, entry_price: 0.0 });
+
+        let mut pnl = 0.0;
+        if position.amount == 0.0 || position.amount.signum() == quantity.signum() {
+            let total = position.amount + quantity;
+            position.entry_price =
+                (position.amount.abs() * position.entry_price + quantity.abs() * price) / total.abs();
+            position.amount = total;
+        } else {
+            let closed = quantity.abs().min(position.amount.abs());
+            pnl = closed * (price - position.entry_price) * position.amount.signum();
+            self.wallet_balance += pnl;
+            self.realized_pnl += pnl;
+
+            let remaining = position.amount + quantity;
+            if remaining.abs() < 1e-12 {
+                position.amount = 0.0;
+                position.entry_price = 0.0;
+            } else if remaining.signum() != position.amount.signum() {
+                position.amount = remaining;
+                position.entry_price = price;
+            } else {
+                position.amount = remaining;
+            }
+        }
+
+        if position.amount == 0.0 {
+            self.positions.remove(symbol);
+        }
+
+        (fee, pnl)
+    }
+}
+
+/// Simulated USDT-M futures account. Market data still comes from `market`;
+/// fills are priced off the last close seen in the candles it served.
+pub struct PaperExchange {
+    market: Arc<dyn Exchange>,
+    settings: PaperSettings,
+    money: f64,
+    default_leverage: u32,
+    persistent: bool,
+    account: Mutex<PaperAccount>,
+    prices: Mutex<HashMap<String, PriceMark>>,
+    fills: Mutex<Vec<PaperFill>>,
+}
+
+impl PaperExchange {
+    pub fn new(market: Arc<dyn Exchange>, settings: &Settings) -> Self {
+        let account = load_account(&settings.paper.state_file);
+        Self::build(market, settings, account, true)
+    }
+
+    /// Fresh account that is never written to `state_file`, for replays.
+    pub fn in_memory(market: Arc<dyn Exchange>, settings: &Settings) -> Self {
+        Self::build(market, settings, None, false)
+    }
+
+    fn build(market: Arc<dyn Exchange>, settings: &Settings, account: Option<PaperAccount>, persistent: bool) -> Self {
+        let paper = settings.paper.clone();
+        let account = account.unwrap_or_else(|| PaperAccount::new(paper.balance));
+
+        PaperExchange {
+            market,
+            settings: paper,
+            money: settings.money,
+            default_leverage: settings.binance.leverage,
+            persistent,
+            account: Mutex::new(account),
+            prices: Mutex::new(HashMap::new()),
+            fills: Mutex::new(vec![]),
+        }
+    }
+
+    pub fn fills(&self) -> Vec<PaperFill> {
+        self.fills.lock().unwrap().clone()
+    }
+
+    /// Wallet balance plus the unrealized PnL of every open position.
+    pub fn equity(&self) -> f64 {
+        let account = self.account.lock().unwrap();
+        let unrealized: f64 = account
+            .positions
+            .iter()
+            .map(|(symbol, p)| {
+                let mark = self.last_price(symbol).unwrap_or(p.entry_price);
+                p.amount * (mark - p.entry_price)
+            })
+            .sum();
+        account.wallet_balance + unrealized
+    }
+
+    fn last_price(&self, symbol: &str) -> Option<f64> {
+        self.prices.lock().unwrap().get(symbol).map(|m| m.price)
+    }
+
+    fn last_time(&self, symbol: &str) -> u64 {
+        self.prices.lock().unwrap().get(symbol).map(|m| m.time).unwrap_or_else(now_ms)
+    }
+
+    /// Wallet plus unrealized PnL, less the margin held by open positions,
+    /// all marked at the last seen price.
+    fn available_margin(&self, account: &PaperAccount) -> f64 {
+        account.positions.iter().fold(account.wallet_balance, |available, (symbol, position)| {
+            let mark = self.last_price(symbol).unwrap_or(position.entry_price);
+            let leverage = account.leverage_for(symbol, self.default_leverage) as f64;
+            available + position.amount * (mark - position.entry_price) - position.amount.abs() * mark / leverage
+        })
+    }
+
+    fn slipped(&self, side: &str, price: f64) -> f64 {
+        if side == "BUY" {
+            price * (1.0 + self.settings.slippage)
+        } else {
+            price * (1.0 - self.settings.slippage)
+        }
+    }
+
+    /// Fills against the account and records the trade in the fill log.
+    fn apply_fill(&self, account: &mut PaperAccount, symbol: &str, side: &str, quantity: f64, price: f64) -> u64 {
+        let signed_quantity = if side == "BUY" { quantity } else { -quantity };
+        let (fee, realized_pnl) = account.fill(symbol, signed_quantity, price, self.settings.fee_rate);
+
+        let id = account.next_order_id;
+        account.next_order_id += 1;
+
+        self.fills.lock().unwrap().push(PaperFill {
+            time: self.last_time(symbol),
+            symbol: symbol.to_string(),
+            side: side.to_string(),
+            quantity,
+            price,
+            fee,
+            realized_pnl,
+        });
+
+        if self.persistent {
+            self.persist(account);
+        }
+        id
+    }
+
+    /// Fires the first protective order reached inside `candle`. Candles
+    /// that opened before the order was placed only count their close.
+    fn check_triggers(&self, symbol: &str, candle: &Candlestick) {
+        let mut account = self.account.lock().unwrap();
+        let Some(triggers) = account.triggers.get(symbol) else {
+            return;
+        };
+
+        let close = candle.close_price.parse::<f64>().unwrap_or(0.0);
+        let high = candle.high_price.parse::<f64>().unwrap_or(close);
+        let low = candle.low_price.parse::<f64>().unwrap_or(close);
+
+        // Stops are checked first, so a candle that spans both levels is
+        // counted as a loss.
+        let mut candidates: Vec<&PaperTrigger> = triggers.iter().collect();
+        candidates.sort_by_key(|t| t.order_type != "STOP_MARKET");
+        let hit = candidates
+            .into_iter()
+            .find(|t| {
+                if candle.open_time < t.placed_at {
+                    t.is_hit(close, close)
+                } else {
+                    t.is_hit(low, high)
+                }
+            })
+            .cloned();
+
+        let Some(trigger) = hit else {
+            return;
+        };
+        account.triggers.remove(symbol);
+
+        let amount = account.positions.get(symbol).map(|p| p.amount).unwrap_or(0.0);
+        if amount == 0.0 {
+            return;
+        }
+
+        let price = self.slipped(&trigger.side, trigger.stop_price);
+        self.apply_fill(&mut account, symbol, &trigger.side, amount.abs(), price);
+        println!(
+            "[PAPER] {} {} triggered at {} (realized PnL total: {:.2})",
+            trigger.order_type, symbol, price, account.realized_pnl
+        );
+    }
+
+    fn persist(&self, account: &PaperAccount) {
+        let path = Path::new(&self.settings.state_file);
+        if let Some(dir) = path.parent() {
+            let _ = fs::create_dir_all(dir);
+        }
+
+        match serde_json::to_string_pretty(account) {
+            Ok(json) => {
+                if let Err(e) = fs::write(path, json) {
+                    eprintln!("Error saving paper account: {}", e);
+                }
+            }
+            Err(e) => eprintln!("Error serializing paper account: {}", e),
+        }
+    }
+
+    fn order_response(id: u64, symbol: &str, side: &str, quantity: f64, price: f64) -> OrderResponse {
+        OrderResponse {
+            symbol: symbol.to_string(),
+            order_id: id,
+            status: "FILLED".to_string(),
+            side: side.to_string(),
+            price: price.to_string(),
+            orig_qty: quantity.to_string(),
+            executed_qty: quantity.to_string(),
+            cummulative_quote_qty: Some((quantity * price).to_string()),
+            avg_price: Some(price.to_string()),
+            time_in_force: "GTC".to_string(),
+            order_type: "MARKET".to_string(),
+            update_time: now_ms(),
+        }
+    }
+}
+
+#[async_trait]
+impl Exchange for PaperExchange {
+    async fn get_candlesticks(&self, symbol: &str, interval: &str, limit: u32) -> Result<Vec<Candlestick>, String> {
+        let candles = self.market.get_candlesticks(symbol, interval, limit).await?;
+        if let Some(last) = candles.last() {
+            self.on_candle(symbol, last);
+        }
+        Ok(candles)
+    }
+
+    /// Marks `symbol` at the close of `candle` and fires the triggers it
+    /// reached, for candles fetched over REST and streamed alike.
+    fn on_candle(&self, symbol: &str, candle: &Candlestick) {
+        let Ok(price) = candle.close_price.parse::<f64>() else {
+            return;
+        };
+        // A still-forming candle closes in the future; stamp fills with "now" then.
+        let time = candle.close_time.min(now_ms());
+        self.prices.lock().unwrap().insert(symbol.to_string(), PriceMark { price, time });
+        self.check_triggers(symbol, candle);
+    }
+
+    async fn get_candlesticks_between(
+        &self,
+        symbol: &str,
+        interval: &str,
+        start_time: Option<u64>,
+        end_time: Option<u64>,
+        limit: u32,
+    ) -> Result<Vec<Candlestick>, String> {
+        self.market
+            .get_candlesticks_between(symbol, interval, start_time, end_time, limit)
+            .await
+    }
+
+    async fn get_current_price(&self, symbol: &str) -> Result<f64, String> {
+        match self.last_price(symbol) {
+            Some(price) => Ok(price),
+            None => self.market.get_current_price(symbol).await,
+        }
+    }
+
+    async fn server_time(&self) -> Result<u64, String> {
+        self.market.server_time().await
+    }
+
+    async fn get_symbol_meta(&self, symbol: &str) -> Result<SymbolMeta, String> {
+        self.market.get_symbol_meta(symbol).await
+    }
+
+    async fn get_book_ticker(&self, symbol: &str) -> Result<Option<BookTicker>, String> {
+        self.market.get_book_ticker(symbol).await
+    }
+
+    async fn get_positions(&self) -> Result<Vec<PositionResponse>, String> {
+        let account = self.account.lock().unwrap().clone();
+
+        Ok(account
+            .positions
+            .iter()
+            .map(|(symbol, position)| {
+                let mark = self.last_price(symbol).unwrap_or(position.entry_price);
+                let unrealized = position.amount * (mark - position.entry_price);
+                PositionResponse {
+                    symbol: symbol.clone(),
+                    position_amt: position.amount.to_string(),
+                    entry_price: position.entry_price.to_string(),
+                    mark_price: mark.to_string(),
+                    unrealized_profit: unrealized.to_string(),
+                    leverage: account.leverage_for(symbol, self.default_leverage).to_string(),
+                }
+            })
+            .collect())
+    }
+
+    async fn execute_future_order(&self, side: &str, symbol: &str) -> Result<OrderResponse, String> {
+        let price = self.get_current_price(symbol).await?;
+        let meta = self.get_symbol_meta(symbol).await?;
+        let money = to_decimal(self.money)?;
+
+        let quantity = order_quantity(money, to_decimal(price)?, &meta)?
+            .to_f64()
+            .ok_or("Quantity out of range")?;
+        let price = self.slipped(side, price);
+
+        let mut account = self.account.lock().unwrap();
+
+        // Only the part that opens or grows a position needs new margin.
+        let held = account.positions.get(symbol).map(|p| p.amount).unwrap_or(0.0);
+        let signed_quantity = if side == "BUY" { quantity } else { -quantity };
+        let opening = if held * signed_quantity < 0.0 { (quantity - held.abs()).max(0.0) } else { quantity };
+
+        let leverage = account.leverage_for(symbol, self.default_leverage) as f64;
+        let required_margin = opening * price / leverage + quantity * price * self.settings.fee_rate;
+        let available = self.available_margin(&account);
+        if required_margin > available {
+            return Err(format!(
+                "Paper account has insufficient margin ({:.2} USDT required, {:.2} USDT available)",
+                required_margin, available
+            ));
+        }
+
+        let id = self.apply_fill(&mut account, symbol, side, quantity, price);
+
+        println!("[PAPER] {} {} {} @ {}", side, quantity, symbol, price);
+        Ok(Self::order_response(id, symbol, side, quantity, price))
+    }
+
+    async fn close_all_positions(&self, symbol: &str) -> Result<Vec<OrderResponse>, String> {
+        let amount = match self.account.lock().unwrap().positions.get(symbol) {
+            Some(position) => position.amount,
+            None => return Ok(vec![]),
+        };
+
+        let side = if amount > 0.0 { "SELL" } else { "BUY" };
+        let price = self.slipped(side, self.get_current_price(symbol).await?);
+
+        let mut account = self.account.lock().unwrap();
+        account.triggers.remove(symbol);
+        let id = self.apply_fill(&mut account, symbol, side, amount.abs(), price);
+
+        println!(
+            "[PAPER] Closed {} {} @ {} (realized PnL total: {:.2})",
+            amount, symbol, price, account.realized_pnl
+        );
+        Ok(vec![Self::order_response(id, symbol, side, amount.abs(), price)])
+    }
+
+    async fn set_leverage(&self, symbol: &str, leverage: u32) -> Result<LeverageResponse, String> {
+        let mut account = self.account.lock().unwrap();
+        account.leverage.insert(symbol.to_string(), leverage);
+        if self.persistent {
+            self.persist(&account);
+        }
+
+        Ok(LeverageResponse {
+            leverage,
+            symbol: symbol.to_string(),
+        })
+    }
+
+    async fn place_protective_order(
+        &self,
+        symbol: &str,
+        side: &str,
+        order_type: &str,
+        stop_price: f64,
+    ) -> Result<OrderResponse, String> {
+        let placed_at = self.last_time(symbol);
+        let mut account = self.account.lock().unwrap();
+
+        let order_id = account.next_order_id;
+        account.next_order_id += 1;
+        account.triggers.entry(symbol.to_string()).or_default().push(PaperTrigger {
+            order_id,
+            side: side.to_string(),
+            order_type: order_type.to_string(),
+            stop_price,
+            placed_at,
+        });
+        if self.persistent {
+            self.persist(&account);
+        }
+
+        Ok(OrderResponse {
+            symbol: symbol.to_string(),
+            order_id,
+            status: "NEW".to_string(),
+            side: side.to_string(),
+            price: "0".to_string(),
+            orig_qty: "0".to_string(),
+            executed_qty: "0".to_string(),
+            cummulative_quote_qty: None,
+            avg_price: None,
+            time_in_force: "GTE_GTC".to_string(),
+            order_type: order_type.to_string(),
+            update_time: placed_at,
+        })
+    }
+
+    async fn cancel_protective_orders(&self, symbol: &str) -> Result<(), String> {
+        let mut account = self.account.lock().unwrap();
+        if account.triggers.remove(symbol).is_some() && self.persistent {
+            self.persist(&account);
+        }
+        Ok(())
+    }
+
+    async fn get_futures_balance(&self) -> Result<Vec<BalanceResponse>, String> {
+        let account = self.account.lock().unwrap();
+
+        Ok(vec![BalanceResponse {
+            asset: "USDT".to_string(),
+            total: account.wallet_balance.to_string(),
+            available: self.available_margin(&account).to_string(),
+        }])
+    }
+}
+
+fn load_account(path: &str) -> Option<PaperAccount> {
+    let content = fs::read_to_string(path).ok()?;
+    match serde_json::from_str(&content) {
+        Ok(account) => Some(account),
+        Err(e) => {
+            eprintln!("Error reading paper account {}: {}", path, e);
+            None
+        }
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64
+}
@@ -0,0 +1,108 @@
+use crate::blockchain::{add_action_block, ActionKind, TradeAction};
+use crate::config::{ProtectionSettings, ProtectionSource, Settings};
+use crate::dto::{Num, Trade, TradeStatus};
+use crate::exchange::Exchange;
+
+/// Zone levels the state machine reacts to in the trade's status: the one whose
+/// crossing invalidates it (stop) and the one it is heading to (target).
+fn zone_levels(trade: &Trade) -> (Option<f64>, Option<f64>) {
+    use TradeStatus::*;
+
+    let zone = |value: &Num| Some(value.to_f64()).filter(|v| *v > 0.0);
+
+    match trade.status {
+        Some(InZone7) => (zone(&trade.zone_5), None),
+        Some(InZone3) | Some(LongZone3) => (zone(&trade.zone_1), zone(&trade.zone_7)),
+        Some(TargetZone7) => (zone(&trade.zone_6), None),
+        Some(InZone1) => (zone(&trade.zone_3), None),
+        Some(InZone5) | Some(ShortZone5) => (zone(&trade.zone_7), zone(&trade.zone_1)),
+        Some(TargetZone1) => (zone(&trade.zone_2), None),
+        _ => (None, None),
+    }
+}
+
+/// Stop-loss and take-profit prices for a position of `amount` opened at
+/// `entry`. Zone levels on the wrong side of `price` would trigger
+/// immediately, so those fall back to the configured percentages.
+fn protective_levels(
+    trade: &Trade,
+    amount: f64,
+    entry: f64,
+    price: f64,
+    settings: &ProtectionSettings,
+) -> (f64, f64) {
+    let long = amount > 0.0;
+    let direction = if long { 1.0 } else { -1.0 };
+
+    let pct_stop = entry * (1.0 - direction * settings.stop_loss_pct / 100.0);
+    let pct_target = entry * (1.0 + direction * settings.take_profit_pct / 100.0);
+
+    let (zone_stop, zone_target) = match settings.source {
+        ProtectionSource::Zone => zone_levels(trade),
+        ProtectionSource::Percent => (None, None),
+    };
+
+    let below = |level: f64| if long { level < price } else { level > price };
+    let above = |level: f64| if long { level > price } else { level < price };
+
+    let stop = zone_stop.filter(|l| below(*l)).unwrap_or(pct_stop);
+    let target = zone_target.filter(|l| above(*l)).unwrap_or(pct_target);
+
+    (stop, target)
+}
+
+/// Replaces the exchange-side stop and target for `trade.symbol` so they
+/// match the current position and status. Flat symbols just get their
+/// leftover orders cancelled. Runs right after the orders of `decide`, so
+/// the position is read from the venue, never from the streamed account.
+pub async fn sync_protection(trade: &Trade, exchange: &dyn Exchange) {
+    let settings = Settings::load().protection;
+    if !settings.enabled {
+        return;
+    }
+
+    let position = match exchange.get_venue_positions().await {
+        Ok(positions) => positions
+            .into_iter()
+            .find(|p| p.symbol == trade.symbol && p.position_amt.parse::<f64>().unwrap_or(0.0) != 0.0),
+        Err(e) => {
+            eprintln!("Error loading positions for protective orders ({}): {}", trade.symbol, e);
+            return;
+        }
+    };
+
+    if let Err(e) = exchange.cancel_protective_orders(&trade.symbol).await {
+        eprintln!("Error cancelling protective orders for {}: {}", trade.symbol, e);
+        return;
+    }
+
+    let Some(position) = position else {
+        return;
+    };
+
+    let amount = position.position_amt.parse::<f64>().unwrap_or(0.0);
+    let entry = position.entry_price.parse::<f64>().unwrap_or(0.0);
+    let price = trade.current_price.to_f64();
+    if entry <= 0.0 || price <= 0.0 {
+        return;
+    }
+
+    let (stop, target) = protective_levels(trade, amount, entry, price, &settings);
+    let side = if amount > 0.0 { "SELL" } else { "BUY" };
+
+    for (order_type, stop_price) in [("STOP_MARKET", stop), ("TAKE_PROFIT_MARKET", target)] {
+        let result = exchange
+            .place_protective_order(&trade.symbol, side, order_type, stop_price)
+            .await;
+        match &result {
+            Ok(order) => println!(
+                "{} {} placed for {} at {} (order {})",
+                order_type, side, trade.symbol, stop_price, order.order_id
+            ),
+            Err(e) => eprintln!("Error placing {} for {}: {}", order_type, trade.symbol, e),
+        }
+
+        let action = TradeAction::from_orders(ActionKind::ProtectiveOrder, trade.status.clone(), result.map(|o| vec![o]));
+        add_action_block(&trade.symbol, action);
+    }
+}
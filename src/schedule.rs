@@ -5,15 +5,20 @@ use tokio::time::interval;
 use once_cell::sync::Lazy;
 
 use crate::config::Settings;
-use crate::spy::spy_cryptos;
-use crate::monitor::monitor_cryptos;
+use crate::dto::Trade;
+use crate::exchange::Exchange;
+use crate::spy::{closed_before, spy_cryptos, spy_streamed};
+use crate::stream::{get_market_data, start_stream};
+use crate::monitor::{load_monitor_account, monitor_cryptos};
 use crate::crypto_candidate::{process_existing_cryptos, choose_candidate_cryptos};
+use crate::universe::watched_symbols;
 
 static SCHEDULER: Lazy<Arc<Mutex<Scheduler>>> = Lazy::new(|| Arc::new(Mutex::new(Scheduler::new())));
 
 pub struct Scheduler {
     active: bool,
     handle: Option<JoinHandle<()>>,
+    stream_handle: Option<JoinHandle<()>>,
 }
 
 impl Scheduler {
@@ -21,6 +26,7 @@ impl Scheduler {
         Scheduler {
             active: false,
             handle: None,
+            stream_handle: None,
         }
     }
 
@@ -28,7 +34,7 @@ impl Scheduler {
         self.active
     }
 
-    pub fn start(&mut self) {
+    pub fn start(&mut self, exchange: Arc<dyn Exchange>) {
         if self.active {
             return;
         }
@@ -36,12 +42,21 @@ impl Scheduler {
         self.active = true;
         let settings = Settings::load();
 
+        // The mock server has no WebSocket endpoint, so it keeps polling.
+        if settings.stream.enabled && !settings.mock.enabled {
+            self.stream_handle = Some(start_stream(exchange.clone(), settings.clone()));
+            self.handle = Some(tokio::spawn(async move {
+                run_streamed(&settings, &exchange).await;
+            }));
+            return;
+        }
+
         self.handle = Some(tokio::spawn(async move {
             let mut interval = interval(Duration::from_secs(50));
 
             loop {
                 interval.tick().await;
-                execute_trade(&settings).await;
+                execute_trade(&settings, &exchange).await;
             }
         }));
     }
@@ -51,6 +66,9 @@ impl Scheduler {
         if let Some(handle) = self.handle.take() {
             handle.abort();
         }
+        if let Some(handle) = self.stream_handle.take() {
+            handle.abort();
+        }
     }
 }
 
@@ -58,17 +76,42 @@ pub fn get_scheduler() -> Arc<Mutex<Scheduler>> {
     SCHEDULER.clone()
 }
 
-async fn execute_trade(settings: &Settings) {
-    
-    let trades = spy_cryptos(
-        &settings.binance.base_url,
-        &settings.binance.interval,
-        settings.binance.limit,
-        settings.cryptos.clone(),
-    )
-    .await;
-
-    monitor_cryptos(&trades, settings);
-    process_existing_cryptos(&trades, settings).await;
-    choose_candidate_cryptos(trades, settings).await;
+async fn execute_trade(settings: &Settings, exchange: &Arc<dyn Exchange>) {
+    let trades = spy_cryptos(exchange, &settings.binance, watched_symbols()).await;
+
+    let account = load_monitor_account(exchange.as_ref(), settings).await;
+    monitor_cryptos(&trades, settings, account);
+    process_trades(trades, settings, exchange.as_ref()).await;
+}
+
+/// Evaluates on every stream update (at most once per `min_eval_ms`) and
+/// keeps printing the monitor on the usual 50s cadence.
+async fn run_streamed(settings: &Settings, exchange: &Arc<dyn Exchange>) {
+    let market = get_market_data();
+    let mut monitor = interval(Duration::from_secs(50));
+    let debounce = Duration::from_millis(settings.stream.min_eval_ms);
+
+    loop {
+        let show_monitor = tokio::select! {
+            _ = monitor.tick() => true,
+            _ = market.updated() => false,
+        };
+
+        let closed_before = closed_before(exchange.as_ref()).await;
+        let trades = spy_streamed(&market, &settings.binance, &watched_symbols(), closed_before);
+        if show_monitor {
+            let account = load_monitor_account(exchange.as_ref(), settings).await;
+            monitor_cryptos(&trades, settings, account);
+        }
+        process_trades(trades, settings, exchange.as_ref()).await;
+
+        tokio::time::sleep(debounce).await;
+    }
+}
+
+/// Feeds one cycle of trades through the chains and the decision engine.
+/// Shared by the live scheduler and the backtester.
+pub async fn process_trades(trades: Vec<Trade>, settings: &Settings, exchange: &dyn Exchange) {
+    process_existing_cryptos(&trades, settings, exchange).await;
+    choose_candidate_cryptos(trades, settings, exchange).await;
 }
@@ -0,0 +1,430 @@
+use crate::config::{SelectionSettings, UniverseFilters};
+use crate::dto::{Bias, Trade};
+use crate::exchange::Exchange;
+use crate::strategy::Strategy;
+use futures::stream::{self, StreamExt};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use serde::Serialize;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+const DAY_MS: u64 = 86_400_000;
+
+/// One eligibility check on a candidate.
+#[derive(Debug, Serialize, Clone)]
+pub struct FilterCheck {
+    pub name: &'static str,
+    pub passed: bool,
+    pub detail: String,
+}
+
+impl FilterCheck {
+    pub fn new(name: &'static str, passed: bool, detail: impl Into<String>) -> Self {
+        FilterCheck { name, passed, detail: detail.into() }
+    }
+}
+
+/// A factor of the score: `raw` as the strategy measured it (higher is
+/// better), `normalized` to 0..1 among the candidates that passed.
+#[derive(Debug, Serialize, Clone)]
+pub struct FactorScore {
+    pub name: &'static str,
+    pub raw: f64,
+    pub normalized: f64,
+    pub weight: f64,
+    pub contribution: f64,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct CandidateScore {
+    pub symbol: String,
+    pub bias: Bias,
+    pub passed: bool,
+    pub filters: Vec<FilterCheck>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub factors: Vec<FactorScore>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub score: Option<f64>,
+    /// Position in the seeded shuffle that orders equal scores.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tie_break: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rank: Option<usize>,
+    #[serde(skip)]
+    pub trade: Trade,
+}
+
+/// Every candidate of one selection, best first, and why the winner won.
+#[derive(Debug, Serialize, Clone)]
+pub struct CandidateReport {
+    pub seed: u64,
+    pub winner: Option<String>,
+    pub reason: String,
+    pub candidates: Vec<CandidateScore>,
+}
+
+impl CandidateReport {
+    /// The winning trade, carrying the reason it was chosen.
+    pub fn winner_trade(&self) -> Option<Trade> {
+        let winner = self.candidates.iter().find(|c| Some(&c.symbol) == self.winner.as_ref())?;
+        let mut trade = winner.trade.clone();
+        trade.selection = Some(self.reason.clone());
+        Some(trade)
+    }
+}
+
+/// Runs the universe filters on the symbols without a chain (those in
+/// `active` have one), at most `max_concurrent_fetches` symbols at a time,
+/// then ranks every trade.
+pub async fn select_candidates(
+    exchange: &dyn Exchange,
+    strategy: &dyn Strategy,
+    trades: Vec<Trade>,
+    active: &[String],
+    settings: &SelectionSettings,
+    max_concurrent_fetches: usize,
+) -> CandidateReport {
+    let free: Vec<Trade> = trades.iter().filter(|t| !active.contains(&t.symbol)).cloned().collect();
+    let filters = &settings.filters;
+    let checks = stream::iter(free)
+        .map(|t| async move { (t.symbol.clone(), universe_checks(exchange, &t, filters).await) })
+        .buffer_unordered(max_concurrent_fetches.max(1))
+        .collect()
+        .await;
+
+    rank_candidates(strategy, trades, active, &checks, settings)
+}
+
+/// The enabled limits of `filters` for `trade`. Listing age is measured at
+/// the newest candle, so backtests see the age the symbol had back then.
+async fn universe_checks(exchange: &dyn Exchange, trade: &Trade, filters: &UniverseFilters) -> Vec<FilterCheck> {
+    let mut checks = Vec::new();
+
+    if filters.min_quote_volume_24h > 0.0 {
+        let volume = trade.quote_volume_24h.as_ref().map(|v| v.to_f64()).unwrap_or(0.0);
+        checks.push(FilterCheck::new(
+            "quote_volume_24h",
+            volume >= filters.min_quote_volume_24h,
+            format!("{:.0} (minimo {:.0})", volume, filters.min_quote_volume_24h),
+        ));
+    }
+
+    if filters.min_atr_pct > 0.0 || filters.max_atr_pct > 0.0 {
+        let atr = trade.atr_pct.as_ref().map(|v| v.to_f64()).unwrap_or(0.0);
+        let max = if filters.max_atr_pct > 0.0 { filters.max_atr_pct } else { f64::INFINITY };
+        checks.push(FilterCheck::new(
+            "volatility",
+            atr >= filters.min_atr_pct && atr <= max,
+            format!("ATR {:.2}% (entre {} e {})", atr, filters.min_atr_pct, max),
+        ));
+    }
+
+    if filters.min_listing_days > 0 {
+        let check = match exchange.get_symbol_meta(&trade.symbol).await {
+            Ok(meta) if meta.onboard_date == 0 => FilterCheck::new("listing_age", true, "data de listagem desconhecida"),
+            Ok(meta) => {
+                let days = trade.candle_time.unwrap_or(0).saturating_sub(meta.onboard_date) / DAY_MS;
+                FilterCheck::new(
+                    "listing_age",
+                    days >= filters.min_listing_days,
+                    format!("{} dia(s) (minimo {})", days, filters.min_listing_days),
+                )
+            }
+            Err(e) => FilterCheck::new("listing_age", false, e),
+        };
+        checks.push(check);
+    }
+
+    if filters.max_spread_pct > 0.0 {
+        let check = match exchange.get_book_ticker(&trade.symbol).await {
+            Ok(None) => FilterCheck::new("spread", true, "indisponivel (sem livro de ofertas)"),
+            Ok(Some(book)) => {
+                let bid = book.bid_price.parse::<f64>().unwrap_or(0.0);
+                let ask = book.ask_price.parse::<f64>().unwrap_or(0.0);
+                let mid = (bid + ask) / 2.0;
+                let spread = if mid > 0.0 { (ask - bid) / mid * 100.0 } else { f64::INFINITY };
+                FilterCheck::new(
+                    "spread",
+                    spread <= filters.max_spread_pct,
+                    format!("{:.4}% (maximo {})", spread, filters.max_spread_pct),
+                )
+            }
+            Err(e) => FilterCheck::new("spread", false, e),
+        };
+        checks.push(check);
+    }
+
+    checks
+}
+
+/// Filters `trades` (symbols in `active` already have a chain; `checks`
+/// holds the universe filters of the others), scores the rest by the
+/// strategy's weighted factors and breaks ties with a shuffle seeded by
+/// `settings.seed` and the newest candle time, so the same candles and
+/// seed always give the same winner.
+fn rank_candidates(
+    strategy: &dyn Strategy,
+    mut trades: Vec<Trade>,
+    active: &[String],
+    checks: &HashMap<String, Vec<FilterCheck>>,
+    settings: &SelectionSettings,
+) -> CandidateReport {
+    trades.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+    let cycle = trades.iter().filter_map(|t| t.candle_time).max().unwrap_or(0);
+
+    let mut candidates: Vec<CandidateScore> = trades
+        .into_iter()
+        .map(|trade| {
+            let free = !active.contains(&trade.symbol);
+            let mut filters = vec![FilterCheck::new(
+                "free",
+                free,
+                if free { "sem blockchain ativa" } else { "ja possui blockchain ativa" },
+            )];
+            filters.extend(checks.get(&trade.symbol).cloned().unwrap_or_default());
+            filters.extend(strategy.candidate_filters(&trade));
+            CandidateScore {
+                symbol: trade.symbol.clone(),
+                bias: trade.bias.clone(),
+                passed: filters.iter().all(|f| f.passed),
+                filters,
+                factors: Vec::new(),
+                score: None,
+                tie_break: None,
+                rank: None,
+                trade,
+            }
+        })
+        .collect();
+
+    let mut passed: Vec<&mut CandidateScore> = candidates.iter_mut().filter(|c| c.passed).collect();
+    let raw: Vec<Vec<(&'static str, f64)>> = passed.iter().map(|c| strategy.candidate_factors(&c.trade)).collect();
+
+    let mut order: Vec<usize> = (0..passed.len()).collect();
+    order.shuffle(&mut StdRng::seed_from_u64(settings.seed ^ cycle));
+
+    for (i, candidate) in passed.iter_mut().enumerate() {
+        candidate.factors = raw[i]
+            .iter()
+            .enumerate()
+            .map(|(k, (name, value))| {
+                let column = raw.iter().map(|factors| factors[k].1);
+                let low = column.clone().fold(f64::INFINITY, f64::min);
+                let high = column.fold(f64::NEG_INFINITY, f64::max);
+                let normalized = if high > low { (value - low) / (high - low) } else { 1.0 };
+                let weight = settings.weight(name);
+                FactorScore { name, raw: *value, normalized, weight, contribution: normalized * weight }
+            })
+            .collect();
+        let score: f64 = candidate.factors.iter().map(|f| f.contribution).sum();
+        candidate.score = Some((score * 1e9).round() / 1e9);
+        candidate.tie_break = order.iter().position(|&o| o == i);
+    }
+
+    let by_rank = |a: &CandidateScore, b: &CandidateScore| {
+        b.score
+            .unwrap_or(f64::NEG_INFINITY)
+            .total_cmp(&a.score.unwrap_or(f64::NEG_INFINITY))
+            .then(a.tie_break.cmp(&b.tie_break))
+    };
+    candidates.sort_by(|a, b| match (a.passed, b.passed) {
+        (true, true) => by_rank(a, b),
+        (true, false) => Ordering::Less,
+        (false, true) => Ordering::Greater,
+        (false, false) => a.symbol.cmp(&b.symbol),
+    });
+    for (i, candidate) in candidates.iter_mut().filter(|c| c.passed).enumerate() {
+        candidate.rank = Some(i + 1);
+    }
+
+    let reason = explain(&candidates, settings.seed);
+    CandidateReport {
+        seed: settings.seed,
+        winner: candidates.first().filter(|c| c.passed).map(|c| c.symbol.clone()),
+        reason,
+        candidates,
+    }
+}
+
+fn explain(candidates: &[CandidateScore], seed: u64) -> String {
+    let passed: Vec<&CandidateScore> = candidates.iter().filter(|c| c.passed).collect();
+    let Some(winner) = passed.first() else {
+        return format!("nenhum de {} simbolo(s) passou nos filtros", candidates.len());
+    };
+
+    let factors: Vec<String> = winner
+        .factors
+        .iter()
+        .map(|f| format!("{} {:.3}x{}", f.name, f.normalized, f.weight))
+        .collect();
+    let mut reason = format!(
+        "{}: score {:.3} ({}), 1o de {} aprovado(s) entre {}",
+        winner.symbol,
+        winner.score.unwrap_or(0.0),
+        factors.join(" + "),
+        passed.len(),
+        candidates.len()
+    );
+    let tied: Vec<&str> = passed[1..]
+        .iter()
+        .filter(|c| c.score == winner.score)
+        .map(|c| c.symbol.as_str())
+        .collect();
+    if !tied.is_empty() {
+        reason.push_str(&format!("; empate com {} desfeito pelo seed {}", tied.join(", "), seed));
+    }
+    reason
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockchain::CloseReason;
+    use crate::dto::{Candlestick, TradeStatus};
+    use crate::strategy::StrategyAction;
+
+    /// Scores `strength` from `performance_24` and `volume` from `volume`,
+    /// with no filters of its own.
+    struct Factors;
+
+    impl Strategy for Factors {
+        fn name(&self) -> &'static str {
+            "factors"
+        }
+        fn analyse(&self, _symbol: &str, _candles: &[Candlestick], _reference: &[Candlestick]) -> Result<Trade, String> {
+            unimplemented!()
+        }
+        fn reference_symbol(&self, symbol: &str) -> String {
+            symbol.to_string()
+        }
+        fn advance(&self, trade: Trade, _history: &[Trade]) -> Trade {
+            trade
+        }
+        fn candidate_filters(&self, _trade: &Trade) -> Vec<FilterCheck> {
+            Vec::new()
+        }
+        fn candidate_factors(&self, trade: &Trade) -> Vec<(&'static str, f64)> {
+            vec![("strength", trade.performance_24.to_f64()), ("volume", trade.volume.to_f64())]
+        }
+        fn factor_names(&self) -> &'static [&'static str] {
+            &["strength", "volume"]
+        }
+        fn actions(&self, _trade: &Trade) -> Vec<StrategyAction> {
+            Vec::new()
+        }
+        fn close_reason(&self, _trade: &Trade, _statuses: &[Option<TradeStatus>]) -> Option<CloseReason> {
+            None
+        }
+        fn graph(&self) -> String {
+            String::new()
+        }
+    }
+
+    fn trade(symbol: &str, strength: f64, volume: f64) -> Trade {
+        let mut fields = serde_json::json!({
+            "symbol": symbol, "bias": Bias::Bullish, "status": null, "of": 0, "candle_time": 1_735_689_600_000u64,
+        });
+        for name in [
+            "current_price", "cma", "oma", "zone_max", "zone_7", "zone_6", "zone_5", "zone_4", "zone_3", "zone_2",
+            "zone_1", "zone_min", "performance_btc_24", "amplitude_ma_200", "quote_asset_volume", "number_of_trades",
+            "taker_buy_base_asset_volume", "taker_buy_quote_asset_volume",
+        ] {
+            fields[name] = "1".into();
+        }
+        fields["performance_24"] = strength.to_string().into();
+        fields["volume"] = volume.to_string().into();
+        serde_json::from_value(fields).unwrap()
+    }
+
+    fn ranked(trades: Vec<Trade>, active: &[String], settings: &SelectionSettings) -> Vec<String> {
+        rank_candidates(&Factors, trades, active, &HashMap::new(), settings)
+            .candidates
+            .into_iter()
+            .map(|c| c.symbol)
+            .collect()
+    }
+
+    fn tied() -> Vec<Trade> {
+        ["ADAUSDT", "BNBUSDT", "DOGEUSDT", "ETHUSDT", "SOLUSDT", "XRPUSDT"]
+            .iter()
+            .map(|symbol| trade(symbol, 2.0, 10.0))
+            .collect()
+    }
+
+    #[test]
+    fn same_trades_and_seed_give_the_same_winner() {
+        let settings = SelectionSettings { seed: 42, ..Default::default() };
+        let first = rank_candidates(&Factors, tied(), &[], &HashMap::new(), &settings);
+
+        let mut reversed = tied();
+        reversed.reverse();
+        let again = rank_candidates(&Factors, reversed, &[], &HashMap::new(), &settings);
+
+        assert!(first.winner.is_some());
+        assert_eq!(first.winner, again.winner);
+        assert_eq!(first.reason, again.reason);
+        assert!(first.reason.contains("desfeito pelo seed 42"), "{}", first.reason);
+        assert_eq!(
+            first.candidates.iter().map(|c| &c.symbol).collect::<Vec<_>>(),
+            again.candidates.iter().map(|c| &c.symbol).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn changing_the_seed_reorders_ties_only() {
+        let orders: Vec<Vec<String>> = (0..8)
+            .map(|seed| ranked(tied(), &[], &SelectionSettings { seed, ..Default::default() }))
+            .collect();
+        assert!(orders.iter().any(|order| order != &orders[0]), "{:?}", orders);
+
+        // A better score wins whatever the seed.
+        for seed in 0..8 {
+            let mut trades = tied();
+            trades.push(trade("BTCUSDT", 3.0, 10.0));
+            let order = ranked(trades, &[], &SelectionSettings { seed, ..Default::default() });
+            assert_eq!(order[0], "BTCUSDT");
+        }
+    }
+
+    #[test]
+    fn factors_are_normalized_among_the_passed_and_weighted() {
+        let settings = SelectionSettings { weights: HashMap::from([("volume".to_string(), 3.0)]), ..Default::default() };
+        let trades = vec![
+            trade("AAAUSDT", 0.0, 100.0),
+            trade("BBBUSDT", 5.0, 0.0),
+            trade("CCCUSDT", 10.0, 50.0),
+            // Active, so it neither ranks nor stretches the normalization.
+            trade("DDDUSDT", 99.0, 1000.0),
+        ];
+        let report = rank_candidates(&Factors, trades, &["DDDUSDT".to_string()], &HashMap::new(), &settings);
+
+        let symbols: Vec<&str> = report.candidates.iter().map(|c| c.symbol.as_str()).collect();
+        assert_eq!(symbols, ["AAAUSDT", "CCCUSDT", "BBBUSDT", "DDDUSDT"]);
+        assert_eq!(report.winner.as_deref(), Some("AAAUSDT"));
+
+        let scores: Vec<Option<f64>> = report.candidates.iter().map(|c| c.score).collect();
+        assert_eq!(scores, [Some(3.0), Some(2.5), Some(0.5), None]);
+        let ranks: Vec<Option<usize>> = report.candidates.iter().map(|c| c.rank).collect();
+        assert_eq!(ranks, [Some(1), Some(2), Some(3), None]);
+
+        // Unlisted factors weigh 1; the column min maps to 0 and the max to 1.
+        let ccc = &report.candidates[1];
+        assert_eq!((ccc.factors[0].name, ccc.factors[0].normalized, ccc.factors[0].weight), ("strength", 1.0, 1.0));
+        assert_eq!((ccc.factors[1].name, ccc.factors[1].normalized, ccc.factors[1].weight), ("volume", 0.5, 3.0));
+        assert_eq!(ccc.factors[1].contribution, 1.5);
+        assert!(!report.candidates[3].passed);
+        assert!(report.candidates[3].factors.is_empty());
+    }
+
+    #[test]
+    fn a_factor_equal_for_every_candidate_counts_in_full() {
+        let trades = vec![trade("AAAUSDT", 1.0, 7.0), trade("BBBUSDT", 2.0, 7.0)];
+        let report = rank_candidates(&Factors, trades, &[], &HashMap::new(), &SelectionSettings::default());
+        assert_eq!(report.candidates[0].symbol, "BBBUSDT");
+        assert_eq!(report.candidates[0].score, Some(2.0));
+        assert_eq!(report.candidates[1].score, Some(1.0));
+        assert!(report.candidates.iter().all(|c| c.factors[1].normalized == 1.0));
+    }
+}
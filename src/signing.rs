@@ -0,0 +1,139 @@
+use crate::config::LedgerSettings;
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use once_cell::sync::OnceCell;
+use rand::rngs::OsRng;
+use std::fs;
+use std::path::Path;
+
+static SIGNING_KEY: OnceCell<SigningKey> = OnceCell::new();
+static SIGNED_SINCE: OnceCell<u64> = OnceCell::new();
+
+/// Loads the operator key from `LEDGER_SIGNING_KEY` (hex seed) or from
+/// `settings.key_file`, generating the file on first use. Only the server
+/// calls this; without it blocks are left unsigned.
+pub fn init_signing_key(settings: &LedgerSettings) -> Result<VerifyingKey, String> {
+    let key = match std::env::var("LEDGER_SIGNING_KEY") {
+        Ok(seed) => key_from_hex(seed.trim())?,
+        Err(_) => load_or_create(Path::new(&settings.key_file))?,
+    };
+
+    let verifying = key.verifying_key();
+    SIGNING_KEY.get_or_init(|| key);
+    Ok(verifying)
+}
+
+/// Pins the unix time (s) from which every block must be signed:
+/// `settings.signed_since`, else the value stored in `{key_file}.since`.
+/// Without either, `inferred` (the first signed entry of the ledger) or now
+/// is written there once, so later runs never take it from the ledger.
+pub fn pin_signed_since(settings: &LedgerSettings, inferred: Option<u64>) -> Result<u64, String> {
+    let since = read_or_pin(settings, inferred)?;
+    Ok(*SIGNED_SINCE.get_or_init(|| since))
+}
+
+fn read_or_pin(settings: &LedgerSettings, inferred: Option<u64>) -> Result<u64, String> {
+    if let Some(since) = settings.signed_since {
+        return Ok(since);
+    }
+
+    let path = format!("{}.since", settings.key_file);
+    let path = Path::new(&path);
+    if path.exists() {
+        let text = fs::read_to_string(path).map_err(|e| format!("Erro ao ler {}: {}", path.display(), e))?;
+        return text
+            .trim()
+            .parse()
+            .map_err(|_| format!("Inicio das assinaturas invalido em {}: '{}'", path.display(), text.trim()));
+    }
+
+    let since = inferred.unwrap_or_else(|| chrono::Utc::now().timestamp() as u64);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("Erro ao criar {}: {}", dir.display(), e))?;
+    }
+    fs::write(path, since.to_string()).map_err(|e| format!("Erro ao gravar {}: {}", path.display(), e))?;
+    println!("Inicio das assinaturas do ledger fixado em {} ({})", since, path.display());
+    Ok(since)
+}
+
+/// Time from which blocks must be signed, once `pin_signed_since` ran.
+pub fn get_signed_since() -> Option<u64> {
+    SIGNED_SINCE.get().copied()
+}
+
+fn load_or_create(path: &Path) -> Result<SigningKey, String> {
+    if path.exists() {
+        let seed = fs::read_to_string(path).map_err(|e| format!("Erro ao ler {}: {}", path.display(), e))?;
+        return key_from_hex(seed.trim());
+    }
+
+    let key = SigningKey::generate(&mut OsRng);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("Erro ao criar {}: {}", dir.display(), e))?;
+    }
+    fs::write(path, hex::encode(key.to_bytes())).map_err(|e| format!("Erro ao gravar {}: {}", path.display(), e))?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let _ = fs::set_permissions(path, fs::Permissions::from_mode(0o600));
+    }
+
+    println!("Nova chave de assinatura do ledger criada em {}", path.display());
+    Ok(key)
+}
+
+fn key_from_hex(seed: &str) -> Result<SigningKey, String> {
+    let bytes: [u8; 32] = hex::decode(seed)
+        .ok()
+        .and_then(|b| b.try_into().ok())
+        .ok_or("Chave de assinatura invalida: esperado seed Ed25519 de 32 bytes em hex")?;
+    Ok(SigningKey::from_bytes(&bytes))
+}
+
+/// Hex signature of `message` with the operator key, if one is loaded.
+pub fn sign(message: &str) -> Option<String> {
+    SIGNING_KEY.get().map(|key| hex::encode(key.sign(message.as_bytes()).to_bytes()))
+}
+
+pub fn get_verifying_key() -> Option<VerifyingKey> {
+    SIGNING_KEY.get().map(SigningKey::verifying_key)
+}
+
+pub fn parse_verifying_key(value: &str) -> Result<VerifyingKey, String> {
+    let bytes: [u8; 32] = hex::decode(value.trim())
+        .ok()
+        .and_then(|b| b.try_into().ok())
+        .ok_or("Chave publica invalida: esperado 32 bytes em hex")?;
+    VerifyingKey::from_bytes(&bytes).map_err(|e| format!("Chave publica invalida: {}", e))
+}
+
+pub fn verify(key: &VerifyingKey, message: &str, signature: &str) -> bool {
+    let Some(bytes) = hex::decode(signature).ok().and_then(|b| <[u8; 64]>::try_from(b).ok()) else {
+        return false;
+    };
+    key.verify(message.as_bytes(), &Signature::from_bytes(&bytes)).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signing_start_is_pinned_once_outside_the_ledger() {
+        let dir = std::env::temp_dir().join(format!("signing_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let mut settings = LedgerSettings {
+            key_file: dir.join("ledger.key").to_str().unwrap().to_string(),
+            ..LedgerSettings::default()
+        };
+
+        assert_eq!(read_or_pin(&settings, Some(250)), Ok(250));
+        // A ledger stripped of every signature no longer moves the start.
+        assert_eq!(read_or_pin(&settings, None), Ok(250));
+        assert_eq!(fs::read_to_string(dir.join("ledger.key.since")).unwrap(), "250");
+
+        settings.signed_since = Some(100);
+        assert_eq!(read_or_pin(&settings, Some(250)), Ok(100));
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,153 @@
+use crate::candle_store::interval_ms;
+use crate::config::BinanceSettings;
+use crate::dto::Candlestick;
+use crate::exchange::Exchange;
+use futures::stream::{self, StreamExt};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Candles of one evaluation cycle: every symbol and bias reference fetched
+/// once and cut to the same last candle, so all trades of the cycle see the
+/// same moment of the market.
+pub struct MarketSnapshot {
+    series: HashMap<String, Arc<Vec<Candlestick>>>,
+    as_of: u64,
+}
+
+impl MarketSnapshot {
+    /// Fetches `symbols` over REST, at most `max_concurrent_fetches` at a
+    /// time. One candle more than `limit` is asked for, so aligning (or
+    /// dropping the forming candle) never leaves a series short. Symbols
+    /// that fail are left out.
+    pub async fn fetch(
+        exchange: &dyn Exchange,
+        settings: &BinanceSettings,
+        symbols: &[String],
+        closed_before: Option<u64>,
+    ) -> Self {
+        let mut unique = symbols.to_vec();
+        unique.sort();
+        unique.dedup();
+
+        let fetched: Vec<(String, Result<Vec<Candlestick>, String>)> = stream::iter(unique)
+            .map(|symbol| async move {
+                let candles = exchange.get_candlesticks(&symbol, &settings.interval, settings.limit + 1).await;
+                (symbol, candles)
+            })
+            .buffer_unordered(settings.max_concurrent_fetches.max(1))
+            .collect()
+            .await;
+
+        let series = fetched
+            .into_iter()
+            .filter_map(|(symbol, result)| match result {
+                Ok(candles) => Some((symbol, candles)),
+                Err(e) => {
+                    eprintln!("Erro ao buscar candles de {}: {}", symbol, e);
+                    None
+                }
+            })
+            .collect();
+
+        Self::aligned(series, &settings.interval, settings.limit as usize, closed_before)
+    }
+
+    /// Aligns `series` on the oldest last candle among the current ones (those
+    /// at most one interval behind the newest) and keeps `limit` candles of
+    /// each. Series further behind are reported stale and left out. With
+    /// `closed_before`, candles still open at that time are dropped first.
+    pub fn aligned(
+        mut series: HashMap<String, Vec<Candlestick>>,
+        interval: &str,
+        limit: usize,
+        closed_before: Option<u64>,
+    ) -> Self {
+        if let Some(now) = closed_before {
+            for candles in series.values_mut() {
+                candles.retain(|c| c.close_time < now);
+            }
+        }
+
+        let step = interval_ms(interval).unwrap_or(0);
+        let last_open = |candles: &Vec<Candlestick>| candles.last().map(|c| c.open_time).unwrap_or(0);
+
+        let newest = series.values().map(last_open).max().unwrap_or(0);
+        let as_of = series
+            .values()
+            .map(last_open)
+            .filter(|open| *open + step >= newest)
+            .min()
+            .unwrap_or(newest);
+
+        let (current, stale): (HashMap<_, _>, HashMap<_, _>) =
+            series.into_iter().partition(|(_, candles)| last_open(candles) >= as_of);
+        if !stale.is_empty() {
+            let mut names: Vec<&String> = stale.keys().collect();
+            names.sort();
+            eprintln!("Candles anteriores a {}, ignorados neste ciclo: {:?}", as_of, names);
+        }
+
+        let series = current
+            .into_iter()
+            .map(|(symbol, mut candles)| {
+                candles.retain(|c| c.open_time <= as_of);
+                let excess = candles.len().saturating_sub(limit);
+                candles.drain(..excess);
+                (symbol, Arc::new(candles))
+            })
+            .collect();
+
+        MarketSnapshot { series, as_of }
+    }
+
+    /// Open time of the last candle every series was cut to, in ms.
+    pub fn as_of(&self) -> u64 {
+        self.as_of
+    }
+
+    /// Candles of `symbol`, unless it is missing or stale.
+    pub fn series(&self, symbol: &str) -> Option<Arc<Vec<Candlestick>>> {
+        self.series.get(symbol).cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOUR: u64 = 3_600_000;
+
+    fn series(last_open: u64, count: u64) -> Vec<Candlestick> {
+        (0..count)
+            .map(|i| Candlestick {
+                open_time: last_open - (count - 1 - i) * HOUR,
+                open_price: "1".to_string(),
+                high_price: "1".to_string(),
+                low_price: "1".to_string(),
+                close_price: "1".to_string(),
+                volume: "0".to_string(),
+                close_time: last_open - (count - 1 - i) * HOUR + HOUR - 1,
+                quote_asset_volume: "0".to_string(),
+                number_of_trades: 0,
+                taker_buy_base_asset_volume: "0".to_string(),
+                taker_buy_quote_asset_volume: "0".to_string(),
+                ignore: "0".to_string(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn as_of_is_the_last_candle_shared_by_current_series() {
+        let windows = HashMap::from([
+            ("ETHUSDT".to_string(), series(100 * HOUR, 10)),
+            ("BTCUSDT".to_string(), series(99 * HOUR, 10)),
+            ("SOLUSDT".to_string(), series(90 * HOUR, 10)),
+        ]);
+        let snapshot = MarketSnapshot::aligned(windows, "1h", 5, None);
+
+        assert_eq!(snapshot.as_of(), 99 * HOUR);
+        assert_eq!(snapshot.series("ETHUSDT").unwrap().last().unwrap().open_time, 99 * HOUR);
+        assert_eq!(snapshot.series("ETHUSDT").unwrap().len(), 5);
+        assert!(snapshot.series("SOLUSDT").is_none());
+    }
+}
@@ -1,31 +1,92 @@
-use crate::binance::get_candlesticks;
-use crate::trade::generate_trade;
+use crate::config::BinanceSettings;
+use crate::exchange::Exchange;
+use crate::snapshot::MarketSnapshot;
+use crate::stream::MarketData;
+use crate::blockchain::get_status_trades_for;
+use crate::config::EvaluationMode;
+use crate::strategy::{evaluate, get_evaluation_mode, get_strategy};
 use crate::dto::Trade;
-use futures::future::join_all;
+use std::collections::HashMap;
+use std::sync::Arc;
 
 pub async fn spy_cryptos(
-    base_url: &str,
-    interval: &str,
-    limit: u32,
+    exchange: &Arc<dyn Exchange>,
+    settings: &BinanceSettings,
     symbols: Vec<String>,
 ) -> Vec<Trade> {
-    let tasks = symbols.into_iter().map(|symbol| {
-        let base_url = base_url.to_string();
-        let interval = interval.to_string();
-        let symbol_clone = symbol.clone();
+    let strategy = get_strategy();
+    let mut wanted = symbols.clone();
+    wanted.extend(symbols.iter().map(|s| strategy.reference_symbol(s)));
 
-        tokio::spawn(async move {
-            let candles = get_candlesticks(&base_url, &symbol_clone, &interval, limit).await?;
-            let ref_data = get_candlesticks(&base_url, "BTCUSDT", &interval, limit).await?;
-            let trade = generate_trade(symbol_clone, candles, ref_data);
-            Ok::<_, String>(trade)
+    let closed_before = closed_before(exchange.as_ref()).await;
+    let snapshot = MarketSnapshot::fetch(exchange.as_ref(), settings, &wanted, closed_before).await;
+    spy_snapshot(&snapshot, &symbols)
+}
+
+/// Venue time before which candles count as closed, when only closed
+/// candles are evaluated.
+pub async fn closed_before(exchange: &dyn Exchange) -> Option<u64> {
+    if get_evaluation_mode() != EvaluationMode::Closed {
+        return None;
+    }
+    match exchange.server_time().await {
+        Ok(time) => Some(time),
+        Err(e) => {
+            eprintln!("Erro ao obter hora do servidor, usando relogio local: {}", e);
+            Some(chrono::Utc::now().timestamp_millis() as u64)
+        }
+    }
+}
+
+/// Same as `spy_cryptos`, but built from the streamed windows without any
+/// REST call. Symbols whose window is not seeded yet are reported and
+/// skipped.
+pub fn spy_streamed(
+    market: &MarketData,
+    settings: &BinanceSettings,
+    symbols: &[String],
+    closed_before: Option<u64>,
+) -> Vec<Trade> {
+    let strategy = get_strategy();
+    let mut missing = Vec::new();
+    let windows: HashMap<_, _> = symbols
+        .iter()
+        .flat_map(|s| [s.clone(), strategy.reference_symbol(s)])
+        .filter_map(|s| match market.window(&s) {
+            Some(candles) => Some((s, candles)),
+            None => {
+                missing.push(s);
+                None
+            }
         })
-    });
+        .collect();
+    if !missing.is_empty() {
+        missing.sort();
+        missing.dedup();
+        eprintln!("Sem candles no stream, ignorados neste ciclo: {:?}", missing);
+    }
+
+    let snapshot = MarketSnapshot::aligned(windows, &settings.interval, settings.limit as usize, closed_before);
+    spy_snapshot(&snapshot, symbols)
+}
 
-    let results = join_all(tasks).await;
+/// Evaluates every symbol against the same snapshot and stamps each trade
+/// with its `as_of`. Symbols whose candles or reference are missing or stale
+/// are skipped.
+pub fn spy_snapshot(snapshot: &MarketSnapshot, symbols: &[String]) -> Vec<Trade> {
+    let strategy = get_strategy();
 
-    results
-        .into_iter()
-        .filter_map(|r| r.ok().and_then(|res| res.ok()))
+    symbols
+        .iter()
+        .filter_map(|symbol| {
+            let candles = snapshot.series(symbol)?;
+            let ref_data = snapshot.series(&strategy.reference_symbol(symbol))?;
+            let history = get_status_trades_for(symbol);
+            let mut trade = evaluate(strategy.as_ref(), symbol, &candles, &ref_data, &history)
+                .map_err(|e| eprintln!("Trade nao gerado para {} (candles ate {}): {}", symbol, snapshot.as_of(), e))
+                .ok()?;
+            trade.candle_time = Some(snapshot.as_of());
+            Some(trade)
+        })
         .collect()
 }
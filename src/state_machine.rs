@@ -0,0 +1,997 @@
+use crate::blockchain::CloseReason;
+use crate::config::EvaluationMode;
+use crate::dto::{Bias, CheckOutcome, Num, Trade, TradeStatus, TransitionCheck};
+use crate::strategy::StrategyAction;
+use crate::trade::zone_rank;
+use rust_decimal::Decimal;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use std::collections::{HashSet, VecDeque};
+use std::fmt::Write;
+
+/// Table shipped with the binary, used when `strategy.zone.machine` is empty.
+const DEFAULT_MACHINE: &str = include_str!("../config/StateMachine.toml");
+
+const BIASES: [Bias; 2] = [Bias::Bullish, Bias::Bearish];
+
+#[derive(Debug, Deserialize)]
+struct MachineFile {
+    transitions: Vec<TransitionRow>,
+    actions: Vec<ActionRow>,
+    #[serde(default)]
+    exits: Vec<ExitRow>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TransitionRow {
+    bias: String,
+    from: String,
+    #[serde(default)]
+    when: String,
+    to: String,
+    confirm: Option<u32>,
+    confirm_by: Option<ConfirmBy>,
+    buffer_pct: Option<f64>,
+    min_dwell_secs: Option<u64>,
+}
+
+/// What `confirm` counts: every evaluation, or only new candles.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum ConfirmBy {
+    Evaluations,
+    Candles,
+}
+
+#[derive(Debug, Deserialize)]
+struct ActionRow {
+    bias: Option<String>,
+    status: String,
+    actions: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ExitRow {
+    bias: Option<String>,
+    status: Option<String>,
+    last: Option<String>,
+    previous: Option<String>,
+    #[serde(default)]
+    when: String,
+    reason: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+/// `price OP level`, with levels numbered zone_min (0), zone_1..zone_7,
+/// zone_max (8). `rank` is the configured level behind it (`zone_rank`).
+#[derive(Debug, Clone, Copy)]
+struct Bound {
+    op: Op,
+    level: u8,
+    rank: u8,
+}
+
+/// Conjunction of bounds on the current price; empty always holds.
+#[derive(Debug, Clone, Default)]
+struct Guard {
+    bounds: Vec<Bound>,
+    text: String,
+}
+
+impl Guard {
+    fn parse(text: &str, count: usize) -> Result<Self, String> {
+        let text = text.trim();
+        let mut bounds = Vec::new();
+        if text.is_empty() {
+            return Ok(Guard::default());
+        }
+
+        for term in text.split("&&") {
+            let parts: Vec<&str> = term.split_whitespace().collect();
+            let [subject, op, level] = parts[..] else {
+                return Err(format!("condicao invalida '{}'", term.trim()));
+            };
+            if subject != "price" {
+                return Err(format!("condicao invalida '{}': esperado 'price'", term.trim()));
+            }
+            let op = match op {
+                "<" => Op::Lt,
+                "<=" => Op::Le,
+                ">" => Op::Gt,
+                ">=" => Op::Ge,
+                other => return Err(format!("operador desconhecido '{}'", other)),
+            };
+            let level = match level {
+                "zone_min" => 0,
+                "zone_max" => 8,
+                other => match other.strip_prefix("zone_").and_then(|k| k.parse::<u8>().ok()) {
+                    Some(k @ 1..=7) => k,
+                    _ => return Err(format!("nivel desconhecido '{}'", other)),
+                },
+            };
+            let rank = zone_rank(level as usize, count) as u8;
+            bounds.push(Bound { op, level, rank });
+        }
+
+        Ok(Guard { bounds, text: text.to_string() })
+    }
+
+    fn holds(&self, trade: &Trade) -> bool {
+        self.holds_beyond(trade, Decimal::ZERO)
+    }
+
+    /// Like `holds`, with every level pushed `buffer` (a fraction) further
+    /// in the direction the price has to cross it.
+    fn holds_beyond(&self, trade: &Trade, buffer: Decimal) -> bool {
+        let price = trade.current_price.value();
+        self.bounds.iter().all(|bound| {
+            let level = level_value(trade, bound.level);
+            let level = match bound.op {
+                Op::Gt | Op::Ge => level * (Decimal::ONE + buffer),
+                Op::Lt | Op::Le => level * (Decimal::ONE - buffer),
+            };
+            match bound.op {
+                Op::Lt => price < level,
+                Op::Le => price <= level,
+                Op::Gt => price > level,
+                Op::Ge => price >= level,
+            }
+        })
+    }
+
+    /// Price range the guard allows on a scale where every level sits at
+    /// 3 × rank, so strict and inclusive bounds on the same level differ.
+    fn range(&self) -> (i32, i32) {
+        self.bounds.iter().fold((i32::MIN, i32::MAX), |(low, high), bound| {
+            let at = 3 * bound.rank as i32;
+            match bound.op {
+                Op::Gt => (low.max(at + 1), high),
+                Op::Ge => (low.max(at), high),
+                Op::Lt => (low, high.min(at - 1)),
+                Op::Le => (low, high.min(at)),
+            }
+        })
+    }
+
+    /// Whether some price satisfies both guards. Levels of different rank
+    /// are strictly ordered and those of the same rank equal;
+    /// `generate_trade` refuses trades where they are not.
+    fn overlaps(&self, other: &Guard) -> bool {
+        let (a_low, a_high) = self.range();
+        let (b_low, b_high) = other.range();
+        a_low.max(b_low) <= a_high.min(b_high)
+    }
+
+    /// Whether every price that satisfies `other` also satisfies this guard.
+    fn covers(&self, other: &Guard) -> bool {
+        let (a_low, a_high) = self.range();
+        let (b_low, b_high) = other.range();
+        b_low > b_high || (a_low <= b_low && b_high <= a_high)
+    }
+}
+
+fn level_value(trade: &Trade, level: u8) -> Decimal {
+    match level {
+        0 => trade.zone_min.value(),
+        1 => trade.zone_1.value(),
+        2 => trade.zone_2.value(),
+        3 => trade.zone_3.value(),
+        4 => trade.zone_4.value(),
+        5 => trade.zone_5.value(),
+        6 => trade.zone_6.value(),
+        7 => trade.zone_7.value(),
+        _ => trade.zone_max.value(),
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Transition {
+    bias: Bias,
+    from: Option<TradeStatus>,
+    guard: Guard,
+    to: Option<TradeStatus>,
+    confirmation: Option<Confirmation>,
+}
+
+/// Requirements before a transition is taken: `required` consecutive
+/// evaluations (or candles) beyond the guard pushed out by `buffer`, after
+/// at least `min_dwell_ms` in the `from` status.
+#[derive(Debug, Clone)]
+struct Confirmation {
+    required: u32,
+    by: ConfirmBy,
+    buffer_pct: Decimal,
+    min_dwell_ms: u64,
+}
+
+impl Confirmation {
+    /// With `Closed` evaluation every poll between two closes sees the same
+    /// candle, so matches are always counted per new candle.
+    fn from_row(row: &TransitionRow, evaluation: EvaluationMode) -> Result<Option<Self>, String> {
+        if row.confirm.is_none() && row.buffer_pct.is_none() && row.min_dwell_secs.is_none() {
+            return Ok(None);
+        }
+        let required = row.confirm.unwrap_or(1);
+        if required == 0 {
+            return Err("confirm deve ser ao menos 1".into());
+        }
+        let buffer_pct = Num::from_f64(row.buffer_pct.unwrap_or(0.0), 6)?.value().normalize();
+        if buffer_pct < Decimal::ZERO || buffer_pct >= Decimal::ONE_HUNDRED {
+            return Err(format!("buffer_pct deve estar entre 0 e 100, recebido {}", buffer_pct));
+        }
+        Ok(Some(Confirmation {
+            required,
+            by: match evaluation {
+                EvaluationMode::Closed => ConfirmBy::Candles,
+                EvaluationMode::Intrabar => row.confirm_by.unwrap_or(ConfirmBy::Evaluations),
+            },
+            buffer_pct,
+            min_dwell_ms: row.min_dwell_secs.unwrap_or(0) * 1000,
+        }))
+    }
+
+    /// Outcome of `rule` for `trade`, whose guard already holds. `history`
+    /// ends with the last status block; `pending` is its check, if one is
+    /// still open.
+    fn check(
+        &self,
+        rule: &Transition,
+        trade: &Trade,
+        history: &[Trade],
+        pending: Option<&TransitionCheck>,
+    ) -> TransitionCheck {
+        let last = &history[history.len() - 1];
+        let outcome = |outcome, confirmations, reason| TransitionCheck {
+            from: rule.from.clone(),
+            to: rule.to.clone(),
+            outcome,
+            confirmations,
+            required: self.required,
+            reason,
+        };
+
+        if !rule.guard.holds_beyond(trade, self.buffer_pct / Decimal::ONE_HUNDRED) {
+            return outcome(
+                CheckOutcome::Rejected,
+                0,
+                format!("dentro da margem de {}% de '{}'", self.buffer_pct, rule.guard.text),
+            );
+        }
+
+        if self.min_dwell_ms > 0 {
+            let since = history
+                .iter()
+                .rev()
+                .take_while(|t| t.status == last.status)
+                .filter_map(|t| t.candle_time)
+                .min();
+            let dwell = trade.candle_time.zip(since).map(|(now, since)| now.saturating_sub(since)).unwrap_or(0);
+            if dwell < self.min_dwell_ms {
+                return outcome(
+                    CheckOutcome::Rejected,
+                    0,
+                    format!("permanencia minima de {}s em {} nao atingida", self.min_dwell_ms / 1000, status_name(&rule.from)),
+                );
+            }
+        }
+
+        let confirmations = match pending.filter(|p| p.to == rule.to) {
+            Some(p) if self.by == ConfirmBy::Candles && trade.candle_time <= last.candle_time => p.confirmations,
+            Some(p) => p.confirmations + 1,
+            None => 1,
+        };
+        let unit = match self.by {
+            ConfirmBy::Evaluations => "avaliacoes",
+            ConfirmBy::Candles => "candles",
+        };
+        if confirmations >= self.required {
+            outcome(
+                CheckOutcome::Confirmed,
+                confirmations,
+                format!("confirmado apos {} {} com '{}'", confirmations, unit, rule.guard.text),
+            )
+        } else {
+            outcome(
+                CheckOutcome::Pending,
+                confirmations,
+                format!("{}/{} {} com '{}'", confirmations, self.required, unit, rule.guard.text),
+            )
+        }
+    }
+
+    fn label(&self) -> String {
+        let mut parts = Vec::new();
+        if self.required > 1 {
+            let unit = if self.by == ConfirmBy::Candles { " candles" } else { "" };
+            parts.push(format!("x{}{}", self.required, unit));
+        }
+        if self.buffer_pct > Decimal::ZERO {
+            parts.push(format!("{}%", self.buffer_pct));
+        }
+        if self.min_dwell_ms > 0 {
+            parts.push(format!("dwell {}s", self.min_dwell_ms / 1000));
+        }
+        parts.join(", ")
+    }
+}
+
+#[derive(Debug, Clone)]
+struct ActionRule {
+    bias: Option<Bias>,
+    status: Option<TradeStatus>,
+    actions: Vec<StrategyAction>,
+}
+
+#[derive(Debug, Clone)]
+struct ExitRule {
+    bias: Option<Bias>,
+    status: Option<Option<TradeStatus>>,
+    last: Option<Option<TradeStatus>>,
+    previous: Option<Option<TradeStatus>>,
+    guard: Guard,
+    reason: CloseReason,
+}
+
+impl ExitRule {
+    /// Whether this rule matches every trade `other` matches.
+    fn shadows(&self, other: &ExitRule) -> bool {
+        fn wider<T: PartialEq>(a: &Option<T>, b: &Option<T>) -> bool {
+            a.is_none() || a == b
+        }
+        wider(&self.bias, &other.bias)
+            && wider(&self.status, &other.status)
+            && wider(&self.last, &other.last)
+            && wider(&self.previous, &other.previous)
+            && self.guard.covers(&other.guard)
+    }
+}
+
+/// Transitions, actions and exits of the zone strategy, loaded from TOML
+/// and checked by `validate`.
+#[derive(Debug, Clone)]
+pub struct StateMachine {
+    transitions: Vec<Transition>,
+    actions: Vec<ActionRule>,
+    exits: Vec<ExitRule>,
+    count: usize,
+}
+
+impl StateMachine {
+    /// Reads the table at `path`, or the built-in one when `path` is empty,
+    /// and validates it for `count` zone levels. `evaluation` decides how
+    /// confirmations are counted.
+    pub fn load(path: &str, evaluation: EvaluationMode, count: usize) -> Result<Self, String> {
+        let text = if path.is_empty() {
+            DEFAULT_MACHINE.to_string()
+        } else {
+            std::fs::read_to_string(path).map_err(|e| format!("Erro ao ler a maquina de estados {}: {}", path, e))?
+        };
+        Self::from_toml(&text, path, evaluation, count)
+    }
+
+    fn from_toml(text: &str, path: &str, evaluation: EvaluationMode, count: usize) -> Result<Self, String> {
+        let file: MachineFile = config::Config::builder()
+            .add_source(config::File::from_str(text, config::FileFormat::Toml))
+            .build()
+            .and_then(|c| c.try_deserialize())
+            .map_err(|e| format!("Erro ao ler a maquina de estados {}: {}", path, e))?;
+
+        let machine = Self::compile(file, evaluation, count)?;
+        machine.validate()?;
+        Ok(machine)
+    }
+
+    fn compile(file: MachineFile, evaluation: EvaluationMode, count: usize) -> Result<Self, String> {
+        let transitions = file
+            .transitions
+            .iter()
+            .enumerate()
+            .map(|(i, row)| {
+                let context = |e: String| format!("transitions[{}]: {}", i, e);
+                let bias = parse_name::<Bias>(&row.bias).map_err(context)?;
+                if !BIASES.contains(&bias) {
+                    return Err(context(format!("bias '{}' nao tem transicoes", row.bias)));
+                }
+                Ok(Transition {
+                    bias,
+                    from: parse_status(&row.from).map_err(context)?,
+                    guard: Guard::parse(&row.when, count).map_err(context)?,
+                    to: parse_status(&row.to).map_err(context)?,
+                    confirmation: Confirmation::from_row(row, evaluation).map_err(context)?,
+                })
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+
+        let actions = file
+            .actions
+            .iter()
+            .enumerate()
+            .map(|(i, row)| {
+                let context = |e: String| format!("actions[{}]: {}", i, e);
+                Ok(ActionRule {
+                    bias: row.bias.as_deref().map(parse_name::<Bias>).transpose().map_err(context)?,
+                    status: parse_status(&row.status).map_err(context)?,
+                    actions: row.actions.iter().map(|a| parse_action(a)).collect::<Result<_, _>>().map_err(context)?,
+                })
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+
+        let exits = file
+            .exits
+            .iter()
+            .enumerate()
+            .map(|(i, row)| {
+                let context = |e: String| format!("exits[{}]: {}", i, e);
+                let status = |value: &Option<String>| value.as_deref().map(parse_status).transpose().map_err(context);
+                Ok(ExitRule {
+                    bias: row.bias.as_deref().map(parse_name::<Bias>).transpose().map_err(context)?,
+                    status: status(&row.status)?,
+                    last: status(&row.last)?,
+                    previous: status(&row.previous)?,
+                    guard: Guard::parse(&row.when, count).map_err(context)?,
+                    reason: parse_name::<CloseReason>(&row.reason).map_err(context)?,
+                })
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+
+        Ok(StateMachine { transitions, actions, exits, count })
+    }
+
+    /// Rejects tables with statuses that cannot be reached from "None",
+    /// transitions out of one status whose conditions overlap, reachable
+    /// statuses without an action rule (or with more than one), exits on a
+    /// status their bias never reaches and exits an earlier one always
+    /// matches first.
+    pub fn validate(&self) -> Result<(), String> {
+        let mut errors = Vec::new();
+
+        for bias in BIASES {
+            let reachable = self.reachable(&bias);
+
+            let mentioned = self
+                .transitions
+                .iter()
+                .filter(|t| t.bias == bias)
+                .flat_map(|t| [t.from.clone(), t.to.clone()])
+                .chain(self.actions.iter().filter(|a| a.bias.as_ref() == Some(&bias)).map(|a| a.status.clone()));
+            let mut unreachable: Vec<String> =
+                mentioned.filter(|s| !reachable.contains(s)).map(|s| status_name(&s)).collect();
+            unreachable.sort();
+            unreachable.dedup();
+            for status in unreachable {
+                errors.push(format!("{} {}: inalcancavel a partir de None", bias, status));
+            }
+
+            for (i, a) in self.transitions.iter().enumerate().filter(|(_, t)| t.bias == bias) {
+                for b in self.transitions[i + 1..].iter().filter(|t| t.bias == bias && t.from == a.from) {
+                    if a.guard.overlaps(&b.guard) {
+                        errors.push(format!(
+                            "{} {}: condicoes sobrepostas '{}' e '{}'",
+                            bias,
+                            status_name(&a.from),
+                            a.guard.text,
+                            b.guard.text
+                        ));
+                    }
+                }
+            }
+
+            for status in &reachable {
+                match self.action_rules(&bias, status).count() {
+                    0 => errors.push(format!("{} {}: sem regra em actions", bias, status_name(status))),
+                    1 => {}
+                    _ => errors.push(format!("{} {}: mais de uma regra em actions", bias, status_name(status))),
+                }
+            }
+        }
+
+        for (i, exit) in self.exits.iter().enumerate() {
+            let biases: Vec<Bias> = match &exit.bias {
+                Some(bias) => vec![bias.clone()],
+                None => BIASES.to_vec(),
+            };
+            let reachable: Vec<Option<TradeStatus>> = biases.iter().flat_map(|b| self.reachable(b)).collect();
+            for (field, status) in [("status", &exit.status), ("last", &exit.last), ("previous", &exit.previous)] {
+                if let Some(status) = status.as_ref().filter(|s| !reachable.contains(s)) {
+                    errors.push(format!(
+                        "exits[{}]: {} {} inalcancavel para {}",
+                        i,
+                        field,
+                        status_name(status),
+                        exit.bias.as_ref().map_or("nenhum bias".to_string(), |b| b.to_string())
+                    ));
+                }
+            }
+
+            if let Some(j) = self.exits[..i].iter().position(|earlier| earlier.shadows(exit)) {
+                errors.push(format!("exits[{}]: nunca aplicada, exits[{}] sempre casa antes", i, j));
+            }
+        }
+
+        if self.action_rules(&Bias::None, &None).count() != 1 {
+            errors.push("None: esperada exatamente uma regra em actions para o bias None".to_string());
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(format!(
+                "Maquina de estados invalida com strategy.zone.count = {}:\n  {}",
+                self.count,
+                errors.join("\n  ")
+            ))
+        }
+    }
+
+    fn reachable(&self, bias: &Bias) -> Vec<Option<TradeStatus>> {
+        let mut seen = vec![None];
+        let mut queue = VecDeque::from([None]);
+
+        while let Some(status) = queue.pop_front() {
+            for t in self.transitions.iter().filter(|t| &t.bias == bias && t.from == status) {
+                if !seen.contains(&t.to) {
+                    seen.push(t.to.clone());
+                    queue.push_back(t.to.clone());
+                }
+            }
+        }
+        seen
+    }
+
+    fn action_rules<'a>(
+        &'a self,
+        bias: &'a Bias,
+        status: &'a Option<TradeStatus>,
+    ) -> impl Iterator<Item = &'a ActionRule> {
+        self.actions
+            .iter()
+            .filter(move |a| a.bias.as_ref().is_none_or(|b| b == bias) && &a.status == status)
+    }
+
+    /// Sets the status of `trade` from the status blocks of its chain,
+    /// oldest first. A change of bias resets it; otherwise the first
+    /// matching transition wins, and no match means no status. Transitions
+    /// with confirmation rules keep the last status until confirmed, and
+    /// record their progress in `trade.transition`.
+    pub fn advance(&self, mut trade: Trade, history: &[Trade]) -> Trade {
+        let Some(last) = history.last() else {
+            return trade;
+        };
+        if trade.bias != last.bias {
+            trade.status = None;
+            return trade;
+        }
+
+        let rule = self
+            .transitions
+            .iter()
+            .find(|t| t.bias == trade.bias && t.from == last.status && t.guard.holds(&trade));
+        let pending = last
+            .transition
+            .as_ref()
+            .filter(|c| c.outcome == CheckOutcome::Pending && c.from == last.status);
+
+        let check = rule
+            .filter(|t| t.to != last.status)
+            .and_then(|t| t.confirmation.as_ref().map(|c| c.check(t, &trade, history, pending)));
+
+        match check {
+            Some(check) => {
+                trade.status = match check.outcome {
+                    CheckOutcome::Confirmed => check.to.clone(),
+                    _ => last.status.clone(),
+                };
+                trade.transition = Some(check);
+            }
+            None => {
+                trade.status = rule.and_then(|t| t.to.clone());
+                trade.transition = pending.map(|p| TransitionCheck {
+                    outcome: CheckOutcome::Rejected,
+                    reason: format!("nao confirmado ({}/{}): preco saiu da condicao", p.confirmations, p.required),
+                    ..p.clone()
+                });
+            }
+        }
+        trade
+    }
+
+    pub fn actions(&self, trade: &Trade) -> Vec<StrategyAction> {
+        self.actions_for(&trade.bias, &trade.status)
+    }
+
+    fn actions_for(&self, bias: &Bias, status: &Option<TradeStatus>) -> Vec<StrategyAction> {
+        self.action_rules(bias, status)
+            .next()
+            .map(|a| a.actions.clone())
+            .unwrap_or_default()
+    }
+
+    pub fn close_reason(&self, trade: &Trade, statuses: &[Option<TradeStatus>]) -> Option<CloseReason> {
+        let last = statuses.last();
+        let previous = statuses.len().checked_sub(2).map(|i| &statuses[i]);
+
+        self.exits
+            .iter()
+            .find(|e| {
+                e.bias.as_ref().is_none_or(|b| b == &trade.bias)
+                    && e.status.as_ref().is_none_or(|s| s == &trade.status)
+                    && e.last.as_ref().is_none_or(|s| Some(s) == last)
+                    && e.previous.as_ref().is_none_or(|s| Some(s) == previous)
+                    && e.guard.holds(trade)
+            })
+            .map(|e| e.reason.clone())
+    }
+
+    /// Graphviz rendering: one cluster per bias, statuses labelled with
+    /// their actions, transitions with their conditions and exits as
+    /// dashed edges.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph state_machine {\n  rankdir=LR;\n  node [shape=box];\n");
+
+        for bias in BIASES {
+            let node = |status: &Option<TradeStatus>| format!("\"{}/{}\"", bias, status_name(status));
+            let _ = writeln!(dot, "  subgraph \"cluster_{}\" {{\n    label=\"{}\";", bias, bias);
+
+            for status in self.reachable(&bias) {
+                let actions: Vec<String> = self.actions_for(&bias, &status).iter().map(action_name).collect();
+                let _ = writeln!(
+                    dot,
+                    "    {} [label=\"{}\\n{}\"];",
+                    node(&status),
+                    status_name(&status),
+                    actions.join(", ")
+                );
+            }
+            for t in self.transitions.iter().filter(|t| t.bias == bias) {
+                let label = match &t.confirmation {
+                    Some(c) => format!("{} [{}]", t.guard.text, c.label()),
+                    None => t.guard.text.clone(),
+                };
+                let _ = writeln!(dot, "    {} -> {} [label=\"{}\"];", node(&t.from), node(&t.to), label);
+            }
+
+            let mut exits = HashSet::new();
+            for e in self.exits.iter().filter(|e| e.bias.as_ref().is_none_or(|b| b == &bias)) {
+                let exit = format!("\"{}/exit {:?}\"", bias, e.reason);
+                if exits.insert(exit.clone()) {
+                    let _ = writeln!(dot, "    {} [shape=doublecircle, label=\"{:?}\"];", exit, e.reason);
+                }
+                let from = e.status.clone().or_else(|| e.last.clone()).unwrap_or(None);
+                let mut label = Vec::new();
+                if let Some(previous) = &e.previous {
+                    label.push(format!("after {}", status_name(previous)));
+                }
+                if !e.guard.text.is_empty() {
+                    label.push(e.guard.text.clone());
+                }
+                let _ = writeln!(dot, "    {} -> {} [style=dashed, label=\"{}\"];", node(&from), exit, label.join(", "));
+            }
+
+            dot.push_str("  }\n");
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+/// `machine-graph [table.toml]`: validates the table (by default the one
+/// configured in `strategy.zone.machine`) and prints it as Graphviz DOT.
+pub fn run_cli(args: &[String]) -> std::io::Result<()> {
+    let settings = crate::config::Settings::load();
+    let path = match args {
+        [] => settings.strategy.zone.machine,
+        [path] if !path.starts_with("--") => path.clone(),
+        _ => return Err(std::io::Error::other("Usage: machine-graph [state-machine.toml]")),
+    };
+    let machine = StateMachine::load(&path, settings.strategy.evaluation, settings.strategy.zone.count)
+        .map_err(std::io::Error::other)?;
+    print!("{}", machine.to_dot());
+    Ok(())
+}
+
+fn parse_name<T: DeserializeOwned>(name: &str) -> Result<T, String> {
+    serde_json::from_value(serde_json::Value::String(name.to_string()))
+        .map_err(|_| format!("nome desconhecido '{}'", name))
+}
+
+fn parse_status(name: &str) -> Result<Option<TradeStatus>, String> {
+    if name == "None" {
+        return Ok(None);
+    }
+    parse_name(name).map(Some)
+}
+
+fn status_name(status: &Option<TradeStatus>) -> String {
+    match status {
+        Some(status) => format!("{:?}", status),
+        None => "None".to_string(),
+    }
+}
+
+fn parse_action(name: &str) -> Result<StrategyAction, String> {
+    match name {
+        "Buy" => Ok(StrategyAction::Buy),
+        "Sell" => Ok(StrategyAction::Sell),
+        "ClosePositions" => Ok(StrategyAction::ClosePositions),
+        other => other
+            .strip_prefix("SetLeverage(")
+            .and_then(|rest| rest.strip_suffix(')'))
+            .and_then(|n| n.parse::<u32>().ok())
+            .filter(|n| *n > 0)
+            .map(StrategyAction::SetLeverage)
+            .ok_or_else(|| format!("acao desconhecida '{}'", other)),
+    }
+}
+
+fn action_name(action: &StrategyAction) -> String {
+    match action {
+        StrategyAction::SetLeverage(n) => format!("SetLeverage({})", n),
+        other => format!("{:?}", other),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use TradeStatus::*;
+
+    const STATUSES: [Option<TradeStatus>; 15] = [
+        None,
+        Some(InZone7),
+        Some(OutZone5),
+        Some(PrepareZone1),
+        Some(InZone3),
+        Some(PrepareZone1Long),
+        Some(LongZone3),
+        Some(TargetZone7),
+        Some(InZone1),
+        Some(OutZone3),
+        Some(PrepareZone7),
+        Some(InZone5),
+        Some(PrepareZone7Short),
+        Some(ShortZone5),
+        Some(TargetZone1),
+    ];
+
+    /// Trade with zone_k at 10·k, zone_min at 5 and zone_max at 75.
+    fn trade(bias: Bias, price: f64, status: Option<TradeStatus>) -> Trade {
+        let mut fields = serde_json::json!({"symbol": "ETHUSDT", "bias": bias, "status": status, "of": 0});
+        for k in 1..=7 {
+            fields[format!("zone_{}", k)] = (k * 10).to_string().into();
+        }
+        fields["zone_min"] = "5".into();
+        fields["zone_max"] = "75".into();
+        fields["current_price"] = price.to_string().into();
+        for name in [
+            "cma", "oma", "performance_24", "performance_btc_24", "amplitude_ma_200", "volume", "quote_asset_volume",
+            "number_of_trades", "taker_buy_base_asset_volume", "taker_buy_quote_asset_volume",
+        ] {
+            fields[name] = "1".into();
+        }
+        serde_json::from_value(fields).unwrap()
+    }
+
+    /// `handle_bullish_status` and `handle_bearish_status` as they were
+    /// before the table, with the zones of `trade()`.
+    fn legacy_status(bias: &Bias, last: &Option<TradeStatus>, price: f64) -> Option<TradeStatus> {
+        let (z1, z2, z3, z5, z6, z7) = (10.0, 20.0, 30.0, 50.0, 60.0, 70.0);
+        match bias {
+            Bias::Bullish => match last {
+                None if price >= z7 => Some(InZone7),
+                None if price <= z1 => Some(PrepareZone1),
+                Some(OutZone5) if price >= z7 => Some(InZone7),
+                Some(InZone7) if price > z5 => Some(InZone7),
+                Some(InZone7) if price <= z5 => Some(OutZone5),
+                Some(OutZone5) if price < z7 && price > z1 => Some(OutZone5),
+                Some(OutZone5) if price <= z1 => Some(PrepareZone1),
+                Some(PrepareZone1) if price < z3 => Some(PrepareZone1),
+                Some(PrepareZone1) if price >= z3 => Some(InZone3),
+                Some(InZone3) if price >= z7 => Some(TargetZone7),
+                Some(InZone3) if price < z7 && price > z1 => Some(InZone3),
+                Some(InZone3) if price <= z1 => Some(PrepareZone1Long),
+                Some(PrepareZone1Long) if price < z3 => Some(PrepareZone1Long),
+                Some(PrepareZone1Long) if price >= z3 => Some(LongZone3),
+                Some(LongZone3) if price > z1 && price < z7 => Some(LongZone3),
+                Some(LongZone3) if price <= z1 => Some(PrepareZone1),
+                Some(LongZone3) if price >= z7 => Some(TargetZone7),
+                Some(TargetZone7) if price > z6 => Some(TargetZone7),
+                _ => None,
+            },
+            Bias::Bearish => match last {
+                None if price <= z1 => Some(InZone1),
+                None if price >= z7 => Some(PrepareZone7),
+                Some(OutZone3) if price <= z1 => Some(InZone1),
+                Some(InZone1) if price < z3 => Some(InZone1),
+                Some(InZone1) if price >= z3 => Some(OutZone3),
+                Some(OutZone3) if price > z1 && price < z7 => Some(OutZone3),
+                Some(OutZone3) if price >= z7 => Some(PrepareZone7),
+                Some(PrepareZone7) if price > z5 => Some(PrepareZone7),
+                Some(PrepareZone7) if price <= z5 => Some(InZone5),
+                Some(InZone5) if price <= z1 => Some(TargetZone1),
+                Some(InZone5) if price > z1 && price < z7 => Some(InZone5),
+                Some(InZone5) if price >= z7 => Some(PrepareZone7Short),
+                Some(PrepareZone7Short) if price > z5 => Some(PrepareZone7Short),
+                Some(PrepareZone7Short) if price <= z5 => Some(ShortZone5),
+                Some(ShortZone5) if price < z7 && price > z1 => Some(ShortZone5),
+                Some(ShortZone5) if price >= z7 => Some(PrepareZone7),
+                Some(ShortZone5) if price <= z1 => Some(TargetZone1),
+                Some(TargetZone1) if price < z2 => Some(TargetZone1),
+                _ => None,
+            },
+            Bias::None => None,
+        }
+    }
+
+    fn machine(text: &str) -> Result<StateMachine, String> {
+        StateMachine::from_toml(text, "test.toml", EvaluationMode::Intrabar, 7)
+    }
+
+    #[test]
+    fn default_table_reproduces_the_old_status_handlers() {
+        let machine = StateMachine::load("", EvaluationMode::Intrabar, 7).unwrap();
+
+        for bias in [Bias::Bullish, Bias::Bearish, Bias::None] {
+            for last in STATUSES {
+                // Every level, and the prices half a step around each one.
+                for step in 8..=152 {
+                    let price = step as f64 / 2.0;
+                    let history = [trade(bias.clone(), price, last.clone())];
+                    let advanced = machine.advance(trade(bias.clone(), price, None), &history);
+                    assert_eq!(
+                        advanced.status,
+                        legacy_status(&bias, &last, price),
+                        "{} {:?} price {}",
+                        bias,
+                        last,
+                        price
+                    );
+                }
+            }
+        }
+
+        let history = [trade(Bias::Bearish, 80.0, Some(InZone7))];
+        assert_eq!(machine.advance(trade(Bias::Bullish, 80.0, None), &history).status, None);
+    }
+
+    const BASE: &str = r#"
+        transitions = [
+          { bias = "Bullish", from = "None", when = "price >= zone_7", to = "InZone7" },
+          { bias = "Bullish", from = "InZone7", when = "price > zone_5", to = "InZone7" },
+          { bias = "Bearish", from = "None", when = "price <= zone_1", to = "InZone1" },
+          { bias = "Bearish", from = "InZone1", when = "price < zone_3", to = "InZone1" },
+        ]
+        actions = [
+          { status = "None", actions = ["ClosePositions"] },
+          { bias = "Bullish", status = "InZone7", actions = ["Buy"] },
+          { bias = "Bearish", status = "InZone1", actions = ["Sell"] },
+        ]
+    "#;
+
+    #[test]
+    fn exit_on_a_status_its_bias_never_reaches_is_rejected() {
+        let table = format!("{}\nexits = [{{ bias = \"Bullish\", status = \"InZone1\", reason = \"OutZone3\" }}]", BASE);
+        let error = machine(&table).unwrap_err();
+        assert!(error.contains("exits[0]: status InZone1 inalcancavel para Bullish"), "{}", error);
+
+        let table = format!("{}\nexits = [{{ last = \"TargetZone7\", reason = \"Invalidation\" }}]", BASE);
+        let error = machine(&table).unwrap_err();
+        assert!(error.contains("exits[0]: last TargetZone7 inalcancavel para nenhum bias"), "{}", error);
+
+        let table = format!("{}\nexits = [{{ status = \"InZone1\", reason = \"OutZone3\" }}]", BASE);
+        assert!(machine(&table).is_ok());
+    }
+
+    #[test]
+    fn exit_shadowed_by_an_earlier_one_is_rejected() {
+        let shadowed = r#"exits = [
+          { status = "None", when = "price > zone_3 && price < zone_6", reason = "NeutralZone" },
+          { bias = "Bullish", status = "None", when = "price > zone_4 && price <= zone_5", reason = "Invalidation" },
+        ]"#;
+        let error = machine(&format!("{}\n{}", BASE, shadowed)).unwrap_err();
+        assert!(error.contains("exits[1]: nunca aplicada, exits[0] sempre casa antes"), "{}", error);
+
+        let reachable = r#"exits = [
+          { status = "None", when = "price > zone_4 && price <= zone_5", reason = "NeutralZone" },
+          { bias = "Bullish", status = "None", when = "price > zone_3 && price < zone_6", reason = "Invalidation" },
+        ]"#;
+        assert!(machine(&format!("{}\n{}", BASE, reachable)).is_ok());
+    }
+
+    const CONFIRMED: &str = r#"
+        transitions = [
+          { bias = "Bullish", from = "None", when = "price >= zone_7", to = "InZone7", confirm = 3, buffer_pct = 1.0 },
+          { bias = "Bullish", from = "InZone7", when = "price > zone_5", to = "InZone7" },
+          { bias = "Bullish", from = "InZone7", when = "price <= zone_5", to = "None", min_dwell_secs = 7200 },
+          { bias = "Bearish", from = "None", when = "price <= zone_1", to = "InZone1" },
+          { bias = "Bearish", from = "InZone1", when = "price < zone_3", to = "InZone1" },
+        ]
+        actions = [
+          { status = "None", actions = ["ClosePositions"] },
+          { bias = "Bullish", status = "InZone7", actions = ["Buy"] },
+          { bias = "Bearish", status = "InZone1", actions = ["Sell"] },
+        ]
+    "#;
+
+    /// Advances a Bullish trade at `price` and `candle_time` (in hours) and
+    /// appends it to `history`.
+    fn step(machine: &StateMachine, history: &mut Vec<Trade>, price: f64, hour: u64) -> Trade {
+        let mut next = trade(Bias::Bullish, price, None);
+        next.candle_time = Some(hour * 3_600_000);
+        let next = machine.advance(next, history);
+        history.push(next.clone());
+        next
+    }
+
+    fn outcome(trade: &Trade) -> (Option<TradeStatus>, CheckOutcome, u32) {
+        let check = trade.transition.as_ref().expect("transition check");
+        (trade.status.clone(), check.outcome, check.confirmations)
+    }
+
+    #[test]
+    fn transition_is_pending_until_confirmed() {
+        let machine = machine(CONFIRMED).unwrap();
+        let mut history = vec![trade(Bias::Bullish, 60.0, None)];
+
+        assert_eq!(outcome(&step(&machine, &mut history, 71.0, 1)), (None, CheckOutcome::Pending, 1));
+        assert_eq!(outcome(&step(&machine, &mut history, 71.0, 1)), (None, CheckOutcome::Pending, 2));
+        assert_eq!(outcome(&step(&machine, &mut history, 72.0, 2)), (Some(InZone7), CheckOutcome::Confirmed, 3));
+    }
+
+    #[test]
+    fn pending_transition_is_rejected_when_the_price_leaves() {
+        let machine = machine(CONFIRMED).unwrap();
+        let mut history = vec![trade(Bias::Bullish, 60.0, None)];
+
+        step(&machine, &mut history, 71.0, 1);
+        let left = step(&machine, &mut history, 65.0, 2);
+        assert_eq!(outcome(&left), (None, CheckOutcome::Rejected, 1));
+        assert!(left.transition.unwrap().reason.contains("preco saiu da condicao"));
+
+        assert_eq!(outcome(&step(&machine, &mut history, 71.0, 3)), (None, CheckOutcome::Pending, 1));
+    }
+
+    #[test]
+    fn price_inside_the_buffer_is_rejected() {
+        let machine = machine(CONFIRMED).unwrap();
+        let mut history = vec![trade(Bias::Bullish, 60.0, None)];
+
+        // zone_7 is 70; with a 1% buffer the price has to reach 70.7.
+        let inside = step(&machine, &mut history, 70.5, 1);
+        assert_eq!(outcome(&inside), (None, CheckOutcome::Rejected, 0));
+        assert!(inside.transition.unwrap().reason.contains("dentro da margem de 1%"));
+    }
+
+    #[test]
+    fn transition_waits_for_the_minimum_dwell() {
+        let machine = machine(CONFIRMED).unwrap();
+        let mut entered = trade(Bias::Bullish, 75.0, Some(InZone7));
+        entered.candle_time = Some(0);
+        let mut history = vec![entered];
+
+        let early = step(&machine, &mut history, 45.0, 1);
+        assert_eq!(outcome(&early), (Some(InZone7), CheckOutcome::Rejected, 0));
+        assert!(early.transition.unwrap().reason.contains("permanencia minima de 7200s"));
+
+        assert_eq!(outcome(&step(&machine, &mut history, 45.0, 2)), (None, CheckOutcome::Confirmed, 1));
+    }
+
+    #[test]
+    fn closed_evaluation_counts_each_candle_once() {
+        let machine = StateMachine::from_toml(CONFIRMED, "test.toml", EvaluationMode::Closed, 7).unwrap();
+        let mut history = vec![trade(Bias::Bullish, 60.0, None)];
+
+        assert_eq!(outcome(&step(&machine, &mut history, 71.0, 1)), (None, CheckOutcome::Pending, 1));
+        assert_eq!(outcome(&step(&machine, &mut history, 71.0, 1)), (None, CheckOutcome::Pending, 1));
+        assert_eq!(outcome(&step(&machine, &mut history, 71.0, 1)), (None, CheckOutcome::Pending, 1));
+        assert_eq!(outcome(&step(&machine, &mut history, 71.0, 2)), (None, CheckOutcome::Pending, 2));
+        assert_eq!(outcome(&step(&machine, &mut history, 71.0, 3)), (Some(InZone7), CheckOutcome::Confirmed, 3));
+    }
+}
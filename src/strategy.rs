@@ -0,0 +1,222 @@
+use crate::blockchain::CloseReason;
+use crate::config::{EvaluationMode, Settings, ZoneSpacing};
+use crate::state_machine::StateMachine;
+use crate::trade::required_candles;
+use crate::dto::{Candlestick, Trade, TradeStatus};
+use crate::selection::FilterCheck;
+use crate::zone_strategy::ZoneStrategy;
+use once_cell::sync::Lazy;
+use std::sync::Arc;
+
+/// One exchange call a strategy asks for; `decide` runs them in order.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StrategyAction {
+    Buy,
+    Sell,
+    ClosePositions,
+    SetLeverage(u32),
+}
+
+/// Everything that makes a trading strategy: how candles become a `Trade`,
+/// how its status moves, which symbol to enter and what to do about it.
+/// Chains, the ledger, orders and protection are shared by all strategies.
+pub trait Strategy: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Snapshot of `symbol` built from its candles and the reference
+    /// symbol's, with no status yet. Fails when the candles are too few or
+    /// unreadable.
+    fn analyse(&self, symbol: &str, candles: &[Candlestick], reference: &[Candlestick]) -> Result<Trade, String>;
+
+    /// Symbol whose candles `analyse` gets as `reference` for `symbol`;
+    /// may be `symbol` itself.
+    fn reference_symbol(&self, symbol: &str) -> String;
+
+    /// Status of `trade` given the status blocks recorded for the symbol,
+    /// oldest first (never empty).
+    fn advance(&self, trade: Trade, history: &[Trade]) -> Trade;
+
+    /// Checks a symbol must pass to open a chain.
+    fn candidate_filters(&self, trade: &Trade) -> Vec<FilterCheck>;
+
+    /// Factors of the candidate score, higher is better, named as in
+    /// `factor_names` and weighted by `[strategy.selection.weights]`.
+    fn candidate_factors(&self, trade: &Trade) -> Vec<(&'static str, f64)>;
+
+    fn factor_names(&self) -> &'static [&'static str];
+
+    /// Exchange actions for the newest block of a chain.
+    fn actions(&self, trade: &Trade) -> Vec<StrategyAction>;
+
+    /// Why the chain of `trade` is over, if it is. `statuses` are the
+    /// statuses recorded in the chain so far, oldest first.
+    fn close_reason(&self, trade: &Trade, statuses: &[Option<TradeStatus>]) -> Option<CloseReason>;
+
+    /// Graphviz DOT of the status transitions, actions and exits.
+    fn graph(&self) -> String;
+}
+
+/// Strategy registered under `name` in `[strategy]`, with its settings
+/// checked against the candle window fetched per symbol.
+pub fn build_strategy(settings: &Settings) -> Result<Arc<dyn Strategy>, String> {
+    match settings.strategy.name.as_str() {
+        "zone" => {
+            let zone = &settings.strategy.zone;
+            if zone.count == 0 {
+                return Err("strategy.zone.count deve ser ao menos 1".into());
+            }
+            if zone.lookback < 2 {
+                return Err("strategy.zone.lookback deve ser ao menos 2".into());
+            }
+            if zone.spacing == ZoneSpacing::Atr && (zone.atr_period == 0 || zone.atr_period >= zone.lookback) {
+                return Err(format!(
+                    "strategy.zone.atr_period deve estar entre 1 e {} (lookback - 1)",
+                    zone.lookback - 1
+                ));
+            }
+            let bias = &settings.strategy.bias;
+            let symbols = std::iter::once(None).chain(bias.symbols.keys().map(Some));
+            for symbol in symbols {
+                let rule = symbol.map(|s| bias.rule_for(s)).unwrap_or_else(|| bias.default.clone());
+                let label = symbol.map(|s| format!("strategy.bias.symbols.{}", s)).unwrap_or("strategy.bias".into());
+                if rule.cma_period == 0 || rule.oma_period == 0 {
+                    return Err(format!("{}: cma_period e oma_period devem ser ao menos 1", label));
+                }
+                if (settings.binance.limit as usize) < required_candles(zone, &rule) {
+                    return Err(format!(
+                        "{}: binance.limit ({}) menor que os {} candles exigidos",
+                        label,
+                        settings.binance.limit,
+                        required_candles(zone, &rule)
+                    ));
+                }
+            }
+            let machine = StateMachine::load(&zone.machine, settings.strategy.evaluation, zone.count)?;
+            let strategy: Arc<dyn Strategy> =
+                Arc::new(ZoneStrategy { settings: zone.clone(), bias: bias.clone(), machine });
+            check_weights(strategy.as_ref(), settings)?;
+            Ok(strategy)
+        }
+        other => Err(format!("Estrategia desconhecida '{}' (disponiveis: zone)", other)),
+    }
+}
+
+fn check_weights(strategy: &dyn Strategy, settings: &Settings) -> Result<(), String> {
+    for (name, weight) in &settings.strategy.selection.weights {
+        if !strategy.factor_names().contains(&name.as_str()) {
+            return Err(format!(
+                "strategy.selection.weights.{}: fator desconhecido (disponiveis: {})",
+                name,
+                strategy.factor_names().join(", ")
+            ));
+        }
+        if !weight.is_finite() {
+            return Err(format!("strategy.selection.weights.{}: peso invalido", name));
+        }
+    }
+    Ok(())
+}
+
+static STRATEGY: Lazy<Arc<dyn Strategy>> = Lazy::new(|| {
+    let settings = Settings::load();
+    build_strategy(&settings).expect("Failed to load strategy")
+});
+
+pub fn get_strategy() -> Arc<dyn Strategy> {
+    STRATEGY.clone()
+}
+
+static EVALUATION: Lazy<EvaluationMode> = Lazy::new(|| Settings::load().strategy.evaluation);
+
+pub fn get_evaluation_mode() -> EvaluationMode {
+    *EVALUATION
+}
+
+/// Analyses `symbol` and advances it from its chain, if it has one.
+/// `history` holds the trades of its status blocks, oldest first.
+pub fn evaluate(
+    strategy: &dyn Strategy,
+    symbol: &str,
+    candles: &[Candlestick],
+    reference: &[Candlestick],
+    history: &[Trade],
+) -> Result<Trade, String> {
+    let trade = strategy.analyse(symbol, candles, reference)?;
+    Ok(if history.is_empty() { trade } else { strategy.advance(trade, history) })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dto::Bias;
+
+    const HOUR: u64 = 3_600_000;
+
+    fn candle(index: usize, close: f64) -> Candlestick {
+        let open_time = index as u64 * HOUR;
+        Candlestick {
+            open_time,
+            open_price: close.to_string(),
+            high_price: (close + 1.0).to_string(),
+            low_price: (close - 1.0).to_string(),
+            close_price: close.to_string(),
+            volume: "10".to_string(),
+            close_time: open_time + HOUR - 1,
+            quote_asset_volume: "500".to_string(),
+            number_of_trades: 5,
+            taker_buy_base_asset_volume: "5".to_string(),
+            taker_buy_quote_asset_volume: "250".to_string(),
+            ignore: "0".to_string(),
+        }
+    }
+
+    #[test]
+    fn zone_count_the_machine_cannot_use_is_rejected() {
+        let mut settings = Settings::load();
+        assert!(build_strategy(&settings).is_ok());
+
+        settings.strategy.zone.count = 0;
+        assert_eq!(build_strategy(&settings).err().unwrap(), "strategy.zone.count deve ser ao menos 1");
+
+        // A single level puts zone_1 and zone_7 on the same price, so the
+        // entries out of None overlap.
+        settings.strategy.zone.count = 1;
+        let error = build_strategy(&settings).err().unwrap();
+        assert!(error.starts_with("Maquina de estados invalida com strategy.zone.count = 1"), "{}", error);
+    }
+
+    #[test]
+    fn five_zone_variant_builds_trades_and_moves_through_the_machine() {
+        let mut settings = Settings::load();
+        settings.strategy.zone.count = 5;
+        settings.strategy.zone.lookback = 100;
+        settings.strategy.zone.spacing = ZoneSpacing::Log;
+        let strategy = build_strategy(&settings).unwrap();
+
+        let required = required_candles(&settings.strategy.zone, &settings.strategy.bias.default);
+        // A rising reference puts the recent average above the shifted one.
+        let reference: Vec<Candlestick> = (0..required).map(|i| candle(i, 100.0 + i as f64)).collect();
+        let ranging = |last: f64| -> Vec<Candlestick> {
+            let mut candles: Vec<Candlestick> =
+                (0..required).map(|i| candle(i, if i % 2 == 0 { 45.0 } else { 55.0 })).collect();
+            candles[required - 2] = candle(required - 2, 59.0);
+            candles[required - 1] = candle(required - 1, last);
+            candles
+        };
+
+        // Range 44..60: five log levels at about 46.3, 48.8, 51.4, 54.1 and 57.0.
+        let top = strategy.analyse("ETHUSDT", &ranging(59.0), &reference).unwrap();
+        assert_eq!(top.bias, Bias::Bullish);
+        assert_eq!(top.zone_2, top.zone_3);
+        assert_eq!(top.zone_6, top.zone_7);
+        assert!(top.zone_1 < top.zone_2 && top.zone_3 < top.zone_4 && top.zone_5 < top.zone_6);
+
+        let mut history = vec![strategy.analyse("ETHUSDT", &ranging(50.0), &reference).unwrap()];
+        let entered = strategy.advance(top, &history);
+        assert_eq!(entered.status, Some(TradeStatus::InZone7));
+
+        history.push(entered);
+        let fallen = strategy.analyse("ETHUSDT", &ranging(53.0), &reference).unwrap();
+        assert_eq!(strategy.advance(fallen, &history).status, Some(TradeStatus::OutZone5));
+    }
+}
@@ -0,0 +1,393 @@
+use crate::candle_store::interval_ms;
+use crate::config::{Settings, StreamTrigger};
+use crate::dto::Candlestick;
+use crate::exchange::Exchange;
+use crate::strategy::get_strategy;
+use crate::universe::{get_universe, watched_symbols};
+use futures::future::join_all;
+use futures::{SinkExt, StreamExt};
+use once_cell::sync::Lazy;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::Notify;
+use tokio::task::JoinHandle;
+use tokio_tungstenite::{connect_async, tungstenite::Message};
+
+const IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+/// How long a connection runs before retrying symbols that failed to seed.
+const RESEED_INTERVAL: Duration = Duration::from_secs(300);
+
+static MARKET_DATA: Lazy<Arc<MarketData>> = Lazy::new(|| Arc::new(MarketData::new()));
+
+/// Rolling candle windows per symbol, seeded from REST and kept current by
+/// the kline stream.
+pub struct MarketData {
+    windows: Mutex<HashMap<String, Vec<Candlestick>>>,
+    updated: Notify,
+}
+
+enum Applied {
+    Updated,
+    Closed,
+    Gap,
+    Ignored,
+}
+
+impl MarketData {
+    fn new() -> Self {
+        MarketData {
+            windows: Mutex::new(HashMap::new()),
+            updated: Notify::new(),
+        }
+    }
+
+    pub fn window(&self, symbol: &str) -> Option<Vec<Candlestick>> {
+        self.windows.lock().unwrap().get(symbol).cloned()
+    }
+
+    /// Resolves after the next update that matches the configured trigger.
+    pub async fn updated(&self) {
+        self.updated.notified().await;
+    }
+
+    fn replace(&self, symbol: &str, candles: Vec<Candlestick>) {
+        self.windows.lock().unwrap().insert(symbol.to_string(), candles);
+    }
+
+    fn apply(&self, symbol: &str, candle: Candlestick, closed: bool, step: u64, limit: usize) -> Applied {
+        let mut windows = self.windows.lock().unwrap();
+        let Some(window) = windows.get_mut(symbol) else {
+            return Applied::Gap;
+        };
+        let Some(last) = window.last_mut() else {
+            return Applied::Gap;
+        };
+
+        if candle.open_time == last.open_time {
+            *last = candle;
+        } else if candle.open_time == last.open_time + step {
+            window.push(candle);
+            let excess = window.len().saturating_sub(limit);
+            window.drain(..excess);
+        } else if candle.open_time > last.open_time {
+            return Applied::Gap;
+        } else {
+            return Applied::Ignored;
+        }
+
+        if closed { Applied::Closed } else { Applied::Updated }
+    }
+}
+
+pub fn get_market_data() -> Arc<MarketData> {
+    MARKET_DATA.clone()
+}
+
+#[derive(Debug, Deserialize)]
+struct StreamEnvelope {
+    data: KlineEvent,
+}
+
+#[derive(Debug, Deserialize)]
+struct KlineEvent {
+    #[serde(rename = "s")]
+    symbol: String,
+    #[serde(rename = "k")]
+    kline: Kline,
+}
+
+#[derive(Debug, Deserialize)]
+struct Kline {
+    #[serde(rename = "t")]
+    open_time: u64,
+    #[serde(rename = "T")]
+    close_time: u64,
+    #[serde(rename = "o")]
+    open: String,
+    #[serde(rename = "c")]
+    close: String,
+    #[serde(rename = "h")]
+    high: String,
+    #[serde(rename = "l")]
+    low: String,
+    #[serde(rename = "v")]
+    volume: String,
+    #[serde(rename = "n")]
+    trades: u64,
+    #[serde(rename = "x")]
+    closed: bool,
+    #[serde(rename = "q")]
+    quote_volume: String,
+    #[serde(rename = "V")]
+    taker_buy_base: String,
+    #[serde(rename = "Q")]
+    taker_buy_quote: String,
+}
+
+impl From<Kline> for Candlestick {
+    fn from(k: Kline) -> Self {
+        Candlestick {
+            open_time: k.open_time,
+            open_price: k.open,
+            high_price: k.high,
+            low_price: k.low,
+            close_price: k.close,
+            volume: k.volume,
+            close_time: k.close_time,
+            quote_asset_volume: k.quote_volume,
+            number_of_trades: k.trades,
+            taker_buy_base_asset_volume: k.taker_buy_base,
+            taker_buy_quote_asset_volume: k.taker_buy_quote,
+            ignore: "0".to_string(),
+        }
+    }
+}
+
+/// Keeps `MarketData` current for every watched symbol plus their bias
+/// references. Windows are re-seeded from REST on every (re)connect and
+/// whenever a symbol skips a candle; a change of the universe reconnects
+/// with the new symbols.
+pub fn start_stream(exchange: Arc<dyn Exchange>, settings: Settings) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let market = get_market_data();
+        let strategy = get_strategy();
+        let mut backoff = Duration::from_secs(1);
+
+        loop {
+            let generation = get_universe().generation();
+            let watched = watched_symbols();
+            let mut symbols = watched.clone();
+            symbols.extend(watched.iter().map(|s| strategy.reference_symbol(s)));
+            symbols.sort();
+            symbols.dedup();
+
+            match seed_all(&market, exchange.as_ref(), &settings, &symbols).await {
+                Ok(seeded) => {
+                    let reseed = (seeded.len() < symbols.len()).then_some(RESEED_INTERVAL);
+                    match run_connection(&market, exchange.as_ref(), &settings, &seeded, generation, reseed).await {
+                        Ok(()) => {
+                            println!("Kline stream closed, reconnecting...");
+                            backoff = Duration::from_secs(1);
+                        }
+                        Err(e) => eprintln!("Kline stream error: {}", e),
+                    }
+                }
+                Err(e) => eprintln!("Erro ao carregar candles iniciais: {}", e),
+            }
+
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(MAX_BACKOFF);
+        }
+    })
+}
+
+/// One candle more than `limit`, so `MarketSnapshot` can align the windows
+/// on a candle rollover without leaving any of them short.
+fn window_size(settings: &Settings) -> u32 {
+    settings.binance.limit + 1
+}
+
+/// Seeds every window it can and returns the symbols that were seeded. A
+/// symbol that fails is reported and left for the next reconnect; only
+/// failing them all is an error.
+async fn seed_all(
+    market: &MarketData,
+    exchange: &dyn Exchange,
+    settings: &Settings,
+    symbols: &[String],
+) -> Result<Vec<String>, String> {
+    let interval = &settings.binance.interval;
+    let limit = window_size(settings);
+
+    let results = join_all(symbols.iter().map(|symbol| exchange.get_candlesticks(symbol, interval, limit))).await;
+
+    let mut seeded = Vec::new();
+    let mut errors = Vec::new();
+    for (symbol, result) in symbols.iter().zip(results) {
+        match result {
+            Ok(candles) => {
+                market.replace(symbol, candles);
+                seeded.push(symbol.clone());
+            }
+            Err(e) => {
+                eprintln!("Erro ao carregar candles iniciais de {}, fora do stream ate reconectar: {}", symbol, e);
+                errors.push(format!("{}: {}", symbol, e));
+            }
+        }
+    }
+
+    if seeded.is_empty() && !errors.is_empty() {
+        return Err(errors.join("; "));
+    }
+    Ok(seeded)
+}
+
+/// Runs one combined-stream connection until it closes, errors, goes idle,
+/// the universe moves past `generation` or `reseed` has passed.
+async fn run_connection(
+    market: &MarketData,
+    exchange: &dyn Exchange,
+    settings: &Settings,
+    symbols: &[String],
+    generation: u64,
+    reseed: Option<Duration>,
+) -> Result<(), String> {
+    let interval = &settings.binance.interval;
+    let step = interval_ms(interval).ok_or_else(|| format!("Unsupported interval '{}'", interval))?;
+
+    let streams: Vec<String> = symbols
+        .iter()
+        .map(|s| format!("{}@kline_{}", s.to_lowercase(), interval))
+        .collect();
+    let url = format!("{}?streams={}", settings.stream.url, streams.join("/"));
+
+    let (mut socket, _) = connect_async(&url).await.map_err(|e| e.to_string())?;
+    println!("Kline stream connected ({} symbols)", symbols.len());
+    let connected_at = std::time::Instant::now();
+
+    loop {
+        if get_universe().generation() != generation {
+            println!("Universo de simbolos alterado, reconectando o kline stream");
+            return Ok(());
+        }
+        if reseed.is_some_and(|after| connected_at.elapsed() >= after) {
+            println!("Reconectando o kline stream para tentar de novo os simbolos sem candles");
+            return Ok(());
+        }
+
+        let message = match tokio::time::timeout(IDLE_TIMEOUT, socket.next()).await {
+            Ok(Some(message)) => message.map_err(|e| e.to_string())?,
+            Ok(None) => return Ok(()),
+            Err(_) => return Err(format!("no data for {}s", IDLE_TIMEOUT.as_secs())),
+        };
+
+        let text = match message {
+            Message::Text(text) => text,
+            Message::Ping(payload) => {
+                socket.send(Message::Pong(payload)).await.map_err(|e| e.to_string())?;
+                continue;
+            }
+            Message::Close(_) => return Ok(()),
+            _ => continue,
+        };
+
+        let event = match serde_json::from_str::<StreamEnvelope>(&text) {
+            Ok(envelope) => envelope.data,
+            Err(e) => {
+                eprintln!("Ignoring kline message: {}", e);
+                continue;
+            }
+        };
+
+        if handle_kline(market, exchange, settings, event, step).await {
+            market.updated.notify_one();
+        }
+    }
+}
+
+/// Applies one streamed kline to its window and hands it to the exchange.
+/// Returns whether evaluation should run for it.
+async fn handle_kline(
+    market: &MarketData,
+    exchange: &dyn Exchange,
+    settings: &Settings,
+    event: KlineEvent,
+    step: u64,
+) -> bool {
+    let interval = &settings.binance.interval;
+    let limit = window_size(settings);
+    let symbol = event.symbol;
+    let closed = event.kline.closed;
+    let candle: Candlestick = event.kline.into();
+
+    match market.apply(&symbol, candle.clone(), closed, step, limit as usize) {
+        Applied::Updated => {
+            exchange.on_candle(&symbol, &candle);
+            settings.stream.trigger == StreamTrigger::Update
+        }
+        Applied::Closed => {
+            exchange.on_candle(&symbol, &candle);
+            true
+        }
+        Applied::Gap => {
+            println!("Gap in {} stream, resyncing from REST", symbol);
+            match exchange.get_candlesticks(&symbol, interval, limit).await {
+                Ok(candles) => {
+                    market.replace(&symbol, candles);
+                    true
+                }
+                Err(e) => {
+                    eprintln!("Erro ao ressincronizar {}: {}", symbol, e);
+                    false
+                }
+            }
+        }
+        Applied::Ignored => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backtest::HistoricalMarket;
+    use crate::paper::PaperExchange;
+
+    const HOUR: u64 = 3_600_000;
+
+    fn candle(index: u64, close: f64) -> Candlestick {
+        Candlestick {
+            open_time: index * HOUR,
+            open_price: close.to_string(),
+            high_price: close.to_string(),
+            low_price: close.to_string(),
+            close_price: close.to_string(),
+            volume: "1".to_string(),
+            close_time: (index + 1) * HOUR - 1,
+            quote_asset_volume: close.to_string(),
+            number_of_trades: 1,
+            taker_buy_base_asset_volume: "0".to_string(),
+            taker_buy_quote_asset_volume: "0".to_string(),
+            ignore: "0".to_string(),
+        }
+    }
+
+    fn kline(index: u64, low: f64, close: f64, closed: bool) -> KlineEvent {
+        let text = serde_json::json!({"data": {"s": "ETHUSDT", "k": {
+            "t": index * HOUR, "T": (index + 1) * HOUR - 1, "o": "100", "c": close.to_string(),
+            "h": "100", "l": low.to_string(), "v": "1", "n": 1, "x": closed, "q": "100", "V": "0", "Q": "0",
+        }}})
+        .to_string();
+        serde_json::from_str::<StreamEnvelope>(&text).unwrap().data
+    }
+
+    #[tokio::test]
+    async fn streamed_candle_fires_the_paper_stop() {
+        let mut settings = Settings::load();
+        settings.binance.interval = "1h".to_string();
+        let history: Vec<Candlestick> = (0..10).map(|i| candle(i, 100.0)).collect();
+        let market = Arc::new(HistoricalMarket::new(HashMap::from([("ETHUSDT".to_string(), history)]), 0.001, 5.0));
+        market.set_time(10 * HOUR);
+        let paper = PaperExchange::in_memory(market, &settings);
+
+        let windows = MarketData::new();
+        let seed = paper.get_candlesticks("ETHUSDT", "1h", window_size(&settings)).await.unwrap();
+        windows.replace("ETHUSDT", seed);
+        paper.execute_future_order("BUY", "ETHUSDT").await.unwrap();
+        paper.place_protective_order("ETHUSDT", "SELL", "STOP_MARKET", 95.0).await.unwrap();
+
+        // Forming candle above the stop: repriced, nothing fires.
+        handle_kline(&windows, &paper, &settings, kline(10, 97.0, 98.0, false), HOUR).await;
+        assert_eq!(paper.get_current_price("ETHUSDT").await.unwrap(), 98.0);
+        assert_eq!(paper.get_positions().await.unwrap().len(), 1);
+
+        // The same candle trades through 95.
+        handle_kline(&windows, &paper, &settings, kline(10, 94.0, 96.0, true), HOUR).await;
+        assert!(paper.get_positions().await.unwrap().is_empty());
+        let exit = paper.fills().pop().unwrap();
+        assert_eq!(exit.side, "SELL");
+        assert!(exit.price < 95.0 && exit.price > 94.9, "{}", exit.price);
+    }
+}
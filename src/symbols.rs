@@ -0,0 +1,173 @@
+use crate::binance::get_exchange_info;
+use crate::config::BinanceSettings;
+use crate::dto::{SymbolFilter, SymbolInfo};
+use once_cell::sync::Lazy;
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Unknown symbols trigger a reload, but not more often than this.
+const MIN_RELOAD_INTERVAL: Duration = Duration::from_secs(60);
+
+static SYMBOL_REGISTRY: Lazy<Arc<SymbolRegistry>> = Lazy::new(|| Arc::new(SymbolRegistry::new()));
+
+/// Trading rules of one futures contract, taken from `/exchangeInfo`.
+/// Sizes that Binance leaves out are zero, which disables that check.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SymbolMeta {
+    pub symbol: String,
+    pub status: String,
+    pub price_precision: u32,
+    pub quantity_precision: u32,
+    pub tick_size: Decimal,
+    pub min_price: Decimal,
+    pub max_price: Decimal,
+    pub step_size: Decimal,
+    pub min_qty: Decimal,
+    pub max_qty: Decimal,
+    pub market_step_size: Decimal,
+    pub market_min_qty: Decimal,
+    pub market_max_qty: Decimal,
+    pub min_notional: Decimal,
+    pub multiplier_up: Decimal,
+    pub multiplier_down: Decimal,
+    /// Listing time in ms, zero when unknown.
+    pub onboard_date: u64,
+}
+
+impl SymbolMeta {
+    pub fn from_info(info: &SymbolInfo) -> Result<Self, String> {
+        let mut meta = SymbolMeta {
+            symbol: info.symbol.clone(),
+            status: info.status.clone(),
+            price_precision: info.price_precision,
+            quantity_precision: info.quantity_precision,
+            onboard_date: info.onboard_date,
+            ..Default::default()
+        };
+        let mut market_lot = None;
+
+        for filter in &info.filters {
+            match filter {
+                SymbolFilter::LotSize { step_size, min_qty, max_qty } => {
+                    meta.step_size = decimal(step_size)?;
+                    meta.min_qty = decimal(min_qty)?;
+                    meta.max_qty = decimal(max_qty)?;
+                }
+                SymbolFilter::MarketLotSize { step_size, min_qty, max_qty } => {
+                    market_lot = Some((decimal(step_size)?, decimal(min_qty)?, decimal(max_qty)?));
+                }
+                SymbolFilter::PriceFilter { tick_size, min_price, max_price } => {
+                    meta.tick_size = decimal(tick_size)?;
+                    meta.min_price = decimal(min_price)?;
+                    meta.max_price = decimal(max_price)?;
+                }
+                SymbolFilter::MinNotional { notional } => meta.min_notional = decimal(notional)?,
+                SymbolFilter::PercentPrice { multiplier_up, multiplier_down } => {
+                    meta.multiplier_up = decimal(multiplier_up)?;
+                    meta.multiplier_down = decimal(multiplier_down)?;
+                }
+                SymbolFilter::Other => {}
+            }
+        }
+
+        if meta.step_size.is_zero() {
+            return Err(format!("Filtro LOT_SIZE nao encontrado para {}", info.symbol));
+        }
+
+        // Market orders follow MARKET_LOT_SIZE when Binance sends it.
+        (meta.market_step_size, meta.market_min_qty, meta.market_max_qty) =
+            market_lot.unwrap_or((meta.step_size, meta.min_qty, meta.max_qty));
+
+        Ok(meta)
+    }
+
+    pub fn is_trading(&self) -> bool {
+        self.status.is_empty() || self.status == "TRADING"
+    }
+}
+
+fn decimal(value: &str) -> Result<Decimal, String> {
+    Decimal::from_str(value).map_err(|e| format!("Invalid decimal '{}': {}", value, e))
+}
+
+/// Cache of `SymbolMeta` for every contract, loaded with a single
+/// `/exchangeInfo` call and refreshed after `symbols_refresh_secs`.
+pub struct SymbolRegistry {
+    symbols: Mutex<HashMap<String, SymbolMeta>>,
+    loaded_at: Mutex<Option<Instant>>,
+    reload: tokio::sync::Mutex<()>,
+}
+
+impl SymbolRegistry {
+    fn new() -> Self {
+        SymbolRegistry {
+            symbols: Mutex::new(HashMap::new()),
+            loaded_at: Mutex::new(None),
+            reload: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    pub async fn get(&self, settings: &BinanceSettings, symbol: &str) -> Result<SymbolMeta, String> {
+        let refresh = Duration::from_secs(settings.symbols_refresh_secs);
+
+        if let Some(meta) = self.cached(symbol)
+            && !self.older_than(refresh)
+        {
+            return Ok(meta);
+        }
+
+        // Only one caller reloads; the rest wait and read the fresh copy.
+        let _guard = self.reload.lock().await;
+        let missing = self.cached(symbol).is_none();
+
+        if (self.older_than(refresh) || (missing && self.older_than(MIN_RELOAD_INTERVAL)))
+            && let Err(e) = self.load(settings).await
+        {
+            // Stale rules beat no rules at all.
+            match self.cached(symbol) {
+                Some(meta) => {
+                    eprintln!("Erro ao atualizar exchangeInfo, usando cache: {}", e);
+                    return Ok(meta);
+                }
+                None => return Err(e),
+            }
+        }
+
+        self.cached(symbol)
+            .ok_or_else(|| format!("Simbolo {} nao encontrado no exchangeInfo", symbol))
+    }
+
+    fn cached(&self, symbol: &str) -> Option<SymbolMeta> {
+        self.symbols.lock().unwrap().get(symbol).cloned()
+    }
+
+    fn older_than(&self, age: Duration) -> bool {
+        self.loaded_at.lock().unwrap().is_none_or(|at| at.elapsed() >= age)
+    }
+
+    async fn load(&self, settings: &BinanceSettings) -> Result<(), String> {
+        let info = get_exchange_info(settings).await?;
+
+        let mut symbols = HashMap::new();
+        for symbol in &info.symbols {
+            match SymbolMeta::from_info(symbol) {
+                Ok(meta) => {
+                    symbols.insert(meta.symbol.clone(), meta);
+                }
+                Err(e) => eprintln!("Ignorando {}: {}", symbol.symbol, e),
+            }
+        }
+
+        *self.symbols.lock().unwrap() = symbols;
+        *self.loaded_at.lock().unwrap() = Some(Instant::now());
+        Ok(())
+    }
+}
+
+pub fn get_symbol_registry() -> Arc<SymbolRegistry> {
+    SYMBOL_REGISTRY.clone()
+}
@@ -1,43 +1,34 @@
-use crate::blockchain::get_last_trade_for;
-use crate::dto::{Bias, Candlestick, Trade};
-use crate::status_trade::update_status;
-use chrono::{Local, Timelike};
+use crate::config::{BiasRule, MovingAverage, ZoneSettings, ZoneSpacing};
+use crate::dto::{Bias, Candlestick, Num, Trade};
+use chrono::{Local, TimeZone, Timelike};
+
+/// Candles needed per symbol (and for its reference) before zones and bias
+/// are computed.
+pub fn required_candles(zone: &ZoneSettings, bias: &BiasRule) -> usize {
+    zone.lookback.max(bias.required_candles())
+}
 
-pub fn generate_trade(symbol: String, candlesticks: Vec<Candlestick>, reference_candles: Vec<Candlestick>) -> Trade {
+pub fn generate_trade(
+    symbol: String,
+    candlesticks: &[Candlestick],
+    reference_candles: &[Candlestick],
+    zone: &ZoneSettings,
+    bias_rule: &BiasRule,
+) -> Result<Trade, String> {
     let of = candlesticks.len();
     let reference_of = reference_candles.len();
+    let required = required_candles(zone, bias_rule);
+
+    if of < required || reference_of < required {
+        return Err(format!("{} candles e {} da referencia, {} exigidos", of, reference_of, required));
+    }
+    for candle in candlesticks.iter().chain(reference_candles) {
+        check_candle(candle)?;
+    }
 
-    if of < 271 || reference_of < 271 {
-        return Trade {
-            symbol,
-            current_price: "0.0".into(),
-            cma: "0.0".into(),
-            oma: "0.0".into(),
-            bias: Bias::None,
-            status: None,
-            zone_max: "0.0".into(),
-            zone_7: "0.0".into(),
-            zone_6: "0.0".into(),
-            zone_5: "0.0".into(),
-            zone_4: "0.0".into(),
-            zone_3: "0.0".into(),
-            zone_2: "0.0".into(),
-            zone_1: "0.0".into(),
-            zone_min: "0.0".into(),
-            of,
-            performance_24: "0.0".into(),
-            amplitude_ma_200: "0.0".into(),
-            performance_btc_24: "0.0".into(),
-            volume: "0.0".into(),
-            quote_asset_volume: "0.0".into(),
-            number_of_trades: "0.0".into(),
-            taker_buy_base_asset_volume: "0.0".into(),
-            taker_buy_quote_asset_volume: "0.0".into(),
-        };
-    }
-
-    let cma_valor = calculate_moving_average(&reference_candles[71..]);
-    let oma_valor = calculate_moving_average(&reference_candles[..200]);
+    let oma_end = reference_of - bias_rule.oma_shift;
+    let cma_valor = bias_average(&reference_candles[reference_of - bias_rule.cma_period..], bias_rule.ma);
+    let oma_valor = bias_average(&reference_candles[oma_end - bias_rule.oma_period..oma_end], bias_rule.ma);
 
     let bias = if cma_valor > oma_valor {
         Bias::Bullish
@@ -47,7 +38,7 @@ pub fn generate_trade(symbol: String, candlesticks: Vec<Candlestick>, reference_
         Bias::None
     };
 
-    let analysis_slice = &candlesticks[71..];
+    let analysis_slice = &candlesticks[of - zone.lookback..];
 
     let max_high = analysis_slice
         .iter()
@@ -59,76 +50,152 @@ pub fn generate_trade(symbol: String, candlesticks: Vec<Candlestick>, reference_
         .filter_map(|c| c.low_price.parse::<f64>().ok())
         .fold(f64::MAX, f64::min);
 
-    let current_price = analysis_slice
-        .iter()
-        .max_by_key(|c| c.close_time)
+    let newest = analysis_slice.iter().max_by_key(|c| c.close_time);
+    let current_price = newest
         .map(|c| c.close_price.clone())
         .unwrap_or_else(|| "0.0".to_string());
 
-    let (volume, quote_asset_volume, number_of_trades, taker_buy_base_asset_volume, taker_buy_quote_asset_volume) =
-    match candlesticks.last() {
-        Some(candle) => (
-            candle.volume.clone(),
-            candle.quote_asset_volume.clone(),
-            candle.number_of_trades.to_string(),
-            candle.taker_buy_base_asset_volume.clone(),
-            candle.taker_buy_quote_asset_volume.clone(),
-        ),
-        None => (
-            "0.0".into(),
-            "0.0".into(),
-            "0".into(),
-            "0.0".into(),
-            "0.0".into(),
-        ),
-    };
+    let last = &candlesticks[of - 1];
+
+    let levels = zone_levels(analysis_slice, min_low, max_high, zone);
+    let level = |role: usize| Num::from_f64(role_level(&levels, role, zone.count), 8);
+
+    let price = current_price.parse::<f64>().unwrap_or(0.0);
+    let atr = average_true_range(candlesticks, zone.atr_period);
+    let atr_pct = if price > 0.0 { atr / price * 100.0 } else { 0.0 };
 
-    let log_min = min_low.ln();
-    let log_max = max_high.ln();
-    let log_zone_4 = (log_min + log_max) / 2.0;
-    let log_zone_2 = (log_min + log_zone_4) / 2.0;
-    let log_zone_6 = (log_max + log_zone_4) / 2.0;
-    let log_zone_3 = (log_zone_2 + log_zone_4) / 2.0;
-    let log_zone_5 = (log_zone_6 + log_zone_4) / 2.0;
-    let log_zone_1 = (log_min + log_zone_2) / 2.0;
-    let log_zone_7 = (log_max + log_zone_6) / 2.0;
-
-    let performance_24_val = calculate_performance_24(&candlesticks);
-    let performance_24 = format!("{:.2}", performance_24_val);
-    let amplitude_ma_200 = calculate_amplitude_ma_200(&candlesticks, &current_price);
-    let performance_btc_24 = calculate_performance_btc_24(&reference_candles, performance_24_val);
+    let performance_24_val = calculate_performance_24(candlesticks);
+    let performance_24 = Num::from_f64(performance_24_val, 2)?;
+    let amplitude_ma_200 = Num::parse(&calculate_amplitude_ma_200(candlesticks, &current_price))?;
+    let performance_btc_24 = Num::parse(&calculate_performance_btc_24(reference_candles, performance_24_val))?;
 
     let trade = Trade {
-        symbol: symbol.clone(),
-        current_price: current_price.clone(),
-        cma: format!("{:.8}", cma_valor),
-        oma: format!("{:.8}", oma_valor),
+        symbol,
+        current_price: Num::parse(&current_price)?,
+        cma: Num::from_f64(cma_valor, 8)?,
+        oma: Num::from_f64(oma_valor, 8)?,
         bias,
         status: None,
-        zone_max: format!("{:.8}", max_high),
-        zone_7: format!("{:.8}", log_zone_7.exp()),
-        zone_6: format!("{:.8}", log_zone_6.exp()),
-        zone_5: format!("{:.8}", log_zone_5.exp()),
-        zone_4: format!("{:.8}", log_zone_4.exp()),
-        zone_3: format!("{:.8}", log_zone_3.exp()),
-        zone_2: format!("{:.8}", log_zone_2.exp()),
-        zone_1: format!("{:.8}", log_zone_1.exp()),
-        zone_min: format!("{:.8}", min_low),
+        zone_max: Num::from_f64(max_high, 8)?,
+        zone_7: level(7)?,
+        zone_6: level(6)?,
+        zone_5: level(5)?,
+        zone_4: level(4)?,
+        zone_3: level(3)?,
+        zone_2: level(2)?,
+        zone_1: level(1)?,
+        zone_min: Num::from_f64(min_low, 8)?,
         of,
         performance_24,
         performance_btc_24,
         amplitude_ma_200,
-        volume,
-        quote_asset_volume,
-        number_of_trades,
-        taker_buy_base_asset_volume,
-        taker_buy_quote_asset_volume,
+        volume: Num::parse(&last.volume)?,
+        quote_asset_volume: Num::parse(&last.quote_asset_volume)?,
+        number_of_trades: Num::parse(&last.number_of_trades.to_string())?,
+        taker_buy_base_asset_volume: Num::parse(&last.taker_buy_base_asset_volume)?,
+        taker_buy_quote_asset_volume: Num::parse(&last.taker_buy_quote_asset_volume)?,
+        quote_volume_24h: Some(Num::from_f64(quote_volume_24h(candlesticks), 2)?),
+        atr_pct: Some(Num::from_f64(atr_pct, 4)?),
+        candle_time: newest.map(|c| c.open_time),
+        transition: None,
+        selection: None,
     };
+    check_level_order(&trade, zone.count)?;
+    Ok(trade)
+}
+
+/// The state machine's guards, and the overlap check in its `validate`,
+/// rely on zone_min < zone_1 < .. < zone_7 < zone_max, except for roles of
+/// the same `zone_rank`, which share a level. A flat range, or an ATR grid
+/// wider than the range, breaks that and yields no trade.
+fn check_level_order(trade: &Trade, count: usize) -> Result<(), String> {
+    let levels = [
+        &trade.zone_min,
+        &trade.zone_1,
+        &trade.zone_2,
+        &trade.zone_3,
+        &trade.zone_4,
+        &trade.zone_5,
+        &trade.zone_6,
+        &trade.zone_7,
+        &trade.zone_max,
+    ];
+    let ordered = (0..8).all(|role| zone_rank(role, count) == zone_rank(role + 1, count) || levels[role] < levels[role + 1]);
+    if ordered {
+        return Ok(());
+    }
+    let text: Vec<String> = levels.iter().map(|level| level.to_string()).collect();
+    Err(format!("niveis fora de ordem estrita (zone_min..zone_max): {}", text.join(" ")))
+}
+
+/// `count` ascending levels between `min` and `max`. Log and linear
+/// spacing divide the range evenly; ATR spacing steps `atr_multiplier`
+/// ATRs apart around the middle of the range.
+fn zone_levels(candles: &[Candlestick], min: f64, max: f64, zone: &ZoneSettings) -> Vec<f64> {
+    let n = zone.count;
+    let position = |k: usize| k as f64 / (n + 1) as f64;
+
+    match zone.spacing {
+        ZoneSpacing::Log => {
+            let (log_min, log_max) = (min.ln(), max.ln());
+            (1..=n).map(|k| (log_min + (log_max - log_min) * position(k)).exp()).collect()
+        }
+        ZoneSpacing::Linear => (1..=n).map(|k| min + (max - min) * position(k)).collect(),
+        ZoneSpacing::Atr => {
+            let step = average_true_range(candles, zone.atr_period) * zone.atr_multiplier;
+            let middle = (min + max) / 2.0;
+            let center = (n + 1) as f64 / 2.0;
+            (1..=n).map(|k| middle + (k as f64 - center) * step).collect()
+        }
+    }
+}
+
+/// Rank of `zone_{role}` among `count` levels: zone_min (role 0) is 0 and
+/// zone_max (role 8) is `count + 1`. zone_1..zone_7 take the level at the
+/// same relative height as in the default seven-level grid, so with fewer
+/// levels some roles share one.
+pub fn zone_rank(role: usize, count: usize) -> usize {
+    match role {
+        0 => 0,
+        1..=7 => ((role as f64 / 8.0 * (count + 1) as f64).round() as usize).clamp(1, count),
+        _ => count + 1,
+    }
+}
+
+/// Level backing `zone_{role}` (1..=7) of `Trade`.
+fn role_level(levels: &[f64], role: usize, count: usize) -> f64 {
+    levels[zone_rank(role, count) - 1]
+}
 
-    match get_last_trade_for(&symbol) {
-        Some(ref last) => update_status(trade, last),
-        None => trade,
+/// Simple average of the last `period` true ranges.
+fn average_true_range(candles: &[Candlestick], period: usize) -> f64 {
+    let price = |v: &str| v.parse::<f64>().unwrap_or(0.0);
+
+    let ranges: Vec<f64> = candles
+        .windows(2)
+        .map(|pair| {
+            let previous_close = price(&pair[0].close_price);
+            let high = price(&pair[1].high_price);
+            let low = price(&pair[1].low_price);
+            (high - low).max((high - previous_close).abs()).max((low - previous_close).abs())
+        })
+        .collect();
+
+    let recent = &ranges[ranges.len().saturating_sub(period)..];
+    if recent.is_empty() {
+        return 0.0;
     }
+    recent.iter().sum::<f64>() / recent.len() as f64
+}
+
+/// Quote volume of the candles closing within 24h of the newest one.
+fn quote_volume_24h(candles: &[Candlestick]) -> f64 {
+    let newest = candles.iter().map(|c| c.close_time).max().unwrap_or(0);
+    candles
+        .iter()
+        .filter(|c| c.close_time + 86_400_000 > newest)
+        .filter_map(|c| c.quote_asset_volume.parse::<f64>().ok())
+        .sum()
 }
 
 fn calculate_amplitude_ma_200(candles: &[Candlestick], current_price_str: &str) -> String {
@@ -154,7 +221,7 @@ fn calculate_performance_24(candles: &[Candlestick]) -> f64 {
         .parse::<f64>()
         .unwrap_or(0.0);
 
-    let hora_atual = Local::now().hour();
+    let hora_atual = candle_hour(candles.last().unwrap());
     let horas_ate_21h = (hora_atual + 24 - 21) % 24;
     let horas_ate_21h = horas_ate_21h as usize;
 
@@ -166,10 +233,10 @@ fn calculate_performance_24(candles: &[Candlestick]) -> f64 {
 
     let candle_21h = &candles[index_21h];
 
-    if let Ok(open_21h) = candle_21h.open_price.parse::<f64>() {
-        if open_21h != 0.0 {
-            return ((close_now / open_21h) - 1.0) * 100.0;
-        }
+    if let Ok(open_21h) = candle_21h.open_price.parse::<f64>()
+        && open_21h != 0.0
+    {
+        return ((close_now / open_21h) - 1.0) * 100.0;
     }
 
     0.0
@@ -185,7 +252,7 @@ fn calculate_performance_btc_24(candles: &[Candlestick], altcoin_perf_24: f64) -
         .parse::<f64>()
         .unwrap_or(0.0);
 
-    let hora_atual = Local::now().hour();
+    let hora_atual = candle_hour(candles.last().unwrap());
     let horas_ate_21h = (hora_atual + 24 - 21) % 24;
     let horas_ate_21h = horas_ate_21h as usize;
 
@@ -197,17 +264,52 @@ fn calculate_performance_btc_24(candles: &[Candlestick], altcoin_perf_24: f64) -
 
     let candle_21h = &candles[index_21h];
 
-    if let Ok(open_21h) = candle_21h.open_price.parse::<f64>() {
-        if open_21h != 0.0 && close_now != 0.0 {
-            let btc_perf_24 = ((close_now / open_21h) - 1.0) * 100.0;
-            let diff = altcoin_perf_24 - btc_perf_24;
-            return format!("{:.2}", diff);
-        }
+    if let Ok(open_21h) = candle_21h.open_price.parse::<f64>()
+        && open_21h != 0.0
+        && close_now != 0.0
+    {
+        let btc_perf_24 = ((close_now / open_21h) - 1.0) * 100.0;
+        let diff = altcoin_perf_24 - btc_perf_24;
+        return format!("{:.2}", diff);
     }
 
     "0.0".into()
 }
 
+/// Prices the analysis reads must be numbers; a garbled candle would
+/// otherwise count as zero.
+fn check_candle(candle: &Candlestick) -> Result<(), String> {
+    for price in [&candle.open_price, &candle.high_price, &candle.low_price, &candle.close_price] {
+        Num::parse(price)?;
+    }
+    Ok(())
+}
+
+/// Local hour of the candle, so the 21h anchor follows the data being
+/// evaluated (live or replayed) instead of the wall clock.
+fn candle_hour(candle: &Candlestick) -> u32 {
+    Local
+        .timestamp_millis_opt(candle.open_time as i64)
+        .single()
+        .map(|t| t.hour())
+        .unwrap_or_else(|| Local::now().hour())
+}
+
+/// SMA, or EMA seeded with the first close, over all of `candles`.
+fn bias_average(candles: &[Candlestick], ma: MovingAverage) -> f64 {
+    match ma {
+        MovingAverage::Sma => calculate_moving_average(candles),
+        MovingAverage::Ema => {
+            let alpha = 2.0 / (candles.len() as f64 + 1.0);
+            candles
+                .iter()
+                .filter_map(|c| c.close_price.parse::<f64>().ok())
+                .reduce(|ema, close| alpha * close + (1.0 - alpha) * ema)
+                .unwrap_or(0.0)
+        }
+    }
+}
+
 pub fn calculate_moving_average(candles: &[Candlestick]) -> f64 {
     let soma: f64 = candles
         .iter()
@@ -216,3 +318,83 @@ pub fn calculate_moving_average(candles: &[Candlestick]) -> f64 {
 
     soma / candles.len() as f64
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candle(close: f64) -> Candlestick {
+        Candlestick {
+            open_time: 0,
+            open_price: close.to_string(),
+            high_price: (close + 1.0).to_string(),
+            low_price: (close - 1.0).to_string(),
+            close_price: close.to_string(),
+            volume: "0".to_string(),
+            close_time: 0,
+            quote_asset_volume: "0".to_string(),
+            number_of_trades: 0,
+            taker_buy_base_asset_volume: "0".to_string(),
+            taker_buy_quote_asset_volume: "0".to_string(),
+            ignore: "0".to_string(),
+        }
+    }
+
+    fn zone(spacing: ZoneSpacing) -> ZoneSettings {
+        ZoneSettings { spacing, atr_multiplier: 1.5, ..ZoneSettings::default() }
+    }
+
+    fn assert_levels(actual: Vec<f64>, expected: &[f64]) {
+        assert_eq!(actual.len(), expected.len());
+        for (a, e) in actual.iter().zip(expected) {
+            assert!((a - e).abs() < 1e-9, "{:?} != {:?}", actual, expected);
+        }
+    }
+
+    #[test]
+    fn log_and_linear_levels_split_the_range_evenly() {
+        let log = zone_levels(&[], 1.0, 256.0, &zone(ZoneSpacing::Log));
+        assert_levels(log, &[2.0, 4.0, 8.0, 16.0, 32.0, 64.0, 128.0]);
+
+        let linear = zone_levels(&[], 0.0, 80.0, &zone(ZoneSpacing::Linear));
+        assert_levels(linear, &[10.0, 20.0, 30.0, 40.0, 50.0, 60.0, 70.0]);
+    }
+
+    #[test]
+    fn atr_levels_step_around_the_middle() {
+        // Every true range is 2, so the step is 2 * 1.5.
+        let candles: Vec<Candlestick> = (0..20).map(|_| candle(50.0)).collect();
+        let levels = zone_levels(&candles, 0.0, 100.0, &zone(ZoneSpacing::Atr));
+        assert_levels(levels, &[41.0, 44.0, 47.0, 50.0, 53.0, 56.0, 59.0]);
+    }
+
+    #[test]
+    fn levels_out_of_order_yield_no_trade() {
+        let rule = BiasRule::default();
+        let candles: Vec<Candlestick> = (0..required_candles(&zone(ZoneSpacing::Log), &rule)).map(|_| candle(50.0)).collect();
+
+        let log = generate_trade("ETHUSDT".to_string(), &candles, &candles, &zone(ZoneSpacing::Log), &rule);
+        assert!(log.is_ok());
+
+        // Steps of 2 ATR = 4 push zone_1 and zone_7 past the 49..51 range.
+        let wide = ZoneSettings { atr_multiplier: 2.0, ..zone(ZoneSpacing::Atr) };
+        let error = generate_trade("ETHUSDT".to_string(), &candles, &candles, &wide, &rule).unwrap_err();
+        assert!(error.starts_with("niveis fora de ordem"), "{}", error);
+    }
+
+    #[test]
+    fn role_level_maps_zone_k_to_the_same_relative_height() {
+        let seven = zone_levels(&[], 0.0, 80.0, &zone(ZoneSpacing::Linear));
+        for role in 1..=7 {
+            assert_eq!(role_level(&seven, role, 7), role as f64 * 10.0);
+        }
+
+        let ranks = |count: usize| (0..=8).map(|role| zone_rank(role, count)).collect::<Vec<_>>();
+        assert_eq!(ranks(5), [0, 1, 2, 2, 3, 4, 5, 5, 6]);
+        assert_eq!(ranks(9), [0, 1, 3, 4, 5, 6, 8, 9, 10]);
+
+        let five = zone_levels(&[], 0.0, 60.0, &ZoneSettings { count: 5, ..zone(ZoneSpacing::Linear) });
+        let roles: Vec<f64> = (1..=7).map(|role| role_level(&five, role, 5)).collect();
+        assert_eq!(roles, [10.0, 20.0, 20.0, 30.0, 40.0, 50.0, 50.0]);
+    }
+}
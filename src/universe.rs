@@ -0,0 +1,230 @@
+use crate::binance::{get_exchange_info, get_spot_exchange_info, get_tickers_24h};
+use crate::blockchain::get_current_blockchain_symbols;
+use crate::config::Settings;
+use crate::dto::{SpotExchangeInfo, SymbolInfo, Ticker24h};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::task::JoinHandle;
+
+static UNIVERSE: Lazy<Arc<Universe>> = Lazy::new(|| Arc::new(Universe::new(&Settings::load().cryptos)));
+
+/// A symbol left out of the universe, and why.
+#[derive(Debug, Serialize, Clone)]
+pub struct Quarantined {
+    pub symbol: String,
+    pub reason: String,
+}
+
+/// Outcome of the last check of the symbol universe.
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct UniverseReport {
+    /// "configured" (`cryptos`) or "discovered" (top N by volume).
+    pub source: String,
+    pub symbols: Vec<String>,
+    pub duplicates: Vec<String>,
+    pub quarantined: Vec<Quarantined>,
+    /// Seconds since the epoch; zero until the first check succeeds.
+    pub refreshed_at: u64,
+    /// Error of the last check; the previous symbols are kept meanwhile.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// The symbols the engine spies on, checked against spot and futures
+/// `/exchangeInfo`. Until the first check, the deduplicated `cryptos`.
+pub struct Universe {
+    report: Mutex<UniverseReport>,
+    generation: AtomicU64,
+}
+
+impl Universe {
+    fn new(configured: &[String]) -> Self {
+        let (symbols, duplicates) = dedupe(configured);
+        Universe {
+            report: Mutex::new(UniverseReport {
+                source: "configured".to_string(),
+                symbols,
+                duplicates,
+                ..Default::default()
+            }),
+            generation: AtomicU64::new(0),
+        }
+    }
+
+    pub fn symbols(&self) -> Vec<String> {
+        self.report.lock().unwrap().symbols.clone()
+    }
+
+    pub fn report(&self) -> UniverseReport {
+        self.report.lock().unwrap().clone()
+    }
+
+    /// Bumped whenever the symbols change, so the stream knows to resubscribe.
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::SeqCst)
+    }
+
+    /// Checks the universe against the exchange. On error the current
+    /// symbols are kept and the error is recorded in the report.
+    pub async fn refresh(&self, settings: &Settings) -> Result<UniverseReport, String> {
+        match build_report(settings).await {
+            Ok(report) => {
+                let mut current = self.report.lock().unwrap();
+                if current.symbols != report.symbols {
+                    self.generation.fetch_add(1, Ordering::SeqCst);
+                }
+                *current = report.clone();
+                Ok(report)
+            }
+            Err(e) => {
+                self.report.lock().unwrap().error = Some(e.clone());
+                Err(e)
+            }
+        }
+    }
+}
+
+pub fn get_universe() -> Arc<Universe> {
+    UNIVERSE.clone()
+}
+
+/// The universe plus every symbol with an open chain, so a chain is still
+/// followed after its symbol leaves the universe.
+pub fn watched_symbols() -> Vec<String> {
+    let mut symbols = get_universe().symbols();
+    for symbol in get_current_blockchain_symbols() {
+        if !symbols.contains(&symbol) {
+            symbols.push(symbol);
+        }
+    }
+    symbols
+}
+
+/// Checks the universe now and then every `universe.refresh_secs`.
+pub async fn start_universe(settings: &Settings) -> JoinHandle<()> {
+    refresh_and_report(settings).await;
+
+    let settings = settings.clone();
+    let period = Duration::from_secs(settings.universe.refresh_secs.max(60));
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(period);
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            refresh_and_report(&settings).await;
+        }
+    })
+}
+
+async fn refresh_and_report(settings: &Settings) {
+    match get_universe().refresh(settings).await {
+        Ok(report) => {
+            println!(
+                "Universo ({}): {} simbolo(s) ativo(s), {} em quarentena",
+                report.source,
+                report.symbols.len(),
+                report.quarantined.len()
+            );
+            if !report.duplicates.is_empty() {
+                println!("Simbolos repetidos em cryptos ignorados: {:?}", report.duplicates);
+            }
+            for q in &report.quarantined {
+                println!("Quarentena {}: {}", q.symbol, q.reason);
+            }
+        }
+        Err(e) => eprintln!("Erro ao validar universo de simbolos, mantendo lista atual: {}", e),
+    }
+}
+
+async fn build_report(settings: &Settings) -> Result<UniverseReport, String> {
+    let futures = get_exchange_info(&settings.binance).await?;
+    let spot = get_spot_exchange_info(&settings.binance).await?;
+    let futures: HashMap<&str, &SymbolInfo> = futures.symbols.iter().map(|s| (s.symbol.as_str(), s)).collect();
+    let spot = spot_status(&spot);
+    let check = |symbol: &str| rejection(symbol, &futures, &spot);
+
+    let (source, symbols, duplicates, quarantined) = if settings.universe.discover_top > 0 {
+        let tickers = get_tickers_24h(&settings.binance).await?;
+        let symbols = top_by_volume(tickers, settings.universe.discover_top, |s| check(s).is_none());
+        ("discovered", symbols, Vec::new(), Vec::new())
+    } else {
+        let (candidates, duplicates) = dedupe(&settings.cryptos);
+        let mut symbols = Vec::new();
+        let mut quarantined = Vec::new();
+        for symbol in candidates {
+            match check(&symbol) {
+                Some(reason) => quarantined.push(Quarantined { symbol, reason }),
+                None => symbols.push(symbol),
+            }
+        }
+        ("configured", symbols, duplicates, quarantined)
+    };
+
+    Ok(UniverseReport {
+        source: source.to_string(),
+        symbols,
+        duplicates,
+        quarantined,
+        refreshed_at: SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs(),
+        error: None,
+    })
+}
+
+fn spot_status(info: &SpotExchangeInfo) -> HashMap<&str, &str> {
+    info.symbols.iter().map(|s| (s.symbol.as_str(), s.status.as_str())).collect()
+}
+
+/// Why `symbol` cannot be traded: futures orders need a USDT perpetual that
+/// is trading, and the candles come from the spot pair.
+fn rejection(symbol: &str, futures: &HashMap<&str, &SymbolInfo>, spot: &HashMap<&str, &str>) -> Option<String> {
+    let Some(contract) = futures.get(symbol) else {
+        return Some("nao listado em futuros USDT-M".to_string());
+    };
+    if !contract.contract_type.is_empty() && contract.contract_type != "PERPETUAL" {
+        return Some(format!("contrato {} nao e perpetuo", contract.contract_type));
+    }
+    if !contract.quote_asset.is_empty() && contract.quote_asset != "USDT" {
+        return Some(format!("margem em {} e nao USDT", contract.quote_asset));
+    }
+    if contract.status != "TRADING" {
+        return Some(format!("futuros com status {}", contract.status));
+    }
+    match spot.get(symbol) {
+        None => Some("sem par spot, candles indisponiveis".to_string()),
+        Some(status) if *status != "TRADING" => Some(format!("spot com status {}", status)),
+        Some(_) => None,
+    }
+}
+
+/// The first `n` tradable symbols by 24h quote volume, highest first.
+fn top_by_volume(mut tickers: Vec<Ticker24h>, n: usize, tradable: impl Fn(&str) -> bool) -> Vec<String> {
+    let volume = |t: &Ticker24h| t.quote_volume.parse::<f64>().unwrap_or(0.0);
+    tickers.sort_by(|a, b| volume(b).total_cmp(&volume(a)).then(a.symbol.cmp(&b.symbol)));
+    tickers
+        .into_iter()
+        .filter(|t| tradable(&t.symbol))
+        .take(n)
+        .map(|t| t.symbol)
+        .collect()
+}
+
+/// `configured` upper-cased, in order, without repeats; the repeats are
+/// returned apart.
+fn dedupe(configured: &[String]) -> (Vec<String>, Vec<String>) {
+    let mut seen = HashSet::new();
+    let mut symbols = Vec::new();
+    let mut duplicates = Vec::new();
+    for symbol in configured {
+        let symbol = symbol.trim().to_uppercase();
+        if seen.insert(symbol.clone()) {
+            symbols.push(symbol);
+        } else {
+            duplicates.push(symbol);
+        }
+    }
+    (symbols, duplicates)
+}
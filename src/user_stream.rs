@@ -0,0 +1,473 @@
+use crate::balance::get_futures_balance;
+use crate::binance::get_position_risk;
+use crate::client::get_binance_client;
+use crate::config::Settings;
+use crate::dto::{AccountSnapshot, BalanceResponse, OrderResponse, PositionResponse};
+use crate::order::get_open_orders;
+use futures::{SinkExt, StreamExt};
+use once_cell::sync::Lazy;
+use reqwest::Method;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::task::JoinHandle;
+use tokio_tungstenite::{connect_async, tungstenite::Message};
+
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(30 * 60);
+const IDLE_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+static ACCOUNT_VIEW: Lazy<Arc<AccountView>> = Lazy::new(|| Arc::new(AccountView::new()));
+
+/// Live copy of the futures account, seeded from REST and then driven by
+/// the user data stream. Every getter returns `None` while the stream is
+/// down so callers fall back to REST.
+pub struct AccountView {
+    state: Mutex<AccountState>,
+}
+
+#[derive(Default)]
+struct AccountState {
+    synced: bool,
+    updated_at: u64,
+    positions: HashMap<String, PositionResponse>,
+    balances: HashMap<String, BalanceResponse>,
+    orders: HashMap<u64, OrderResponse>,
+    /// Futures mark prices from the `!markPrice@arr` stream.
+    marks: HashMap<String, f64>,
+}
+
+impl AccountView {
+    fn new() -> Self {
+        AccountView {
+            state: Mutex::new(AccountState::default()),
+        }
+    }
+
+    /// Positions with `markPrice` and `unRealizedProfit` refreshed from the
+    /// futures mark price stream, since `ACCOUNT_UPDATE` only arrives when
+    /// the account itself changes.
+    pub fn positions(&self) -> Option<Vec<PositionResponse>> {
+        let state = self.state.lock().unwrap();
+        if !state.synced {
+            return None;
+        }
+
+        let mut positions: Vec<PositionResponse> = state.positions.values().cloned().collect();
+        for position in positions.iter_mut() {
+            let amount = position.position_amt.parse::<f64>().unwrap_or(0.0);
+            let entry = position.entry_price.parse::<f64>().unwrap_or(0.0);
+
+            if let Some(&price) = state.marks.get(&position.symbol)
+                && amount != 0.0
+            {
+                position.mark_price = price.to_string();
+                position.unrealized_profit = (amount * (price - entry)).to_string();
+            }
+        }
+        positions.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+        Some(positions)
+    }
+
+    pub fn balances(&self) -> Option<Vec<BalanceResponse>> {
+        let state = self.state.lock().unwrap();
+        state.synced.then(|| state.balances.values().cloned().collect())
+    }
+
+    pub fn has_open_orders(&self, symbol: &str) -> Option<bool> {
+        let state = self.state.lock().unwrap();
+        state.synced.then(|| state.orders.values().any(|o| o.symbol == symbol))
+    }
+
+    pub fn snapshot(&self) -> Option<AccountSnapshot> {
+        let positions = self.positions()?;
+        let state = self.state.lock().unwrap();
+
+        let mut open_orders: Vec<OrderResponse> = state.orders.values().cloned().collect();
+        open_orders.sort_by_key(|o| o.order_id);
+
+        Some(AccountSnapshot {
+            source: "stream".to_string(),
+            updated_at: state.updated_at,
+            balances: state.balances.values().cloned().collect(),
+            positions: positions
+                .into_iter()
+                .filter(|p| p.position_amt.parse::<f64>().unwrap_or(0.0) != 0.0)
+                .collect(),
+            open_orders,
+        })
+    }
+
+    fn reset(&self, positions: Vec<PositionResponse>, balances: Vec<BalanceResponse>, orders: Vec<OrderResponse>) {
+        let mut state = self.state.lock().unwrap();
+        state.positions = positions.into_iter().map(|p| (p.symbol.clone(), p)).collect();
+        state.balances = balances.into_iter().map(|b| (b.asset.clone(), b)).collect();
+        state.orders = orders.into_iter().map(|o| (o.order_id, o)).collect();
+        state.updated_at = now_ms();
+        state.synced = true;
+    }
+
+    fn set_balances(&self, balances: Vec<BalanceResponse>) {
+        let mut state = self.state.lock().unwrap();
+        state.balances = balances.into_iter().map(|b| (b.asset.clone(), b)).collect();
+    }
+
+    fn set_marks(&self, updates: Vec<MarkPriceUpdate>) {
+        let mut state = self.state.lock().unwrap();
+        for update in updates {
+            if let Ok(price) = update.mark_price.parse::<f64>() {
+                state.marks.insert(update.symbol, price);
+            }
+        }
+    }
+
+    fn mark_stale(&self) {
+        self.state.lock().unwrap().synced = false;
+    }
+
+    fn apply(&self, event: UserEvent) {
+        let mut state = self.state.lock().unwrap();
+
+        match event {
+            UserEvent::OrderTradeUpdate { event_time, order } => {
+                if order.last_filled_qty.parse::<f64>().unwrap_or(0.0) > 0.0 {
+                    println!(
+                        "Fill {} {} {} @ {} (order {} {}, realized {})",
+                        order.symbol,
+                        order.side,
+                        order.last_filled_qty,
+                        order.last_filled_price,
+                        order.order_id,
+                        order.status,
+                        order.realized_profit
+                    );
+                }
+
+                match order.status.as_str() {
+                    "NEW" | "PARTIALLY_FILLED" => {
+                        state.orders.insert(order.order_id, (*order).into());
+                    }
+                    _ => {
+                        state.orders.remove(&order.order_id);
+                    }
+                }
+                state.updated_at = event_time;
+            }
+
+            UserEvent::AccountUpdate { event_time, account } => {
+                for balance in account.balances {
+                    let entry = state.balances.entry(balance.asset.clone()).or_insert(BalanceResponse {
+                        asset: balance.asset,
+                        total: "0".to_string(),
+                        available: "0".to_string(),
+                    });
+                    // The event carries no available balance; the session
+                    // re-reads it from REST right after.
+                    entry.total = balance.wallet_balance;
+                }
+
+                for update in account.positions {
+                    let position = state
+                        .positions
+                        .entry(update.symbol.clone())
+                        .or_insert_with(|| PositionResponse {
+                            symbol: update.symbol.clone(),
+                            position_amt: "0".to_string(),
+                            entry_price: "0".to_string(),
+                            mark_price: "0".to_string(),
+                            unrealized_profit: "0".to_string(),
+                            leverage: String::new(),
+                        });
+                    position.position_amt = update.position_amt;
+                    position.entry_price = update.entry_price;
+                    position.unrealized_profit = update.unrealized_profit;
+                }
+                state.updated_at = event_time;
+            }
+
+            UserEvent::MarginCall { event_time, positions } => {
+                for call in positions {
+                    eprintln!(
+                        "MARGIN CALL {}: amount {}, mark {}, unrealized {}, maintenance {}",
+                        call.symbol, call.position_amt, call.mark_price, call.unrealized_profit, call.maintenance_margin
+                    );
+                    if let Some(position) = state.positions.get_mut(&call.symbol) {
+                        position.mark_price = call.mark_price;
+                        position.unrealized_profit = call.unrealized_profit;
+                    }
+                }
+                state.updated_at = event_time;
+            }
+
+            UserEvent::ListenKeyExpired | UserEvent::Other => {}
+        }
+    }
+}
+
+pub fn get_account_view() -> Arc<AccountView> {
+    ACCOUNT_VIEW.clone()
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "e")]
+enum UserEvent {
+    #[serde(rename = "ORDER_TRADE_UPDATE")]
+    OrderTradeUpdate {
+        #[serde(rename = "E")]
+        event_time: u64,
+        #[serde(rename = "o")]
+        order: Box<OrderUpdate>,
+    },
+    #[serde(rename = "ACCOUNT_UPDATE")]
+    AccountUpdate {
+        #[serde(rename = "E")]
+        event_time: u64,
+        #[serde(rename = "a")]
+        account: AccountUpdate,
+    },
+    #[serde(rename = "MARGIN_CALL")]
+    MarginCall {
+        #[serde(rename = "E")]
+        event_time: u64,
+        #[serde(rename = "p")]
+        positions: Vec<MarginCallPosition>,
+    },
+    #[serde(rename = "listenKeyExpired")]
+    ListenKeyExpired,
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Deserialize)]
+struct OrderUpdate {
+    #[serde(rename = "s")]
+    symbol: String,
+    #[serde(rename = "S")]
+    side: String,
+    #[serde(rename = "o")]
+    order_type: String,
+    #[serde(rename = "f")]
+    time_in_force: String,
+    #[serde(rename = "q")]
+    orig_qty: String,
+    #[serde(rename = "p")]
+    price: String,
+    #[serde(rename = "X")]
+    status: String,
+    #[serde(rename = "i")]
+    order_id: u64,
+    #[serde(rename = "l")]
+    last_filled_qty: String,
+    #[serde(rename = "z")]
+    executed_qty: String,
+    #[serde(rename = "L")]
+    last_filled_price: String,
+    #[serde(rename = "ap", default)]
+    avg_price: String,
+    #[serde(rename = "T")]
+    trade_time: u64,
+    #[serde(rename = "rp", default)]
+    realized_profit: String,
+}
+
+impl From<OrderUpdate> for OrderResponse {
+    fn from(o: OrderUpdate) -> Self {
+        OrderResponse {
+            symbol: o.symbol,
+            order_id: o.order_id,
+            status: o.status,
+            side: o.side,
+            price: o.price,
+            orig_qty: o.orig_qty,
+            executed_qty: o.executed_qty,
+            cummulative_quote_qty: None,
+            avg_price: Some(o.avg_price).filter(|p| !p.is_empty()),
+            time_in_force: o.time_in_force,
+            order_type: o.order_type,
+            update_time: o.trade_time,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct AccountUpdate {
+    #[serde(rename = "B", default)]
+    balances: Vec<BalanceUpdate>,
+    #[serde(rename = "P", default)]
+    positions: Vec<PositionUpdate>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BalanceUpdate {
+    #[serde(rename = "a")]
+    asset: String,
+    #[serde(rename = "wb")]
+    wallet_balance: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct PositionUpdate {
+    #[serde(rename = "s")]
+    symbol: String,
+    #[serde(rename = "pa")]
+    position_amt: String,
+    #[serde(rename = "ep")]
+    entry_price: String,
+    #[serde(rename = "up")]
+    unrealized_profit: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct MarginCallPosition {
+    #[serde(rename = "s")]
+    symbol: String,
+    #[serde(rename = "pa")]
+    position_amt: String,
+    #[serde(rename = "mp")]
+    mark_price: String,
+    #[serde(rename = "up")]
+    unrealized_profit: String,
+    #[serde(rename = "mm")]
+    maintenance_margin: String,
+}
+
+/// One entry of the `!markPrice@arr` stream.
+#[derive(Debug, Deserialize)]
+struct MarkPriceUpdate {
+    #[serde(rename = "s")]
+    symbol: String,
+    #[serde(rename = "p")]
+    mark_price: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ListenKey {
+    #[serde(rename = "listenKey")]
+    listen_key: String,
+}
+
+/// Keeps `AccountView` in sync for as long as the process runs, opening a
+/// new listenKey and re-seeding from REST after every disconnect.
+pub fn start_user_stream(settings: Settings) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let view = get_account_view();
+        let mut backoff = Duration::from_secs(1);
+
+        loop {
+            match run_session(&view, &settings).await {
+                Ok(()) => {
+                    println!("User data stream closed, reconnecting...");
+                    backoff = Duration::from_secs(1);
+                }
+                Err(e) => eprintln!("User data stream error: {}", e),
+            }
+            view.mark_stale();
+
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(MAX_BACKOFF);
+        }
+    })
+}
+
+async fn run_session(view: &AccountView, settings: &Settings) -> Result<(), String> {
+    let client = get_binance_client();
+    let listen_key_url = format!("{}/listenKey", settings.binance.future_url);
+
+    let ListenKey { listen_key } = client.keyed(Method::POST, &listen_key_url).await?;
+    let (mut socket, _) = connect_async(format!("{}/{}", settings.stream.user_url, listen_key))
+        .await
+        .map_err(|e| e.to_string())?;
+    let (mut marks, _) = connect_async(format!("{}/!markPrice@arr@1s", settings.stream.user_url))
+        .await
+        .map_err(|e| e.to_string())?;
+
+    // Seed only after subscribing, so nothing happens between the snapshot
+    // and the first event. Events older than the snapshot are skipped; the
+    // cut-off is in server time because `event_time` is Binance's clock.
+    let seeded_at = client.server_time().await;
+    let positions = get_position_risk(&settings.binance).await?;
+    let balances = get_futures_balance(&settings.binance).await.map_err(|e| e.to_string())?;
+    let orders = get_open_orders(&settings.binance).await?;
+    view.reset(positions, balances, orders);
+    println!("User data stream connected");
+
+    let mut keepalive = tokio::time::interval(KEEPALIVE_INTERVAL);
+    keepalive.tick().await;
+    let mut last_message = tokio::time::Instant::now();
+
+    loop {
+        let message = tokio::select! {
+            _ = keepalive.tick() => {
+                client.keyed::<serde_json::Value>(Method::PUT, &listen_key_url).await?;
+                continue;
+            }
+            mark = marks.next() => {
+                match mark {
+                    Some(Ok(Message::Text(text))) => match serde_json::from_str::<Vec<MarkPriceUpdate>>(&text) {
+                        Ok(updates) => view.set_marks(updates),
+                        Err(e) => eprintln!("Ignoring mark price message: {}", e),
+                    },
+                    Some(Ok(Message::Ping(payload))) => {
+                        marks.send(Message::Pong(payload)).await.map_err(|e| e.to_string())?;
+                    }
+                    Some(Ok(Message::Close(_))) | None => return Err("mark price stream closed".to_string()),
+                    Some(Ok(_)) => {}
+                    Some(Err(e)) => return Err(e.to_string()),
+                }
+                continue;
+            }
+            _ = tokio::time::sleep_until(last_message + IDLE_TIMEOUT) => {
+                return Err(format!("no data for {}s", IDLE_TIMEOUT.as_secs()));
+            }
+            message = socket.next() => message,
+        };
+        last_message = tokio::time::Instant::now();
+
+        let message = match message {
+            Some(message) => message.map_err(|e| e.to_string())?,
+            None => return Ok(()),
+        };
+
+        let text = match message {
+            Message::Text(text) => text,
+            Message::Ping(payload) => {
+                socket.send(Message::Pong(payload)).await.map_err(|e| e.to_string())?;
+                continue;
+            }
+            Message::Close(_) => return Ok(()),
+            _ => continue,
+        };
+
+        let event = match serde_json::from_str::<UserEvent>(&text) {
+            Ok(event) => event,
+            Err(e) => {
+                eprintln!("Ignoring user data message: {}", e);
+                continue;
+            }
+        };
+
+        match event {
+            UserEvent::ListenKeyExpired => return Err("listenKey expired".to_string()),
+            UserEvent::OrderTradeUpdate { event_time, .. }
+            | UserEvent::AccountUpdate { event_time, .. }
+            | UserEvent::MarginCall { event_time, .. }
+                if event_time < seeded_at => {}
+            UserEvent::AccountUpdate { .. } => {
+                view.apply(event);
+                match get_futures_balance(&settings.binance).await {
+                    Ok(balances) => view.set_balances(balances),
+                    Err(e) => eprintln!("Erro ao atualizar saldo disponivel: {}", e),
+                }
+            }
+            event => view.apply(event),
+        }
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64
+}
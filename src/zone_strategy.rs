@@ -0,0 +1,72 @@
+use crate::blockchain::CloseReason;
+use crate::config::{BiasSettings, ZoneSettings};
+use crate::crypto_candidate::entry_zone;
+use crate::dto::{Bias, Candlestick, Trade, TradeStatus};
+use crate::selection::FilterCheck;
+use crate::state_machine::StateMachine;
+use crate::strategy::{Strategy, StrategyAction};
+use crate::trade::generate_trade;
+
+/// The default strategy: price zones over the last candles (geometry from
+/// `[strategy.zone]`), a moving-average bias (`[strategy.bias]`) and the
+/// zone state machine (`strategy.zone.machine`).
+pub struct ZoneStrategy {
+    pub settings: ZoneSettings,
+    pub bias: BiasSettings,
+    pub machine: StateMachine,
+}
+
+impl Strategy for ZoneStrategy {
+    fn name(&self) -> &'static str {
+        "zone"
+    }
+
+    fn analyse(&self, symbol: &str, candles: &[Candlestick], reference: &[Candlestick]) -> Result<Trade, String> {
+        generate_trade(symbol.to_string(), candles, reference, &self.settings, &self.bias.rule_for(symbol))
+    }
+
+    fn reference_symbol(&self, symbol: &str) -> String {
+        self.bias.rule_for(symbol).reference_for(symbol)
+    }
+
+    fn advance(&self, trade: Trade, history: &[Trade]) -> Trade {
+        self.machine.advance(trade, history)
+    }
+
+    fn candidate_filters(&self, trade: &Trade) -> Vec<FilterCheck> {
+        let zone = entry_zone(trade);
+        vec![
+            FilterCheck::new("bias", trade.bias != Bias::None, trade.bias.to_string()),
+            FilterCheck::new("entry_zone", zone.is_some(), zone.unwrap_or("preco fora das zonas de entrada")),
+        ]
+    }
+
+    /// `relative_strength`: 24h performance against the bias reference, in
+    /// the direction of the bias. `stretch`: distance below (bullish) or
+    /// above (bearish) the 200 average. `volume`: quote volume of the last
+    /// candle.
+    fn candidate_factors(&self, trade: &Trade) -> Vec<(&'static str, f64)> {
+        let direction = if trade.bias == Bias::Bearish { -1.0 } else { 1.0 };
+        vec![
+            ("relative_strength", direction * trade.performance_btc_24.to_f64()),
+            ("stretch", -direction * trade.amplitude_ma_200.to_f64()),
+            ("volume", trade.quote_asset_volume.to_f64()),
+        ]
+    }
+
+    fn factor_names(&self) -> &'static [&'static str] {
+        &["relative_strength", "stretch", "volume"]
+    }
+
+    fn actions(&self, trade: &Trade) -> Vec<StrategyAction> {
+        self.machine.actions(trade)
+    }
+
+    fn close_reason(&self, trade: &Trade, statuses: &[Option<TradeStatus>]) -> Option<CloseReason> {
+        self.machine.close_reason(trade, statuses)
+    }
+
+    fn graph(&self) -> String {
+        self.machine.to_dot()
+    }
+}